@@ -0,0 +1,274 @@
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// number of branches of [`crate::join2`] and the like, including the tasks
+/// of [`crate::MiniExecutor`], which can have a waker of their own at the same time
+///
+/// The wakers are kept in a static table, so they take no memory from the heap.
+/// A branch polled for the first time while the table is full shares the waker
+/// of the task or branch it belongs to, and is polled each time that one is polled.
+pub const MAX_BRANCHES: usize = if cfg!(feature = "std") { 256 } else { 64 };
+
+/// stands for no slot, e.g. for the parent of a task
+const NONE: usize = usize::MAX;
+
+/// the state behind the waker of a branch, see [`Branch`]
+struct Slot {
+    /// taken by a branch
+    claimed: AtomicBool,
+    /// set by the waker, cleared when the branch is polled
+    woken: AtomicBool,
+    /// set by the waker while the branch is not being polled,
+    /// i.e. by another branch or task, or by an expired timer
+    woken_by_other: AtomicBool,
+    polling: AtomicBool,
+    /// the slot of the task or branch this branch belongs to, or [`NONE`]
+    parent: AtomicUsize,
+}
+
+impl Slot {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: Slot = Slot {
+        claimed: AtomicBool::new(false),
+        woken: AtomicBool::new(false),
+        woken_by_other: AtomicBool::new(false),
+        polling: AtomicBool::new(false),
+        parent: AtomicUsize::new(NONE),
+    };
+}
+
+static SLOTS: [Slot; MAX_BRANCHES] = [Slot::FREE; MAX_BRANCHES];
+
+// the slot of the branch being polled, to recognize its waker
+#[cfg(feature = "std")]
+std::thread_local! {
+    static CURRENT: Cell<usize> = const { Cell::new(NONE) };
+}
+
+#[cfg(feature = "std")]
+fn current() -> usize {
+    CURRENT.with(Cell::get)
+}
+
+#[cfg(feature = "std")]
+fn set_current(index: usize) {
+    CURRENT.with(|current| current.set(index));
+}
+
+#[cfg(not(feature = "std"))]
+static CURRENT: AtomicUsize = AtomicUsize::new(NONE);
+
+#[cfg(not(feature = "std"))]
+fn current() -> usize {
+    CURRENT.load(Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "std"))]
+fn set_current(index: usize) {
+    CURRENT.store(index, Ordering::Relaxed);
+}
+
+/// The data pointer of the raw waker is the index of the slot, it is never dereferenced.
+/// Waking only sets flags in the static table, so a waker can be woken from anywhere.
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |data| RawWaker::new(data, &VTABLE),
+    |data| wake(data as usize),
+    |data| wake(data as usize),
+    |_| {},
+);
+
+/// the waker of the slot `index`, which does nothing for [`NONE`]
+fn waker(index: usize) -> Waker {
+    // SAFETY: the functions of the vtable are safe to call from anywhere, with any data
+    unsafe { Waker::from_raw(RawWaker::new(index as *const (), &VTABLE)) }
+}
+
+/// wakes the branch with the slot `index`, and the ones it belongs to
+pub(crate) fn wake(mut index: usize) {
+    // a parent left behind by a slot which has been given back may lead in a circle
+    for _ in 0..MAX_BRANCHES {
+        let Some(slot) = SLOTS.get(index) else {
+            return;
+        };
+        slot.woken.store(true, Ordering::Relaxed);
+        if !slot.polling.load(Ordering::Relaxed) {
+            slot.woken_by_other.store(true, Ordering::Relaxed);
+        }
+        index = slot.parent.load(Ordering::Relaxed);
+    }
+}
+
+/// returns the slot of `waker`, if it is the waker of the branch being polled
+///
+/// Within the `poll()` function of a branch, this is the case for the waker passed
+/// to the futures it awaits, unless they are polled through a combinator of another crate.
+pub(crate) fn slot_of(waker: &Waker) -> Option<usize> {
+    let current = current();
+    (current != NONE && waker.will_wake(&self::waker(current))).then_some(current)
+}
+
+/// returns `true` if the slot `index` has been taken by a branch
+pub(crate) fn is_claimed(index: usize) -> bool {
+    SLOTS[index].claimed.load(Ordering::Relaxed)
+}
+
+/// takes a free slot, which is woken, so the branch is polled the first time round
+fn claim() -> Option<usize> {
+    let index = SLOTS.iter().position(|slot| {
+        slot.claimed
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    })?;
+    let slot = &SLOTS[index];
+    slot.woken.store(true, Ordering::Relaxed);
+    slot.woken_by_other.store(false, Ordering::Relaxed);
+    slot.polling.store(false, Ordering::Relaxed);
+    slot.parent.store(NONE, Ordering::Relaxed);
+    Some(index)
+}
+
+/// gives back the slot `index`
+///
+/// Wakers of the slot still around only cause spurious wakes of the next branch taking it.
+fn release(index: usize) {
+    let slot = &SLOTS[index];
+    slot.woken.store(false, Ordering::Relaxed);
+    slot.woken_by_other.store(false, Ordering::Relaxed);
+    slot.polling.store(false, Ordering::Relaxed);
+    slot.parent.store(NONE, Ordering::Relaxed);
+    slot.claimed.store(false, Ordering::Release);
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    /// not polled yet
+    New,
+    /// shares the waker of the task or branch it belongs to,
+    /// because there was no free slot, or because that one is polled by another executor
+    Detached,
+    Slot(usize),
+}
+
+/// one branch of a combinator like [`crate::join2`], or a task of [`crate::MiniExecutor`],
+/// with a waker of its own
+///
+/// Waking the branch also wakes the task or branch it belongs to,
+/// so the combinators on the way down know which branches to poll.
+/// The waker refers to a slot of a static table, see [`MAX_BRANCHES`],
+/// which the branch takes when it is polled for the first time, and gives back when it is dropped.
+pub(crate) struct Branch {
+    mode: Cell<Mode>,
+    /// woken while the branch has no slot of its own
+    woken_detached: Cell<bool>,
+}
+
+impl Branch {
+    /// creates a branch, which is polled the first time round
+    pub(crate) const fn new() -> Self {
+        Self {
+            mode: Cell::new(Mode::New),
+            woken_detached: Cell::new(false),
+        }
+    }
+
+    /// creates the branch of a task, which does not belong to another branch
+    pub(crate) fn task() -> Self {
+        let branch = Self::new();
+        branch.mode.set(claim().map_or(Mode::Detached, Mode::Slot));
+        branch
+    }
+
+    /// wakes the branch as if by another branch
+    pub(crate) fn wake(&self) {
+        match self.mode.get() {
+            Mode::Slot(index) => wake(index),
+            _ => self.woken_detached.set(true),
+        }
+    }
+
+    /// returns `true` if the branch has been woken while it was not being polled
+    pub(crate) fn is_woken_by_other(&self) -> bool {
+        match self.mode.get() {
+            Mode::Slot(index) => SLOTS[index].woken_by_other.load(Ordering::Relaxed),
+            _ => self.woken_detached.get(),
+        }
+    }
+
+    /// runs `poll` with the waker of the branch, regardless of whether it has been woken
+    ///
+    /// A task without a slot of its own is polled with a waker which does nothing.
+    pub(crate) fn poll_with<T>(&self, poll: impl FnOnce(&mut Context<'_>) -> Poll<T>) -> Poll<T> {
+        let index = match self.mode.get() {
+            Mode::Slot(index) => index,
+            _ => NONE,
+        };
+        let slot = SLOTS.get(index);
+        if let Some(slot) = slot {
+            slot.woken.store(false, Ordering::Relaxed);
+            slot.woken_by_other.store(false, Ordering::Relaxed);
+            slot.polling.store(true, Ordering::Relaxed);
+        }
+        let outer = current();
+        set_current(index);
+        let result = poll(&mut Context::from_waker(&waker(index)));
+        set_current(outer);
+        if let Some(slot) = slot {
+            slot.polling.store(false, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// polls `future` if the branch has been woken since its latest poll
+    ///
+    /// `cx` belongs to the combinator, whose waker is woken together with the branch.
+    /// With `again`, the combinator polls its branches once more within the same `poll()`,
+    /// and only the branches woken by others are due. A branch that has woken
+    /// itself, e.g. by [`crate::yield_now`], waits for the next `poll()` of the combinator.
+    pub(crate) fn poll<F: Future>(
+        &self,
+        future: Pin<&mut F>,
+        cx: &mut Context<'_>,
+        again: bool,
+    ) -> Poll<F::Output> {
+        let outer = current();
+        let parent = cx.waker().will_wake(&waker(outer)).then_some(outer);
+        if let Mode::New = self.mode.get() {
+            // the waker of a combinator polled by another executor cannot be passed on
+            let slot = parent.and_then(|_| claim());
+            self.mode.set(slot.map_or(Mode::Detached, Mode::Slot));
+        }
+        match self.mode.get() {
+            Mode::Slot(index) => {
+                let slot = &SLOTS[index];
+                slot.parent.store(parent.unwrap_or(NONE), Ordering::Relaxed);
+                let due = if again {
+                    slot.woken_by_other.load(Ordering::Relaxed)
+                } else {
+                    slot.woken.load(Ordering::Relaxed)
+                };
+                if !due {
+                    return Poll::Pending;
+                }
+                self.poll_with(|cx| future.poll(cx))
+            }
+            _ => {
+                if again && !self.woken_detached.get() {
+                    return Poll::Pending;
+                }
+                self.woken_detached.set(false);
+                future.poll(cx)
+            }
+        }
+    }
+}
+
+impl Drop for Branch {
+    fn drop(&mut self) {
+        if let Mode::Slot(index) = self.mode.get() {
+            release(index);
+        }
+    }
+}
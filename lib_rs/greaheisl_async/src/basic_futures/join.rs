@@ -1,3 +1,4 @@
+use super::branch::Branch;
 use core::future::Future;
 use core::pin::{pin, Pin};
 use core::task::{Context, Poll};
@@ -9,6 +10,12 @@ use pin_project::pin_project;
 /// `poll()` of `future1` and `future2` are invoked in sequence,
 /// until results have been collected from both futures.
 /// The return value is a tuple with the return values from `future1` und `future2`.
+///
+/// Each future has a waker of its own, and is only polled if it has been woken,
+/// see [`crate::MiniExecutor::step`].
+/// When one of the futures completes before the other, the other one is woken,
+/// so it gets to see the completion right away: often, it waits for a stop signal
+/// set by the completed one, e.g. a `Cell<bool>` which does not wake anyone.
 pub async fn join2<F1: Future<Output = T>, F2: Future<Output = U>, T, U>(
     future1: F1,
     future2: F2,
@@ -18,6 +25,8 @@ pub async fn join2<F1: Future<Output = T>, F2: Future<Output = U>, T, U>(
     let pair = Join2 {
         future1,
         future2,
+        branch1: Branch::new(),
+        branch2: Branch::new(),
         retval1: None,
         retval2: None,
     };
@@ -30,33 +39,291 @@ struct Join2<F1, F2, T, U> {
     future1: F1,
     #[pin]
     future2: F2,
+    branch1: Branch,
+    branch2: Branch,
     retval1: Option<T>,
     retval2: Option<U>,
 }
 
-impl<'a, F1: Future<Output = T>, F2: Future<Output = U>, T, U> Future for Join2<F1, F2, T, U> {
+impl<F1: Future<Output = T>, F2: Future<Output = U>, T, U> Future for Join2<F1, F2, T, U> {
     type Output = (T, U);
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-        if this.retval1.is_none() {
-            let res = this.future1.poll(cx);
-            if let Poll::Ready(retval) = res {
-                *this.retval1 = Some(retval);
+        let mut this = self.project();
+        let mut again = false;
+        loop {
+            let mut completed = false;
+            if this.retval1.is_none() {
+                let res = this.branch1.poll(this.future1.as_mut(), cx, again);
+                if let Poll::Ready(retval) = res {
+                    *this.retval1 = Some(retval);
+                    completed = true;
+                }
+            }
+            if this.retval2.is_none() {
+                let res = this.branch2.poll(this.future2.as_mut(), cx, again);
+                if let Poll::Ready(retval) = res {
+                    *this.retval2 = Some(retval);
+                    completed = true;
+                }
+            }
+            if this.retval1.is_some() && this.retval2.is_some() {
+                let retval1 = this.retval1.take().unwrap();
+                let retval2 = this.retval2.take().unwrap();
+                return Poll::Ready((retval1, retval2));
+            }
+            if completed {
+                this.branch1.wake();
+                this.branch2.wake();
+            }
+            // a future woken by the other one is polled again right away
+            if !(this.retval1.is_none() && this.branch1.is_woken_by_other()
+                || this.retval2.is_none() && this.branch2.is_woken_by_other())
+            {
+                return Poll::Pending;
             }
+            again = true;
         }
-        if this.retval2.is_none() {
-            let res = this.future2.poll(cx);
-            if let Poll::Ready(retval) = res {
-                *this.retval2 = Some(retval);
+    }
+}
+
+/// defines a function `joinN` like [`join2`] for more futures
+macro_rules! define_join {
+    ($name:ident, $number:literal, $join:ident, $(($F:ident, $T:ident, $future:ident, $branch:ident, $retval:ident)),+) => {
+        #[doc = concat!("runs ", $number, " futures \"in parallel\"")]
+        ///
+        /// Like [`join2`], the futures are polled in sequence
+        /// until results have been collected from all of them,
+        /// and the others are woken when one of them completes.
+        /// The return value is a tuple with the return values in the same order.
+        /// See also [`crate::join!`].
+        #[allow(clippy::too_many_arguments)]
+        pub async fn $name<$($F: Future<Output = $T>, $T),+>($($future: $F),+) -> ($($T),+) {
+            $(let $future = pin!($future);)+
+            $join {
+                $($future, $branch: Branch::new(), $retval: None,)+
             }
+            .await
         }
-        if this.retval1.is_some() && this.retval2.is_some() {
-            let retval1 = this.retval1.take().unwrap();
-            let retval2 = this.retval2.take().unwrap();
-            Poll::Ready((retval1, retval2))
-        } else {
-            Poll::Pending
+
+        #[pin_project]
+        struct $join<$($F, $T),+> {
+            $(#[pin] $future: $F, $branch: Branch, $retval: Option<$T>,)+
+        }
+
+        impl<$($F: Future<Output = $T>, $T),+> Future for $join<$($F, $T),+> {
+            type Output = ($($T),+);
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                let mut this = self.project();
+                let mut again = false;
+                loop {
+                    let mut completed = false;
+                    $(
+                        if this.$retval.is_none() {
+                            let res = this.$branch.poll(this.$future.as_mut(), cx, again);
+                            if let Poll::Ready(retval) = res {
+                                *this.$retval = Some(retval);
+                                completed = true;
+                            }
+                        }
+                    )+
+                    if $(this.$retval.is_some())&&+ {
+                        return Poll::Ready(($(this.$retval.take().unwrap()),+));
+                    }
+                    if completed {
+                        $(this.$branch.wake();)+
+                    }
+                    if !($(this.$retval.is_none() && this.$branch.is_woken_by_other())||+) {
+                        return Poll::Pending;
+                    }
+                    again = true;
+                }
+            }
+        }
+    };
+}
+
+define_join!(
+    join3,
+    "three",
+    Join3,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3)
+);
+define_join!(
+    join4,
+    "four",
+    Join4,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3),
+    (F4, T4, future4, branch4, retval4)
+);
+define_join!(
+    join5,
+    "five",
+    Join5,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3),
+    (F4, T4, future4, branch4, retval4),
+    (F5, T5, future5, branch5, retval5)
+);
+define_join!(
+    join6,
+    "six",
+    Join6,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3),
+    (F4, T4, future4, branch4, retval4),
+    (F5, T5, future5, branch5, retval5),
+    (F6, T6, future6, branch6, retval6)
+);
+define_join!(
+    join7,
+    "seven",
+    Join7,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3),
+    (F4, T4, future4, branch4, retval4),
+    (F5, T5, future5, branch5, retval5),
+    (F6, T6, future6, branch6, retval6),
+    (F7, T7, future7, branch7, retval7)
+);
+define_join!(
+    join8,
+    "eight",
+    Join8,
+    (F1, T1, future1, branch1, retval1),
+    (F2, T2, future2, branch2, retval2),
+    (F3, T3, future3, branch3, retval3),
+    (F4, T4, future4, branch4, retval4),
+    (F5, T5, future5, branch5, retval5),
+    (F6, T6, future6, branch6, retval6),
+    (F7, T7, future7, branch7, retval7),
+    (F8, T8, future8, branch8, retval8)
+);
+
+/// runs 2 to 8 futures "in parallel", using [`join2`] ... [`join8`]
+///
+/// Evaluates to a future with a tuple of the results.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{join, InstantMillis, MiniExecutor, Timer};
+///
+/// let executor_builder = MiniExecutor::<()>::new(InstantMillis::from_absolute(0));
+/// let sys = executor_builder.scheduler().clone();
+/// let mut executor = executor_builder.build(async move {
+///     let (a, b, c) = join!(
+///         async { 1 },
+///         async { Timer::new(&sys, 20).wait().await; 'b' },
+///         async { "c" },
+///     )
+///     .await;
+///     assert_eq!((a, b, c), (1, 'b', "c"));
+/// });
+/// let mut time = InstantMillis::from_absolute(0);
+/// while let Some(delay) = executor.step(time, ()) {
+///     time += delay;
+/// }
+/// assert_eq!(time.into_inner(), 20);
+/// ```
+#[macro_export]
+macro_rules! join {
+    ($f1:expr, $f2:expr $(,)?) => {
+        $crate::join2($f1, $f2)
+    };
+    ($f1:expr, $f2:expr, $f3:expr $(,)?) => {
+        $crate::join3($f1, $f2, $f3)
+    };
+    ($f1:expr, $f2:expr, $f3:expr, $f4:expr $(,)?) => {
+        $crate::join4($f1, $f2, $f3, $f4)
+    };
+    ($f1:expr, $f2:expr, $f3:expr, $f4:expr, $f5:expr $(,)?) => {
+        $crate::join5($f1, $f2, $f3, $f4, $f5)
+    };
+    ($f1:expr, $f2:expr, $f3:expr, $f4:expr, $f5:expr, $f6:expr $(,)?) => {
+        $crate::join6($f1, $f2, $f3, $f4, $f5, $f6)
+    };
+    ($f1:expr, $f2:expr, $f3:expr, $f4:expr, $f5:expr, $f6:expr, $f7:expr $(,)?) => {
+        $crate::join7($f1, $f2, $f3, $f4, $f5, $f6, $f7)
+    };
+    ($f1:expr, $f2:expr, $f3:expr, $f4:expr, $f5:expr, $f6:expr, $f7:expr, $f8:expr $(,)?) => {
+        $crate::join8($f1, $f2, $f3, $f4, $f5, $f6, $f7, $f8)
+    };
+}
+
+/// runs an array of futures "in parallel"
+///
+/// Like [`join2`], the futures are polled in sequence
+/// until results have been collected from all of them,
+/// and the others are woken when one of them completes.
+/// Returns an array with the results in the same order.
+/// Unlike a `Vec` of futures, the array takes no memory from the heap.
+pub async fn join_array<F: Future<Output = T>, T, const N: usize>(futures: [F; N]) -> [T; N] {
+    let futures = pin!(futures);
+    JoinArray {
+        futures,
+        branches: core::array::from_fn(|_| Branch::new()),
+        retvals: core::array::from_fn(|_| None),
+    }
+    .await
+}
+
+#[pin_project]
+struct JoinArray<'a, F, T, const N: usize> {
+    futures: Pin<&'a mut [F; N]>,
+    branches: [Branch; N],
+    retvals: [Option<T>; N],
+}
+
+impl<F: Future<Output = T>, T, const N: usize> Future for JoinArray<'_, F, T, N> {
+    type Output = [T; N];
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut again = false;
+        loop {
+            let mut completed = false;
+            for (index, retval) in this.retvals.iter_mut().enumerate() {
+                if retval.is_some() {
+                    continue;
+                }
+                // SAFETY: the elements of a pinned array are pinned as well,
+                // none of them is moved out of the array
+                let future = unsafe {
+                    this.futures
+                        .as_mut()
+                        .map_unchecked_mut(|futures| &mut futures[index])
+                };
+                if let Poll::Ready(value) = this.branches[index].poll(future, cx, again) {
+                    *retval = Some(value);
+                    completed = true;
+                }
+            }
+            if this.retvals.iter().all(Option::is_some) {
+                return Poll::Ready(core::array::from_fn(|index| {
+                    this.retvals[index].take().unwrap()
+                }));
+            }
+            if completed {
+                this.branches.iter().for_each(Branch::wake);
+            }
+            let woken_by_other = this
+                .retvals
+                .iter()
+                .zip(this.branches.iter())
+                .any(|(retval, branch)| retval.is_none() && branch.is_woken_by_other());
+            if !woken_by_other {
+                return Poll::Pending;
+            }
+            again = true;
         }
     }
 }
@@ -1,7 +1,10 @@
 //! basic building blocks to fork and interrupt tasks
 
+pub(crate) mod branch;
 mod join;
+mod select;
 mod yield_now;
 
-pub use join::join2;
+pub use join::{join2, join3, join4, join5, join6, join7, join8, join_array};
+pub use select::{select2, select_n, Either};
 pub use yield_now::yield_now;
@@ -0,0 +1,115 @@
+use super::branch::Branch;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::task::{Context, Poll};
+use pin_project::pin_project;
+
+/// the result of [`select2`], telling which future has completed first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<T, U> {
+    First(T),
+    Second(U),
+}
+
+/// runs two futures "in parallel" until one of them completes
+///
+/// Each time `poll()` is invoked for this future,
+/// `poll()` of `future1` and `future2` are invoked in sequence,
+/// until one of them returns a result.
+/// The other future is dropped without being polled again.
+/// If both would complete in the same step, `future1` wins.
+///
+/// This replaces a stop signal set by one branch of [`super::join2`]
+/// and checked by the other one.
+pub async fn select2<F1: Future<Output = T>, F2: Future<Output = U>, T, U>(
+    future1: F1,
+    future2: F2,
+) -> Either<T, U> {
+    let future1 = pin!(future1);
+    let future2 = pin!(future2);
+    Select2 {
+        future1,
+        future2,
+        branch1: Branch::new(),
+        branch2: Branch::new(),
+    }
+    .await
+}
+
+#[pin_project]
+struct Select2<F1, F2> {
+    #[pin]
+    future1: F1,
+    #[pin]
+    future2: F2,
+    branch1: Branch,
+    branch2: Branch,
+}
+
+impl<F1: Future<Output = T>, F2: Future<Output = U>, T, U> Future for Select2<F1, F2> {
+    type Output = Either<T, U>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let mut again = false;
+        loop {
+            if let Poll::Ready(retval) = this.branch1.poll(this.future1.as_mut(), cx, again) {
+                return Poll::Ready(Either::First(retval));
+            }
+            if let Poll::Ready(retval) = this.branch2.poll(this.future2.as_mut(), cx, again) {
+                return Poll::Ready(Either::Second(retval));
+            }
+            // like in `join2`, a future woken by the other one is polled again right away
+            if !(this.branch1.is_woken_by_other() || this.branch2.is_woken_by_other()) {
+                return Poll::Pending;
+            }
+            again = true;
+        }
+    }
+}
+
+/// runs an array of futures "in parallel" until one of them completes
+///
+/// Returns the index of the future which has completed first, and its result.
+/// Like [`select2`], the futures are polled in sequence,
+/// the one with the lowest index wins and the others are dropped.
+pub async fn select_n<F: Future<Output = T>, T, const N: usize>(futures: [F; N]) -> (usize, T) {
+    let futures = pin!(futures);
+    SelectN {
+        futures,
+        branches: core::array::from_fn(|_| Branch::new()),
+    }
+    .await
+}
+
+struct SelectN<'a, F, const N: usize> {
+    futures: Pin<&'a mut [F; N]>,
+    branches: [Branch; N],
+}
+
+impl<F: Future<Output = T>, T, const N: usize> Future for SelectN<'_, F, N> {
+    type Output = (usize, T);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut again = false;
+        loop {
+            for index in 0..N {
+                // SAFETY: the elements of a pinned array are pinned as well,
+                // none of them is moved out of the array
+                let future = unsafe {
+                    this.futures
+                        .as_mut()
+                        .map_unchecked_mut(|futures| &mut futures[index])
+                };
+                if let Poll::Ready(retval) = this.branches[index].poll(future, cx, again) {
+                    return Poll::Ready((index, retval));
+                }
+            }
+            if !this.branches.iter().any(Branch::is_woken_by_other) {
+                return Poll::Pending;
+            }
+            again = true;
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use super::{with_timeout, AccessTiming, DurationMillis};
+use crate::waiters::Waiters;
+use core::cell::Cell;
+
+/// a queue of up to `N` values passed from one or more sending to receiving tasks
+///
+/// The channel is shared by reference, so any number of tasks,
+/// or branches of [`crate::join2`], can send and receive.
+/// The values and the waiting tasks are kept in place, so the channel takes no memory from the heap.
+/// Receiving tasks are woken when a value arrives,
+/// and sending tasks waiting for room are woken when a value is taken out.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{join2, Channel, InstantMillis, MiniExecutor, Timer};
+///
+/// let executor_builder = MiniExecutor::<()>::new(InstantMillis::from_absolute(0));
+/// let sys = executor_builder.scheduler().clone();
+/// let mut executor = executor_builder.build(async move {
+///     let channel = Channel::<u32, 2>::new();
+///     join2(
+///         async {
+///             for value in 1..=5 {
+///                 // waits while the channel is full
+///                 channel.send(value).await;
+///             }
+///         },
+///         async {
+///             let mut sum = 0;
+///             for _ in 1..=5 {
+///                 Timer::new(&sys, 10).wait().await;
+///                 sum += channel.receive().await;
+///             }
+///             assert_eq!(sum, 15);
+///         },
+///     )
+///     .await;
+/// });
+/// let mut time = InstantMillis::from_absolute(0);
+/// while let Some(delay) = executor.step(time, ()) {
+///     time += delay;
+/// }
+/// assert_eq!(time.into_inner(), 50);
+/// ```
+pub struct Channel<T, const N: usize> {
+    items: [Cell<Option<T>>; N],
+    first: Cell<usize>,
+    len: Cell<usize>,
+    receivers: Waiters,
+    senders: Waiters,
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Cell<Option<T>> = Cell::new(None);
+
+    /// creates an empty channel
+    pub const fn new() -> Self {
+        Self {
+            items: [Self::EMPTY; N],
+            first: Cell::new(0),
+            len: Cell::new(0),
+            receivers: Waiters::new(),
+            senders: Waiters::new(),
+        }
+    }
+    /// the number of values the channel can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+    /// the number of values in the channel
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+    /// returns `true` if there is no value in the channel
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// returns `true` if there is no room for another value
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+    /// appends `value` if there is room for it
+    ///
+    /// Otherwise, `value` is given back as error.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        let len = self.len.get();
+        self.items[(self.first.get() + len) % N].set(Some(value));
+        self.len.set(len + 1);
+        self.receivers.wake_all();
+        Ok(())
+    }
+    /// appends `value`, waiting until there is room for it
+    pub async fn send(&self, value: T) {
+        let mut value = Some(value);
+        self.senders
+            .wait_for(|| match self.try_send(value.take().unwrap()) {
+                Ok(()) => Some(()),
+                Err(rejected) => {
+                    value = Some(rejected);
+                    None
+                }
+            })
+            .await
+    }
+    /// takes out the oldest value, if any
+    pub fn try_receive(&self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let first = self.first.get();
+        let value = self.items[first].take();
+        self.first.set((first + 1) % N);
+        self.len.set(self.len.get() - 1);
+        self.senders.wake_all();
+        value
+    }
+    /// takes out the oldest value, waiting until there is one
+    pub async fn receive(&self) -> T {
+        self.receivers.wait_for(|| self.try_receive()).await
+    }
+    /// takes out the oldest value, waiting at most for `duration`
+    ///
+    /// Returns `None` if no value has arrived in time.
+    pub async fn receive_timeout(
+        &self,
+        sys: &impl AccessTiming,
+        duration: DurationMillis,
+    ) -> Option<T> {
+        with_timeout(sys, duration, self.receive()).await.ok()
+    }
+}
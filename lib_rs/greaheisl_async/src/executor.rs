@@ -1,39 +1,90 @@
+use super::basic_futures::branch::{self, Branch, MAX_BRANCHES};
 use super::DurationWrapper;
-use super::{AccessExecutorSignals, AccessTiming};
+use super::{AccessExecutorSignals, AccessTiming, SignalSet, Spawn, TaskTableFull};
 use super::{DurationMillis, InstantMillis};
 use alloc::boxed::Box;
 use alloc::rc::Rc;
 use core::cell::RefCell;
 use core::future::Future;
+use core::mem;
 use core::pin::Pin;
-use core::ptr;
-use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use core::task::{Context, Poll, Waker};
 
-pub struct MiniExecutorBuilder<X> {
-    scheduler: Rc<RefCell<MiniScheduler<X>>>,
+/// number of tasks a [`MiniExecutor`] can run at the same time, unless specified otherwise
+pub const DEFAULT_MAX_TASKS: usize = 4;
+
+pub struct MiniExecutorBuilder<X, const MAX_TASKS: usize = DEFAULT_MAX_TASKS> {
+    scheduler: Rc<RefCell<MiniScheduler<X, MAX_TASKS>>>,
 }
 
 /// simple executor to be integrated into the event loop of an embedded device
 ///
 /// You need to call the [`MiniExecutor::step`] function
-/// to run the single task managed by the executor.
-/// This task can fork into emulated parallel tasks
+/// to run the tasks managed by the executor.
+/// The main task is passed to [`MiniExecutorBuilder::build`].
+/// Up to `MAX_TASKS - 1` further tasks can be added at any time
+/// through the [`Spawn`] trait of the scheduler.
+/// Each task can fork into emulated parallel tasks
 /// using [`crate::basic_futures::join2()`].
 ///
-pub struct MiniExecutor<X> {
-    task: Option<Pin<Box<dyn Future<Output = ()>>>>,
-    waker: Waker,
-    scheduler: Rc<RefCell<MiniScheduler<X>>>,
+pub struct MiniExecutor<X, const MAX_TASKS: usize = DEFAULT_MAX_TASKS> {
+    /// the wakers of the tasks, one per entry of the task table
+    branches: [Branch; MAX_TASKS],
+    scheduler: Rc<RefCell<MiniScheduler<X, MAX_TASKS>>>,
 }
 
 /// an implementation of the [`super::Scheduler`] trait for [`MiniExecutor`]
-pub struct MiniScheduler<X> {
+pub struct MiniScheduler<X, const MAX_TASKS: usize = DEFAULT_MAX_TASKS> {
+    /// delay requested by the task being polled
     delay_request: Option<DurationMillis>,
+    /// deadlines of the branches waiting for a timer, by the slot of their waker,
+    /// see [`AccessTiming::set_wake_request`]
+    timers: [Option<InstantMillis>; MAX_BRANCHES],
+    /// executor signals awaited by the branches, by the slot of their waker,
+    /// see [`AccessExecutorSignals::set_signal_wake_request`]
+    signal_waiters: [X; MAX_BRANCHES],
     instant: InstantMillis,
     executor_signals: X,
+    tasks: [TaskSlot; MAX_TASKS],
+}
+
+/// a task, allocated on the heap or provided by the application
+enum Task {
+    Boxed(Pin<Box<dyn Future<Output = ()>>>),
+    Static(Pin<&'static mut dyn Future<Output = ()>>),
+}
+
+impl Task {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self {
+            Task::Boxed(future) => future.as_mut().poll(cx),
+            Task::Static(future) => future.as_mut().poll(cx),
+        }
+    }
+}
+
+enum TaskSlot {
+    Free,
+    /// added since the start of the latest step, to be polled in the next step
+    Spawned(Task),
+    /// taken out of the table while its `poll()` function runs
+    Polled,
+    Occupied(Task),
+}
+
+impl<X, const MAX_TASKS: usize> MiniScheduler<X, MAX_TASKS> {
+    fn add_task(&mut self, task: Task) -> Result<(), TaskTableFull> {
+        let slot = self
+            .tasks
+            .iter_mut()
+            .find(|slot| matches!(slot, TaskSlot::Free))
+            .ok_or(TaskTableFull)?;
+        *slot = TaskSlot::Spawned(task);
+        Ok(())
+    }
 }
 
-impl<X> AccessTiming for Rc<RefCell<MiniScheduler<X>>> {
+impl<X, const MAX_TASKS: usize> AccessTiming for Rc<RefCell<MiniScheduler<X, MAX_TASKS>>> {
     fn set_delay_request(&self, delay: DurationWrapper) {
         let delay_mut = &mut self.borrow_mut().delay_request;
         if let Some(ref mut delay_mut) = delay_mut {
@@ -45,28 +96,61 @@ impl<X> AccessTiming for Rc<RefCell<MiniScheduler<X>>> {
         }
     }
 
+    fn set_wake_request(&self, delay: DurationWrapper, waker: &Waker) {
+        let Some(index) = branch::slot_of(waker) else {
+            self.set_delay_request(delay);
+            waker.wake_by_ref();
+            return;
+        };
+        let mut scheduler = self.borrow_mut();
+        let deadline = scheduler.instant + delay.0;
+        // a branch waits for one timer at a time, an earlier deadline
+        // of a timer it has given up only causes a spurious wake
+        let timer = &mut scheduler.timers[index];
+        if timer.map_or(true, |registered| deadline - registered < 0) {
+            *timer = Some(deadline);
+        }
+    }
+
     fn get_instant(&self) -> InstantMillis {
         self.borrow().instant
     }
 }
 
-impl<X: Copy> AccessExecutorSignals<X> for Rc<RefCell<MiniScheduler<X>>> {
+impl<X: SignalSet, const MAX_TASKS: usize> AccessExecutorSignals<X>
+    for Rc<RefCell<MiniScheduler<X, MAX_TASKS>>>
+{
     fn get_executor_signals(&self) -> X {
         self.borrow().executor_signals
     }
+
+    fn set_signal_wake_request(&self, signals: X, waker: &Waker) {
+        let Some(index) = branch::slot_of(waker) else {
+            waker.wake_by_ref();
+            return;
+        };
+        let signal_waiter = &mut self.borrow_mut().signal_waiters[index];
+        *signal_waiter = signal_waiter.union(signals);
+    }
 }
 
-static VTABLE: RawWakerVTable = RawWakerVTable::new(
-    |_| RawWaker::new(ptr::null(), &VTABLE),
-    |_| {},
-    |_| {},
-    |_| {},
-);
+impl<X, const MAX_TASKS: usize> Spawn for Rc<RefCell<MiniScheduler<X, MAX_TASKS>>> {
+    fn spawn_boxed(&self, task: Pin<Box<dyn Future<Output = ()>>>) -> Result<(), TaskTableFull> {
+        self.borrow_mut().add_task(Task::Boxed(task))
+    }
+
+    fn spawn_static(
+        &self,
+        task: Pin<&'static mut dyn Future<Output = ()>>,
+    ) -> Result<(), TaskTableFull> {
+        self.borrow_mut().add_task(Task::Static(task))
+    }
+}
 
-impl<X> MiniExecutorBuilder<X> {
+impl<X, const MAX_TASKS: usize> MiniExecutorBuilder<X, MAX_TASKS> {
     /// You can use this to obtain a clone of the scheduler and pass it to the
     /// future representing the main task.
-    pub fn scheduler(&self) -> &Rc<RefCell<MiniScheduler<X>>> {
+    pub fn scheduler(&self) -> &Rc<RefCell<MiniScheduler<X, MAX_TASKS>>> {
         &self.scheduler
     }
 
@@ -74,69 +158,176 @@ impl<X> MiniExecutorBuilder<X> {
     ///
     /// `fut` is the main task run by the executor.
     ///  Returns an instance of the `MiniExecutor`.
-    pub fn build(self, fut: impl Future<Output = ()> + 'static) -> MiniExecutor<X> {
-        let raw_waker = RawWaker::new(ptr::null(), &VTABLE);
-        let waker = unsafe { Waker::from_raw(raw_waker) };
-        let task = Box::pin(fut);
+    ///
+    /// # Panics
+    ///
+    /// if `MAX_TASKS` tasks have already been spawned through the scheduler
+    pub fn build(self, fut: impl Future<Output = ()> + 'static) -> MiniExecutor<X, MAX_TASKS> {
+        if self.scheduler.spawn(fut).is_err() {
+            panic!("no room for the main task");
+        }
         MiniExecutor {
-            task: Some(task),
-            waker,
+            branches: core::array::from_fn(|_| Branch::task()),
             scheduler: self.scheduler,
         }
     }
 }
 
-impl<X: Copy + Default> MiniExecutor<X> {
+#[allow(clippy::new_ret_no_self)]
+impl<X: SignalSet> MiniExecutor<X> {
     /// first stage of initialization of the executor
     ///
     /// `start_time` needs to be the current time when the executor is created.
     /// Returns an executor builder, needed for the second stage of initialization.
+    /// The executor can run up to [`DEFAULT_MAX_TASKS`] tasks,
+    /// use [`MiniExecutor::with_max_tasks`] to choose another number.
     pub fn new(start_time: InstantMillis) -> MiniExecutorBuilder<X> {
+        Self::with_max_tasks(start_time)
+    }
+}
+
+impl<X: SignalSet, const MAX_TASKS: usize> MiniExecutor<X, MAX_TASKS> {
+    /// like [`MiniExecutor::new`], for an executor running up to `MAX_TASKS` tasks
+    /// including the main task
+    pub fn with_max_tasks(start_time: InstantMillis) -> MiniExecutorBuilder<X, MAX_TASKS> {
         let scheduler = Rc::new(RefCell::new(MiniScheduler {
             delay_request: None,
+            timers: [None; MAX_BRANCHES],
+            signal_waiters: [X::default(); MAX_BRANCHES],
             instant: start_time,
             executor_signals: X::default(),
+            tasks: core::array::from_fn(|_| TaskSlot::Free),
         }));
         MiniExecutorBuilder { scheduler }
     }
 
-    /// runs the `poll()` function of the main task once
+    /// runs the `poll()` function of each task once
+    ///
+    /// Within a task, the futures combined by [`crate::join2`], [`crate::select2`]
+    /// and the like are only polled if they have been woken.
+    /// A future which wakes itself, e.g. by [`crate::yield_now`] or [`crate::sleep_at_most`],
+    /// is polled again in the next step.
+    /// A future waiting for [`crate::Timer::wait`] is woken when the timer expires,
+    /// and a future waiting for a [`crate::Notify`] or the like when it is notified.
     ///
     /// `instant` is the current time with milli second resolution.
-    /// `executor signals` can be any type of data implementing `Copy` and `Default`.
+    /// `executor signals` can be any type of data implementing [`SignalSet`].
     /// This information can be made available to the running tasks
     /// by means of the scheduler. It can be used to indicate
     /// whether any events have happened and triggered the call to `step()`.
+    /// A future waiting for some of these signals by means of
+    /// [`AccessExecutorSignals::set_signal_wake_request`] is woken
+    /// when `executor_signals` contains any of them.
     ///
-    /// The return value is `Some(duration)` in milliseconds if the running task
-    /// has called [`AccessTiming::set_delay_request`] at least once in its `poll()` function.
+    /// The return value is `Some(duration)` in milliseconds with the shortest delay
+    /// that a task has requested by calling [`AccessTiming::set_delay_request`]
+    /// in its `poll()` function.
     /// The duration is the requested maximum delay before the next call to `step()`.
     /// Calling `step()` sooner is always acceptable, and
     /// may be needed if an event happened that requires immediate processing.
+    /// Waiting for a timer counts as requesting a delay until it expires.
+    /// If no task has requested a delay, or a task has been spawned,
+    /// the return value is `Some(0)`.
+    /// This is also the case if a task has been woken
+    /// by another one after it has been polled.
+    /// Tasks spawned during `step()` are polled for the first time in the next step.
     ///
-    /// The return value `None` indicates that [`AccessTiming::set_delay_request`]
-    /// has not been called. In that case, the next call to `step()` needs to be
-    /// made when an event has happened, at the latest.
+    /// Tasks which have completed are removed from the executor.
+    /// The return value `None` indicates that all tasks have completed,
+    /// so there is no need to call `step()` again.
     pub fn step(&mut self, instant: InstantMillis, executor_signals: X) -> Option<DurationMillis> {
         {
             let mut scheduler = self.scheduler.borrow_mut();
             scheduler.instant = instant;
             scheduler.executor_signals = executor_signals;
-            scheduler.delay_request = None;
+            for slot in scheduler.tasks.iter_mut() {
+                if let TaskSlot::Spawned(_) = slot {
+                    let TaskSlot::Spawned(task) = mem::replace(slot, TaskSlot::Free) else {
+                        unreachable!()
+                    };
+                    *slot = TaskSlot::Occupied(task);
+                }
+            }
+            for (index, timer) in scheduler.timers.iter_mut().enumerate() {
+                if timer.is_some_and(|deadline| instant - deadline >= 0) {
+                    *timer = None;
+                    branch::wake(index);
+                }
+            }
+            for (index, signals) in scheduler.signal_waiters.iter_mut().enumerate() {
+                if signals.intersects(executor_signals) {
+                    *signals = X::default();
+                    branch::wake(index);
+                }
+            }
         }
-        let mut context = Context::from_waker(&self.waker);
-        let Some(ref mut task) = self.task else {
-            return None;
-        };
-        let pollres = task.as_mut().poll(&mut context);
-        if let Poll::Ready(()) = pollres {
-            self.task = None;
-            return None;
-        } else {
-            let Some(delay_request) = self.scheduler.borrow_mut().delay_request else {
-                return Some(0);
+        let mut pending = false;
+        let mut delay_request: Option<DurationMillis> = None;
+        for index in 0..MAX_TASKS {
+            // the scheduler must not be borrowed while the task runs
+            let mut task = {
+                let mut scheduler = self.scheduler.borrow_mut();
+                let slot = &mut scheduler.tasks[index];
+                if !matches!(slot, TaskSlot::Occupied(_)) {
+                    continue;
+                }
+                let TaskSlot::Occupied(task) = mem::replace(slot, TaskSlot::Polled) else {
+                    unreachable!()
+                };
+                scheduler.delay_request = None;
+                task
             };
-            return Some(delay_request);
+            let pollres = self.branches[index].poll_with(|cx| task.poll(cx));
+            let mut scheduler = self.scheduler.borrow_mut();
+            if let Poll::Ready(()) = pollres {
+                scheduler.tasks[index] = TaskSlot::Free;
+                drop(scheduler);
+                drop(task);
+            } else {
+                scheduler.tasks[index] = TaskSlot::Occupied(task);
+                pending = true;
+                if let Some(task_delay) = scheduler.delay_request {
+                    delay_request = Some(delay_request.map_or(task_delay, |d| d.min(task_delay)));
+                }
+            }
+        }
+        let scheduler = self.scheduler.borrow();
+        let spawned = scheduler
+            .tasks
+            .iter()
+            .any(|slot| matches!(slot, TaskSlot::Spawned(_)));
+        if spawned {
+            return Some(0);
+        }
+        if !pending {
+            return None;
         }
+        let woken = scheduler
+            .tasks
+            .iter()
+            .zip(self.branches.iter())
+            .any(|(slot, branch)| {
+                matches!(slot, TaskSlot::Occupied(_)) && branch.is_woken_by_other()
+            });
+        if woken {
+            return Some(0);
+        }
+        for deadline in scheduler.timers.iter().flatten() {
+            let timer_delay = (*deadline - instant).max(0);
+            delay_request = Some(delay_request.map_or(timer_delay, |d| d.min(timer_delay)));
+        }
+        Some(delay_request.unwrap_or(0))
+    }
+}
+
+impl<X, const MAX_TASKS: usize> Drop for MiniExecutor<X, MAX_TASKS> {
+    fn drop(&mut self) {
+        // The tasks usually hold a reference to the scheduler, so they would
+        // keep each other alive. They are dropped after the scheduler is released.
+        let tasks = mem::replace(
+            &mut self.scheduler.borrow_mut().tasks,
+            core::array::from_fn(|_| TaskSlot::Free),
+        );
+        drop(tasks);
     }
 }
@@ -54,22 +54,39 @@
 //! ## Features
 //!
 //! - `std`: (default) uses standard library.
-//!    *Note:* set `default-features = false` for no-std targets.
+//!   *Note:* set `default-features = false` for no-std targets.
 
 // no_std only when freature "std" is missing
 #![cfg_attr(not(feature = "std"), no_std)]
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use ambassador::delegatable_trait;
 use core::cell::Cell;
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::Poll;
 
 mod basic_futures;
+mod channel;
 mod executor;
 mod milliseconds;
+mod notify;
 mod timer;
+mod waiters;
+mod watch;
 
-pub use basic_futures::{join2, yield_now};
+// tests with standard library
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests;
+
+pub use basic_futures::branch::MAX_BRANCHES;
+pub use basic_futures::{
+    join2, join3, join4, join5, join6, join7, join8, join_array, select2, select_n, yield_now,
+    Either,
+};
 pub use milliseconds::{DurationMillis, InstantMillis};
 
 //use crate::system::SignalFlags;
@@ -83,8 +100,11 @@ pub use futures::{
     wait_avail_timeout
 };
 */
-pub use executor::{MiniExecutor, MiniExecutorBuilder, MiniScheduler};
-pub use timer::Timer;
+pub use channel::Channel;
+pub use executor::{MiniExecutor, MiniExecutorBuilder, MiniScheduler, DEFAULT_MAX_TASKS};
+pub use notify::{Event, Notify};
+pub use timer::{with_timeout, Elapsed, Timer};
+pub use watch::{Watch, WatchReceiver};
 
 /// for internal use
 ///
@@ -101,6 +121,15 @@ pub trait AccessTiming {
     fn get_instant(&self) -> InstantMillis;
     /// Use `sleep_at_most()` instead. This function is internal to this module.
     fn set_delay_request(&self, millis: DurationWrapper);
+    /// Use [`Timer::wait`] instead. This function is internal to this module.
+    ///
+    /// Requests `waker` to be woken when the delay is over.
+    /// By default, the delay is requested like by [`Self::set_delay_request`],
+    /// and `waker` is woken right away, so the caller is polled in every step.
+    fn set_wake_request(&self, millis: DurationWrapper, waker: &core::task::Waker) {
+        self.set_delay_request(millis);
+        waker.wake_by_ref();
+    }
 }
 
 /// interrupts execution and resumes after at most the given duration    
@@ -109,20 +138,172 @@ pub async fn sleep_at_most(sys: &impl AccessTiming, duration: DurationMillis) {
     yield_now().await;
 }
 
+/// like [`sleep_at_most`], but the branch is not polled until the duration
+/// is over or something else wakes it, see [`AccessTiming::set_wake_request`]
+async fn sleep_until_woken(sys: &impl AccessTiming, duration: DurationMillis) {
+    let mut requested = false;
+    poll_fn(|cx| {
+        if requested {
+            Poll::Ready(())
+        } else {
+            sys.set_wake_request(DurationWrapper(duration), cx.waker());
+            requested = true;
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 /// access to the signals passed to [`executor::MiniExecutor::step`]
 #[delegatable_trait]
 pub trait AccessExecutorSignals<X> {
     /// get the data that was passed to the latest call to [`executor::MiniExecutor::step`]
     fn get_executor_signals(&self) -> X;
+    /// requests `waker` to be woken in the next step with executor signals
+    /// intersecting `signals`, see [`SignalSet`]
+    ///
+    /// The request holds until it is fulfilled, so the waiting branch of [`join2`]
+    /// or the like is not polled meanwhile, unless something else wakes it.
+    /// By default, `waker` is woken right away, so the caller is polled in every step.
+    ///
+    /// ## example
+    ///
+    /// ```
+    /// use core::future::{pending, poll_fn};
+    /// use core::task::Poll;
+    /// use greaheisl_async::{join2, AccessExecutorSignals, InstantMillis, MiniExecutor};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// let polls = Rc::new(Cell::new(0));
+    /// let executor_builder = MiniExecutor::<u8>::new(InstantMillis::from_absolute(0));
+    /// let sys = executor_builder.scheduler().clone();
+    /// let task_polls = polls.clone();
+    /// let waiting = poll_fn(move |cx| {
+    ///     task_polls.set(task_polls.get() + 1);
+    ///     sys.set_signal_wake_request(0b10, cx.waker());
+    ///     Poll::<()>::Pending
+    /// });
+    /// let mut executor = executor_builder.build(async {
+    ///     join2(waiting, pending::<()>()).await;
+    /// });
+    /// let time = InstantMillis::from_absolute(0);
+    /// // the branch is polled the first time round
+    /// executor.step(time, 0);
+    /// // other signals do not wake it
+    /// executor.step(time + 10, 0b01);
+    /// assert_eq!(polls.get(), 1);
+    /// executor.step(time + 20, 0b11);
+    /// assert_eq!(polls.get(), 2);
+    /// ```
+    fn set_signal_wake_request(&self, _signals: X, waker: &core::task::Waker) {
+        waker.wake_by_ref();
+    }
+}
+
+/// executor signals that tasks can wait for, see [`AccessExecutorSignals::set_signal_wake_request`]
+///
+/// Usually, this is a set of flags. The default value is the empty set.
+pub trait SignalSet: Copy + Default {
+    /// the signals contained in `self` or `other`
+    fn union(self, other: Self) -> Self;
+    /// returns `true` if `self` and `other` have any signal in common
+    fn intersects(self, other: Self) -> bool;
+}
+
+/// no signals at all
+impl SignalSet for () {
+    fn union(self, _other: Self) -> Self {}
+    fn intersects(self, _other: Self) -> bool {
+        false
+    }
 }
 
+macro_rules! impl_signal_set_for_bits {
+    ($($t:ty),*) => {
+        $(
+            /// one signal per bit
+            impl SignalSet for $t {
+                fn union(self, other: Self) -> Self {
+                    self | other
+                }
+                fn intersects(self, other: Self) -> bool {
+                    self & other != 0
+                }
+            }
+        )*
+    };
+}
+
+impl_signal_set_for_bits!(u8, u16, u32);
+
 /// trait for a "scheduler" which manages timing of the calls to the `step()` function
 ///
-/// `X` is the type for executor signals and needs to implement `Copy` and `Default`,
+/// `X` is the type for executor signals and needs to implement [`SignalSet`],
 /// see documentation of [`MiniExecutor::step`]
 pub trait Scheduler<X>: AccessTiming + AccessExecutorSignals<X> {}
 impl<T, X> Scheduler<X> for T where T: AccessTiming + AccessExecutorSignals<X> {}
 
+/// adds tasks to a running executor
+///
+/// A spawned task runs independently of the task that spawned it,
+/// until it completes or the executor is dropped.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{sleep_at_most, InstantMillis, MiniExecutor, Spawn};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let ticks = Rc::new(Cell::new(0));
+/// let executor_builder = MiniExecutor::<()>::new(InstantMillis::from_absolute(0));
+/// let sys = executor_builder.scheduler().clone();
+/// let background_ticks = ticks.clone();
+/// let mut executor = executor_builder.build(async move {
+///     let background_sys = sys.clone();
+///     sys.spawn(async move {
+///         for _ in 0..3 {
+///             background_ticks.set(background_ticks.get() + 1);
+///             sleep_at_most(&background_sys, 10).await;
+///         }
+///     })
+///     .ok()
+///     .unwrap();
+///     // the main task ends here, the spawned task keeps running
+/// });
+/// let mut time = InstantMillis::from_absolute(0);
+/// while let Some(delay) = executor.step(time, ()) {
+///     time += delay;
+/// }
+/// assert_eq!(ticks.get(), 3);
+/// assert_eq!(time.into_inner(), 30);
+/// ```
+pub trait Spawn {
+    /// adds a task allocated on the heap
+    fn spawn_boxed(&self, task: Pin<Box<dyn Future<Output = ()>>>) -> Result<(), TaskTableFull>;
+    /// adds a task kept in memory provided by the application, e.g. in a `static`
+    ///
+    /// Adding and running the task allocates no memory. Note that [`MiniExecutor`]
+    /// allocates its scheduler once when it is created, and the main task
+    /// passed to [`executor::MiniExecutorBuilder::build`] is boxed.
+    fn spawn_static(
+        &self,
+        task: Pin<&'static mut dyn Future<Output = ()>>,
+    ) -> Result<(), TaskTableFull>;
+    /// moves `fut` to the heap and adds it as a task
+    fn spawn(&self, fut: impl Future<Output = ()> + 'static) -> Result<(), TaskTableFull>
+    where
+        Self: Sized,
+    {
+        self.spawn_boxed(Box::pin(fut))
+    }
+}
+
+/// error returned by [`Spawn`] if the executor already runs as many tasks as it can
+#[derive(Debug)]
+pub struct TaskTableFull;
+
 /// An struct that can be used as an error return value when a stop signal was received
 pub struct Stopped;
 
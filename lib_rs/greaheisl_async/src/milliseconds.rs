@@ -21,7 +21,7 @@ use core::ops::{Add, AddAssign, Sub};
 ///
 /// See also the module level documentation.
 ///
-#[derive(Clone, Copy, Debug)] // later also ,Serialize,Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)] // later also ,Serialize,Deserialize)]
 pub struct InstantMillis(InstantMillisInner);
 
 /// The underlying integer type representing an instant
@@ -0,0 +1,164 @@
+use super::{with_timeout, AccessTiming, DurationMillis, ReceiveBool};
+use crate::waiters::Waiters;
+use core::cell::Cell;
+
+/// wakes up a task waiting for something to change
+///
+/// A notification is kept until it is consumed by one of the
+/// waiting functions, so it does not get lost if the waiting task
+/// is busy with something else when [`Notify::notify`] is called.
+/// The waiting task may run in the same task as the notifying one,
+/// e.g. in another branch of [`crate::join2`], or in a task of its own.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{join2, InstantMillis, MiniExecutor, Notify, Timer};
+///
+/// let time = InstantMillis::from_absolute(0);
+/// let executor_builder = MiniExecutor::<()>::new(time);
+/// let sys = executor_builder.scheduler().clone();
+/// let mut executor = executor_builder.build(async move {
+///     let notify = Notify::new();
+///     join2(
+///         async {
+///             // would wait for 10 seconds without the notification
+///             assert!(notify.wait_timeout(&sys, 10000).await);
+///         },
+///         async {
+///             Timer::new(&sys, 100).wait().await;
+///             notify.notify();
+///         },
+///     )
+///     .await;
+/// });
+/// assert_eq!(executor.step(time, ()), Some(100));
+/// // the waiting branch sees the notification right away
+/// assert_eq!(executor.step(time + 100, ()), None);
+/// ```
+pub struct Notify {
+    pending: Cell<bool>,
+    waiters: Waiters,
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Self {
+            pending: Cell::new(false),
+            waiters: Waiters::new(),
+        }
+    }
+    /// sets the notification
+    ///
+    /// Wakes the waiting tasks. Other branches of the same task see the notification
+    /// right away, other tasks at the latest in the next step,
+    /// which `step()` of the executor requests by returning `Some(0)`.
+    pub fn notify(&self) {
+        self.pending.set(true);
+        self.waiters.wake_all();
+    }
+    /// consumes the notification
+    ///
+    /// Returns `true` if there was one.
+    pub fn take(&self) -> bool {
+        self.pending.replace(false)
+    }
+    /// waits until notified and consumes the notification
+    pub async fn wait(&self) {
+        self.waiters.wait_for(|| self.take().then_some(())).await
+    }
+    /// waits until notified or until `duration` has passed
+    ///
+    /// Returns `true` if a notification has been consumed.
+    pub async fn wait_timeout(&self, sys: &impl AccessTiming, duration: DurationMillis) -> bool {
+        with_timeout(sys, duration, self.wait()).await.is_ok()
+    }
+}
+
+/// a flag that tasks can wait for
+///
+/// Unlike [`Notify`], the flag stays set until [`Event::reset`] is called,
+/// so any number of tasks can wait for it.
+/// It can replace a `Cell<bool>` used as a stop signal,
+/// with the difference that the waiting tasks are woken when it is set.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{Event, InstantMillis, MiniExecutor, Spawn, Timer};
+/// use std::rc::Rc;
+///
+/// let executor_builder = MiniExecutor::<()>::new(InstantMillis::from_absolute(0));
+/// let sys = executor_builder.scheduler().clone();
+/// let mut executor = executor_builder.build(async move {
+///     let stop = Rc::new(Event::new());
+///     for _ in 0..3 {
+///         let stop = stop.clone();
+///         sys.spawn(async move { stop.wait().await }).ok().unwrap();
+///     }
+///     Timer::new(&sys, 100).wait().await;
+///     stop.set();
+/// });
+/// let mut time = InstantMillis::from_absolute(0);
+/// while let Some(delay) = executor.step(time, ()) {
+///     time += delay;
+/// }
+/// assert_eq!(time.into_inner(), 100);
+/// ```
+pub struct Event {
+    set: Cell<bool>,
+    waiters: Waiters,
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Event {
+    /// creates an event which is not set
+    pub const fn new() -> Self {
+        Self {
+            set: Cell::new(false),
+            waiters: Waiters::new(),
+        }
+    }
+    /// sets the flag and wakes the waiting tasks
+    pub fn set(&self) {
+        self.set.set(true);
+        self.waiters.wake_all();
+    }
+    /// clears the flag
+    pub fn reset(&self) {
+        self.set.set(false);
+    }
+    /// returns `true` if the flag is set
+    pub fn is_set(&self) -> bool {
+        self.set.get()
+    }
+    /// waits until the flag is set
+    ///
+    /// The flag is left as it is.
+    pub async fn wait(&self) {
+        self.waiters.wait_for(|| self.is_set().then_some(())).await
+    }
+    /// waits until the flag is set or until `duration` has passed
+    ///
+    /// Returns `true` if the flag is set.
+    pub async fn wait_timeout(&self, sys: &impl AccessTiming, duration: DurationMillis) -> bool {
+        with_timeout(sys, duration, self.wait()).await.is_ok()
+    }
+}
+
+impl ReceiveBool for Event {
+    fn get(&self) -> bool {
+        self.is_set()
+    }
+}
@@ -0,0 +1,634 @@
+use crate::{InstantMillis, MiniExecutor, MiniScheduler};
+use core::future::Future;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+type Sys = Rc<RefCell<MiniScheduler<()>>>;
+
+/// runs the task returned by `task` until it completes,
+/// returning its result and the time at completion
+fn run<T: 'static, F: Future<Output = T> + 'static>(task: impl FnOnce(Sys) -> F) -> (T, u32) {
+    let mut time = InstantMillis::from_absolute(0);
+    let executor_builder = MiniExecutor::<()>::new(time);
+    let sys = executor_builder.scheduler().clone();
+    let result = Rc::new(Cell::new(None));
+    let task_result = result.clone();
+    let fut = task(sys);
+    let mut executor = executor_builder.build(async move {
+        task_result.set(Some(fut.await));
+    });
+    while let Some(delay) = executor.step(time, ()) {
+        time += delay;
+    }
+    (result.take().unwrap(), time.into_inner())
+}
+
+/// sets a flag when dropped
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+/// the system allocator, counting the allocations of each thread
+struct CountingAllocator;
+
+std::thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // the counter is gone while the thread exits
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// the number of allocations of the current thread so far
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+mod executor {
+    use crate::{
+        join2, yield_now, InstantMillis, MiniExecutor, Notify, Spawn, TaskTableFull, Timer,
+    };
+    use core::future::{poll_fn, Future};
+    use core::pin::Pin;
+    use core::task::{Poll, Waker};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn only_woken_branches_are_polled() {
+        let time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<()>::new(time);
+        let sys = executor_builder.scheduler().clone();
+        let timer_polls = Rc::new(Cell::new(0));
+        let yield_polls = Rc::new(Cell::new(0));
+        let (task_timer_polls, task_yield_polls) = (timer_polls.clone(), yield_polls.clone());
+        let mut executor = executor_builder.build(async move {
+            let timer = Timer::new(&sys, 50);
+            let mut wait = core::pin::pin!(timer.wait());
+            join2(
+                poll_fn(|cx| {
+                    task_timer_polls.set(task_timer_polls.get() + 1);
+                    wait.as_mut().poll(cx)
+                }),
+                async {
+                    for _ in 0..10 {
+                        task_yield_polls.set(task_yield_polls.get() + 1);
+                        yield_now().await;
+                    }
+                },
+            )
+            .await;
+        });
+        // the yielding branch does not request a delay, the timer does
+        assert_eq!(executor.step(time, ()), Some(50));
+        for millis in [10, 20, 30, 40] {
+            assert_eq!(executor.step(time + millis, ()), Some(50 - millis));
+        }
+        assert_eq!(timer_polls.get(), 1);
+        assert_eq!(yield_polls.get(), 5);
+        executor.step(time + 50, ());
+        assert_eq!(timer_polls.get(), 2);
+        // once more, because the timer branch has completed
+        assert_eq!(yield_polls.get(), 7);
+    }
+
+    #[test]
+    fn task_woken_by_a_later_task_is_due_at_once() {
+        let time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<()>::new(time);
+        let sys = executor_builder.scheduler().clone();
+        let mut executor = executor_builder.build(async move {
+            let notify = Rc::new(Notify::new());
+            let sender_notify = notify.clone();
+            let sender_sys = sys.clone();
+            sys.spawn(async move {
+                Timer::new(&sender_sys, 100).wait().await;
+                sender_notify.notify();
+            })
+            .ok()
+            .unwrap();
+            notify.wait().await;
+        });
+        assert_eq!(executor.step(time, ()), Some(0));
+        assert_eq!(executor.step(time, ()), Some(100));
+        // the main task has been polled before the spawned task notifies it
+        assert_eq!(executor.step(time + 100, ()), Some(0));
+        assert_eq!(executor.step(time + 100, ()), None);
+    }
+
+    #[test]
+    fn waker_can_be_woken_from_another_thread() {
+        let time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<()>::new(time);
+        let sys = executor_builder.scheduler().clone();
+        let waker = Rc::new(Cell::new(None));
+        let polls = Rc::new(Cell::new(0));
+        let (task_waker, task_polls) = (waker.clone(), polls.clone());
+        let mut executor = executor_builder.build(async move {
+            join2(
+                Timer::new(&sys, 1000).wait(),
+                poll_fn(|cx| {
+                    task_polls.set(task_polls.get() + 1);
+                    task_waker.set(Some(cx.waker().clone()));
+                    Poll::<()>::Pending
+                }),
+            )
+            .await;
+        });
+        assert_eq!(executor.step(time, ()), Some(1000));
+        assert_eq!(executor.step(time + 10, ()), Some(990));
+        assert_eq!(polls.get(), 1);
+        let waker: Waker = waker.take().unwrap();
+        std::thread::spawn(move || waker.wake()).join().unwrap();
+        assert_eq!(executor.step(time + 20, ()), Some(980));
+        assert_eq!(polls.get(), 2);
+    }
+
+    #[test]
+    fn completed_tasks_make_room() {
+        let mut time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<(), 2>::with_max_tasks(time);
+        let sys = executor_builder.scheduler().clone();
+        let finished = Rc::new(Cell::new(0));
+        let task_finished = finished.clone();
+        let mut executor = executor_builder.build(async move {
+            for _ in 0..3 {
+                let task_sys = sys.clone();
+                let finished = task_finished.clone();
+                sys.spawn(async move {
+                    Timer::new(&task_sys, 10).wait().await;
+                    finished.set(finished.get() + 1);
+                })
+                .ok()
+                .unwrap();
+                // the main task and the spawned one take the whole table
+                assert!(matches!(sys.spawn(async {}), Err(TaskTableFull)));
+                Timer::new(&sys, 20).wait().await;
+            }
+        });
+        while let Some(delay) = executor.step(time, ()) {
+            time += delay;
+        }
+        assert_eq!(finished.get(), 3);
+        assert_eq!(time.into_inner(), 60);
+    }
+
+    #[test]
+    fn static_task_does_not_allocate() {
+        let mut time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<()>::new(time);
+        let sys = executor_builder.scheduler().clone();
+        let ticks = Rc::new(Cell::new(0));
+        let (task_sys, task_ticks) = (sys.clone(), ticks.clone());
+        let task = Pin::static_mut(Box::leak(Box::new(async move {
+            for _ in 0..3 {
+                Timer::new(&task_sys, 10).wait().await;
+                task_ticks.set(task_ticks.get() + 1);
+            }
+        })));
+        let mut executor = executor_builder.build(async {});
+        let before = super::allocations();
+        sys.spawn_static(task).ok().unwrap();
+        while let Some(delay) = executor.step(time, ()) {
+            time += delay;
+        }
+        assert_eq!(super::allocations() - before, 0);
+        assert_eq!(ticks.get(), 3);
+        assert_eq!(time.into_inner(), 30);
+    }
+}
+
+mod select {
+    use super::{run, DropFlag};
+    use crate::{select2, select_n, Either, Timer};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn first_future_to_complete_wins() {
+        let dropped = Rc::new(Cell::new(false));
+        let slow_dropped = dropped.clone();
+        let (result, time) = run(|sys| async move {
+            select2(
+                async {
+                    let _flag = DropFlag(slow_dropped);
+                    Timer::new(&sys, 300).wait().await;
+                    "slow"
+                },
+                async {
+                    Timer::new(&sys, 100).wait().await;
+                    7
+                },
+            )
+            .await
+        });
+        assert_eq!(result, Either::Second(7));
+        assert_eq!(time, 100);
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn first_future_wins_a_tie() {
+        let (result, time) = run(|sys| async move {
+            select2(Timer::new(&sys, 50).wait(), Timer::new(&sys, 50).wait()).await
+        });
+        assert_eq!(result, Either::First(()));
+        assert_eq!(time, 50);
+    }
+
+    #[test]
+    fn select_n_returns_the_index() {
+        let (result, time) = run(|sys| async move {
+            let sys = &sys;
+            let delays = [400, 250, 300, 250];
+            select_n(delays.map(|delay| async move {
+                Timer::new(sys, delay).wait().await;
+                delay * 2
+            }))
+            .await
+        });
+        assert_eq!(result, (1, 500));
+        assert_eq!(time, 250);
+    }
+
+    #[test]
+    fn select_does_not_allocate() {
+        let (allocations, time) = run(|sys| async move {
+            let sys = &sys;
+            let before = super::allocations();
+            for _ in 0..10 {
+                select2(Timer::new(sys, 10).wait(), Timer::new(sys, 20).wait()).await;
+                select_n([20, 10].map(|delay| async move {
+                    Timer::new(sys, delay).wait().await;
+                }))
+                .await;
+            }
+            super::allocations() - before
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(time, 200);
+    }
+}
+
+mod timeout {
+    use super::run;
+    use crate::{with_timeout, Elapsed, Notify, Timer};
+
+    #[test]
+    fn completes_in_time() {
+        let (result, time) = run(|sys| async move {
+            with_timeout(&sys, 200, async {
+                Timer::new(&sys, 150).wait().await;
+                "done"
+            })
+            .await
+        });
+        assert_eq!(result, Ok("done"));
+        assert_eq!(time, 150);
+    }
+
+    #[test]
+    fn elapses() {
+        let (result, time) = run(|sys| async move {
+            with_timeout(&sys, 200, async {
+                Timer::new(&sys, 1500).wait().await;
+                "done"
+            })
+            .await
+        });
+        assert_eq!(result, Err(Elapsed));
+        assert_eq!(time, 200);
+    }
+
+    #[test]
+    fn does_not_allocate() {
+        let (allocations, time) = run(|sys| async move {
+            let notify = Notify::new();
+            let before = super::allocations();
+            for _ in 0..10 {
+                assert_eq!(with_timeout(&sys, 10, notify.wait()).await, Err(Elapsed));
+                notify.notify();
+                assert_eq!(with_timeout(&sys, 10, notify.wait()).await, Ok(()));
+            }
+            super::allocations() - before
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(time, 100);
+    }
+}
+
+mod join {
+    use super::run;
+    use crate::{join, join3, join_array, Timer};
+    use std::cell::Cell;
+
+    #[test]
+    fn join3_waits_for_all() {
+        let (result, time) = run(|sys| async move {
+            join3(
+                async {
+                    Timer::new(&sys, 300).wait().await;
+                    1
+                },
+                async {
+                    Timer::new(&sys, 100).wait().await;
+                    "two"
+                },
+                async { 3.0 },
+            )
+            .await
+        });
+        assert_eq!(result, (1, "two", 3.0));
+        assert_eq!(time, 300);
+    }
+
+    #[test]
+    fn join_macro_sees_stop_signal() {
+        let (result, time) = run(|sys| async move {
+            let stop = Cell::new(false);
+            let stop = &stop;
+            let sys = &sys;
+            let waiter = |delay| async move {
+                while !stop.get() {
+                    Timer::new(sys, delay).wait().await;
+                }
+                delay
+            };
+            join!(
+                async {
+                    Timer::new(sys, 50).wait().await;
+                    stop.set(true);
+                },
+                waiter(1000),
+                waiter(2000),
+                waiter(3000),
+            )
+            .await
+        });
+        assert_eq!(result, ((), 1000, 2000, 3000));
+        assert_eq!(time, 3000);
+    }
+
+    #[test]
+    fn join_array_keeps_order() {
+        let (result, time) = run(|sys| async move {
+            let sys = &sys;
+            join_array([400, 250, 300, 0].map(|delay| async move {
+                Timer::new(sys, delay).wait().await;
+                delay / 50
+            }))
+            .await
+        });
+        assert_eq!(result, [8, 5, 6, 0]);
+        assert_eq!(time, 400);
+    }
+
+    #[test]
+    fn join_does_not_allocate() {
+        let (allocations, time) = run(|sys| async move {
+            let sys = &sys;
+            let before = super::allocations();
+            for _ in 0..10 {
+                join3(
+                    Timer::new(sys, 10).wait(),
+                    Timer::new(sys, 20).wait(),
+                    async {},
+                )
+                .await;
+                join_array([20, 10].map(|delay| async move {
+                    Timer::new(sys, delay).wait().await;
+                }))
+                .await;
+            }
+            super::allocations() - before
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(time, 400);
+    }
+}
+
+mod notify {
+    use super::run;
+    use crate::{join2, Event, InstantMillis, MiniExecutor, Notify, ReceiveBool, Spawn, Timer};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn wakes_another_task() {
+        let (woken_at, time) = run(|sys| async move {
+            let notify = Rc::new(Notify::new());
+            let woken_at = Rc::new(Cell::new(None));
+            let waiter_sys = sys.clone();
+            let waiter_notify = notify.clone();
+            let waiter_woken_at = woken_at.clone();
+            sys.spawn(async move {
+                waiter_notify.wait().await;
+                waiter_woken_at.set(Some(crate::AccessTiming::get_instant(&waiter_sys)));
+            })
+            .ok()
+            .unwrap();
+            Timer::new(&sys, 100).wait().await;
+            notify.notify();
+            Timer::new(&sys, 200).wait().await;
+            woken_at.get().unwrap().into_inner()
+        });
+        assert_eq!(woken_at, 100);
+        assert_eq!(time, 300);
+    }
+
+    #[test]
+    fn notification_is_consumed() {
+        let (result, time) = run(|sys| async move {
+            let notify = Notify::new();
+            notify.notify();
+            let first = notify.wait_timeout(&sys, 100).await;
+            let second = notify.wait_timeout(&sys, 100).await;
+            (first, second)
+        });
+        assert_eq!(result, (true, false));
+        assert_eq!(time, 100);
+    }
+
+    #[test]
+    fn event_wakes_all_waiters() {
+        // several tasks wait, and two branches of the main task
+        const TASKS: usize = 7;
+        let mut time = InstantMillis::from_absolute(0);
+        let executor_builder = MiniExecutor::<(), { TASKS + 1 }>::with_max_tasks(time);
+        let sys = executor_builder.scheduler().clone();
+        let stop = Rc::new(Event::new());
+        let finished = Rc::new(Cell::new(0));
+        for _ in 0..TASKS {
+            let stop = stop.clone();
+            let finished = finished.clone();
+            sys.spawn(async move {
+                stop.wait().await;
+                finished.set(finished.get() + 1);
+            })
+            .ok()
+            .unwrap();
+        }
+        let main_stop = stop.clone();
+        let mut executor = executor_builder.build(async move {
+            let stop = main_stop;
+            join2(
+                async {
+                    Timer::new(&sys, 100).wait().await;
+                    stop.set();
+                },
+                async {
+                    // keeps waiting while the event is set
+                    stop.wait().await;
+                    stop.wait().await;
+                    assert!(stop.get());
+                },
+            )
+            .await;
+        });
+        while let Some(delay) = executor.step(time, ()) {
+            time += delay;
+        }
+        assert_eq!(finished.get(), TASKS);
+        assert_eq!(time.into_inner(), 100);
+    }
+}
+
+mod waiters {
+    use super::run;
+    use crate::waiters::Waiters;
+    use crate::with_timeout;
+
+    #[test]
+    fn timed_out_waits_are_forgotten() {
+        let (lengths, time) = run(|sys| async move {
+            let waiters = Waiters::new();
+            let mut lengths = Vec::new();
+            for _ in 0..100 {
+                let result = with_timeout(&sys, 10, waiters.wait_for(|| None::<()>)).await;
+                assert!(result.is_err());
+                lengths.push(waiters.len());
+            }
+            lengths
+        });
+        assert!(lengths.iter().all(|&len| len <= 2), "{lengths:?}");
+        assert_eq!(time, 1000);
+    }
+}
+
+mod channel {
+    use super::run;
+    use crate::{join2, Channel, Spawn, Timer};
+    use std::rc::Rc;
+
+    #[test]
+    fn keeps_order_and_capacity() {
+        let channel = Channel::<u8, 3>::new();
+        for round in 0..3 {
+            assert!(channel.is_empty());
+            for value in 0..3 {
+                assert_eq!(channel.try_send(round * 10 + value), Ok(()));
+            }
+            assert!(channel.is_full());
+            assert_eq!(channel.try_send(99), Err(99));
+            for value in 0..3 {
+                assert_eq!(channel.try_receive(), Some(round * 10 + value));
+            }
+            assert_eq!(channel.try_receive(), None);
+        }
+    }
+
+    #[test]
+    fn sender_waits_for_room() {
+        let (result, time) = run(|sys| async move {
+            let channel = Channel::<u32, 1>::new();
+            join2(
+                async {
+                    channel.send(1).await;
+                    // waits until the receiver takes out the first value
+                    channel.send(2).await;
+                    crate::AccessTiming::get_instant(&sys).into_inner()
+                },
+                async {
+                    Timer::new(&sys, 100).wait().await;
+                    let first = channel.receive().await;
+                    let second = channel.receive().await;
+                    (first, second)
+                },
+            )
+            .await
+        });
+        assert_eq!(result, (100, (1, 2)));
+        assert_eq!(time, 100);
+    }
+
+    #[test]
+    fn receives_from_several_tasks() {
+        let (result, time) = run(|sys| async move {
+            let channel = Rc::new(Channel::<i32, 2>::new());
+            for delay in [300, 100, 200] {
+                let channel = channel.clone();
+                let sys = sys.clone();
+                sys.clone()
+                    .spawn(async move {
+                        Timer::new(&sys, delay).wait().await;
+                        channel.send(delay).await;
+                    })
+                    .ok()
+                    .unwrap();
+            }
+            let mut received = Vec::new();
+            while let Some(value) = channel.receive_timeout(&sys, 150).await {
+                received.push(value);
+            }
+            received
+        });
+        assert_eq!(result, vec![100, 200, 300]);
+        assert_eq!(time, 450);
+    }
+}
+
+mod watch {
+    use super::run;
+    use crate::{join2, ReceiveBool, Timer, Watch};
+
+    #[test]
+    fn receiver_sees_latest_value() {
+        let (result, time) = run(|sys| async move {
+            let watch = Watch::new(0);
+            let receiver = watch.receiver();
+            assert!(!receiver.get());
+            join2(
+                async {
+                    Timer::new(&sys, 100).wait().await;
+                    watch.send(1);
+                    watch.send_modify(|value| *value += 1);
+                },
+                async {
+                    receiver.changed().await;
+                    assert!(receiver.get());
+                    let value = *receiver.borrow_and_update();
+                    assert!(!receiver.has_changed());
+                    (value, receiver.changed_timeout(&sys, 50).await)
+                },
+            )
+            .await
+        });
+        assert_eq!(result, ((), (2, false)));
+        assert_eq!(time, 150);
+    }
+}
@@ -1,5 +1,6 @@
 use super::{AccessTiming, DurationMillis, InstantMillis};
-use crate::sleep_at_most;
+use crate::{select2, sleep_at_most, sleep_until_woken, Either};
+use core::future::Future;
 
 /// useful for waiting until a certain time has passed or an event has occured
 pub struct Timer<'a, S> {
@@ -47,8 +48,17 @@ impl<'a, S: AccessTiming> Timer<'a, S> {
     /// this is needed,
     /// create a loop an use [`Self::yield_if_time_left]
     /// instead.
+    ///
+    /// Meanwhile, the branch waiting for the timer is not polled,
+    /// unless something else wakes it.
     pub async fn wait(&self) {
-        while self.yield_if_time_left().await {}
+        loop {
+            let time_left = self.time_left();
+            if time_left <= 0 {
+                return;
+            }
+            sleep_until_woken(self.sys, time_left).await;
+        }
     }
     /// returns the instant when [`Self::new`] was invoked.
     pub fn start_time(&self) -> InstantMillis {
@@ -56,6 +66,26 @@ impl<'a, S: AccessTiming> Timer<'a, S> {
     }
 }
 
+/// error returned by [`with_timeout`] if the future has not completed in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// runs `fut` for at most `duration` milliseconds
+///
+/// Returns the result of `fut`, or [`Elapsed`] if the time is up first.
+/// In that case, `fut` is dropped.
+pub async fn with_timeout<T>(
+    sys: &impl AccessTiming,
+    duration: DurationMillis,
+    fut: impl Future<Output = T>,
+) -> Result<T, Elapsed> {
+    let timer = Timer::new(sys, duration);
+    match select2(fut, timer.wait()).await {
+        Either::First(retval) => Ok(retval),
+        Either::Second(()) => Err(Elapsed),
+    }
+}
+
 /*
 
 pub async fn wait_cond(cond: impl Fn()->bool) {
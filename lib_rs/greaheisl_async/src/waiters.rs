@@ -0,0 +1,83 @@
+use crate::basic_futures::branch::{self, MAX_BRANCHES};
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+const WORDS: usize = MAX_BRANCHES.div_ceil(32);
+
+/// the branches waiting for a primitive like [`crate::Notify`]
+///
+/// Each branch of [`crate::join2`] and the like has a waker of its own,
+/// so only the waiting branches are polled when they are woken.
+/// The waiting branches are kept as a set of slots of the static table
+/// of branch wakers, see [`MAX_BRANCHES`], so the set takes no memory from the heap
+/// and a branch which has stopped waiting takes no room once its slot is given back.
+pub(crate) struct Waiters {
+    slots: Cell<[u32; WORDS]>,
+}
+
+impl Waiters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: Cell::new([0; WORDS]),
+        }
+    }
+    /// adds the branch of `waker`
+    ///
+    /// A waker which does not belong to a branch is woken right away,
+    /// so its task polls the primitive in every step.
+    pub(crate) fn register(&self, waker: &Waker) {
+        let Some(index) = branch::slot_of(waker) else {
+            waker.wake_by_ref();
+            return;
+        };
+        let mut slots = self.slots.get();
+        // forget the branches which have been dropped while waiting
+        for (word_index, word) in slots.iter_mut().enumerate() {
+            let mut bits = *word;
+            while bits != 0 {
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                if !branch::is_claimed(word_index * 32 + bit as usize) {
+                    *word &= !(1 << bit);
+                }
+            }
+        }
+        slots[index / 32] |= 1 << (index % 32);
+        self.slots.set(slots);
+    }
+    /// wakes all registered branches and forgets them
+    pub(crate) fn wake_all(&self) {
+        let slots = self.slots.replace([0; WORDS]);
+        for (word_index, mut bits) in slots.into_iter().enumerate() {
+            while bits != 0 {
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                branch::wake(word_index * 32 + bit as usize);
+            }
+        }
+    }
+    /// the number of registered branches
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.slots
+            .get()
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+    /// waits until `f` returns a value
+    ///
+    /// `f` is called each time the task is polled.
+    /// It is supposed to return `Some` after [`Self::wake_all`] has been called.
+    pub(crate) async fn wait_for<T>(&self, mut f: impl FnMut() -> Option<T>) -> T {
+        poll_fn(|cx| match f() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                self.register(cx.waker());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,134 @@
+use super::{with_timeout, AccessTiming, DurationMillis, ReceiveBool};
+use crate::waiters::Waiters;
+use core::cell::{Cell, Ref, RefCell};
+
+/// holds the latest value of something, e.g. settings, and tells receivers when it changes
+///
+/// Unlike a [`crate::Channel`], intermediate values are not kept:
+/// a receiver only gets to see the value current at the time it looks.
+///
+/// ## example
+///
+/// ```
+/// use greaheisl_async::{join2, InstantMillis, MiniExecutor, Timer, Watch};
+///
+/// let executor_builder = MiniExecutor::<()>::new(InstantMillis::from_absolute(0));
+/// let sys = executor_builder.scheduler().clone();
+/// let mut executor = executor_builder.build(async move {
+///     let setpoint = Watch::new(20);
+///     let receiver = setpoint.receiver();
+///     join2(
+///         async {
+///             Timer::new(&sys, 100).wait().await;
+///             setpoint.send(22);
+///         },
+///         async {
+///             receiver.changed().await;
+///             assert_eq!(*receiver.borrow_and_update(), 22);
+///         },
+///     )
+///     .await;
+/// });
+/// let mut time = InstantMillis::from_absolute(0);
+/// while let Some(delay) = executor.step(time, ()) {
+///     time += delay;
+/// }
+/// assert_eq!(time.into_inner(), 100);
+/// ```
+pub struct Watch<T> {
+    value: RefCell<T>,
+    version: Cell<u32>,
+    waiters: Waiters,
+}
+
+impl<T: Default> Default for Watch<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Watch<T> {
+    /// creates a watch holding `value`
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: RefCell::new(value),
+            version: Cell::new(0),
+            waiters: Waiters::new(),
+        }
+    }
+    /// replaces the value and wakes the waiting receivers
+    pub fn send(&self, value: T) {
+        self.send_modify(|current| *current = value);
+    }
+    /// modifies the value in place and wakes the waiting receivers
+    pub fn send_modify(&self, modify: impl FnOnce(&mut T)) {
+        modify(&mut self.value.borrow_mut());
+        self.version.set(self.version.get().wrapping_add(1));
+        self.waiters.wake_all();
+    }
+    /// borrows the current value
+    ///
+    /// The borrow must end before the value is changed, i.e. before the next `.await`.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.value.borrow()
+    }
+    /// returns a copy of the current value
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.borrow().clone()
+    }
+    /// creates a receiver which has seen the current value
+    pub fn receiver(&self) -> WatchReceiver<'_, T> {
+        WatchReceiver {
+            watch: self,
+            seen: Cell::new(self.version.get()),
+        }
+    }
+}
+
+/// keeps track of which value of a [`Watch`] has been seen
+pub struct WatchReceiver<'a, T> {
+    watch: &'a Watch<T>,
+    seen: Cell<u32>,
+}
+
+impl<T> WatchReceiver<'_, T> {
+    /// returns `true` if the value has changed since it has last been seen
+    pub fn has_changed(&self) -> bool {
+        self.seen.get() != self.watch.version.get()
+    }
+    /// borrows the current value and marks it as seen
+    pub fn borrow_and_update(&self) -> Ref<'_, T> {
+        self.mark_seen();
+        self.watch.borrow()
+    }
+    /// marks the current value as seen
+    pub fn mark_seen(&self) {
+        self.seen.set(self.watch.version.get());
+    }
+    /// waits until the value has changed since it has last been seen
+    ///
+    /// Returns immediately if it has changed already.
+    /// The new value is not marked as seen, use [`Self::borrow_and_update`] for that.
+    pub async fn changed(&self) {
+        self.watch
+            .waiters
+            .wait_for(|| self.has_changed().then_some(()))
+            .await
+    }
+    /// waits until the value has changed, or until `duration` has passed
+    ///
+    /// Returns `true` if the value has changed.
+    pub async fn changed_timeout(&self, sys: &impl AccessTiming, duration: DurationMillis) -> bool {
+        with_timeout(sys, duration, self.changed()).await.is_ok()
+    }
+}
+
+/// `true` if the value has changed since it has last been seen
+impl<T> ReceiveBool for WatchReceiver<'_, T> {
+    fn get(&self) -> bool {
+        self.has_changed()
+    }
+}
@@ -5,7 +5,7 @@
 //! ## Features
 //!
 //! - `std`: (default) uses standard library.
-//!    *Note:* set `default-features = false` for no-std targets.
+//!   *Note:* set `default-features = false` for no-std targets.
 //! - `font`: implements the font traits of crate `greaheisl_typeset` 
 //! - `fitzl_font`: additionally includes a tiny font with very narrow digits
 //!
@@ -309,7 +309,7 @@ impl<'a> BitVecImgViewMut for ImageRegionMut<'a> {
 /// after:
 ///                    |target_start----------|target_start+target_length
 /// ```
-
+///
 /// The range specified by `target_start` and `target_length` is clipped
 /// such that it fits into a window that starts at 0 and has a length
 /// window_length. Thus if target_start is negative, it will be set to 0.
@@ -329,5 +329,5 @@ pub fn clip_range(target_start: &mut i32, target_length: &mut u32, window_length
     let window_length_i: i32 = window_length.try_into().unwrap();
     let remaining_length: i32 = window_length_i.checked_sub(*target_start).unwrap().max(0);
     *target_length = (*target_length).min(remaining_length as u32);
-    return src_start as u32;
+    src_start as u32
 }
@@ -1,7 +1,8 @@
-use core::{cell::Cell, future::Future};
+use core::task::Poll;
+use core::{cell::Cell, future::poll_fn, future::Future};
 
 use greaheisl_async::{join2, yield_now};
-use greaheisl_async::{sleep_at_most, AccessTiming};
+use greaheisl_async::{sleep_at_most, with_timeout, AccessTiming};
 use greaheisl_async::{DurationMillis, InstantMillis};
 use greaheisl_async::{ReceiveBool, Stopped};
 
@@ -139,7 +140,7 @@ impl<F: ButtonFlagsTrait> ButtonProcessor<F> {
     /// If you do not check every time, then events can get "lost",
     /// i.e., there is no reaction to them.
     pub fn event(&self) -> ButtonEvent<F> {
-        self.event.get().clone()
+        self.event.get()
     }
     /// get information about the state of the button processor
     ///
@@ -317,17 +318,26 @@ impl<F: ButtonFlagsTrait> ButtonProcessor<F> {
 /// signal flags.
 /// Also stops waiting if the stop signal becomes true.
 /// In that case the function returns `Err(Stopped)`.
+///
+/// The waiting branch of [`join2`] is only polled once there is button activity,
+/// see [`AccessButtonSignal::set_button_wake_request`], unless it is woken otherwise.
 pub async fn wait_stop_or_button(
     sys: &impl AccessButtonSignal,
     stop_signal: &impl ReceiveBool,
 ) -> Result<(), Stopped> {
-    while !stop_signal.get() {
-        yield_now().await;
-        if sys.is_button_signal() {
-            return Ok(());
+    let mut requested = false;
+    poll_fn(|cx| {
+        if requested && sys.is_button_signal() {
+            return Poll::Ready(Ok(()));
         }
-    }
-    Err(Stopped)
+        if stop_signal.get() {
+            return Poll::Ready(Err(Stopped));
+        }
+        requested = true;
+        sys.set_button_wake_request(cx.waker());
+        Poll::Pending
+    })
+    .await
 }
 
 /// Waits until one of the set bits in `event_signal`
@@ -343,18 +353,8 @@ pub async fn wait_stop_or_button_or_timeout(
     stop_signal: &impl ReceiveBool,
     timeout: DurationMillis,
 ) -> Result<bool, Stopped> {
-    let start_time = sys.get_instant();
-    let mut time_left = timeout;
-    while !stop_signal.get() {
-        sleep_at_most(sys, time_left).await;
-        if sys.is_button_signal() {
-            return Ok(true);
-        }
-        let time_passed = sys.get_instant() - start_time;
-        time_left = timeout - time_passed;
-        if time_left <= 0 {
-            return Ok(false);
-        }
+    match with_timeout(sys, timeout, wait_stop_or_button(sys, stop_signal)).await {
+        Ok(result) => result.map(|()| true),
+        Err(_elapsed) => Ok(false),
     }
-    Err(Stopped)
 }
@@ -6,7 +6,7 @@
 //! ## Features
 //!
 //! - `std`: (default) uses standard library.
-//!    *Note:* set `default-features = false` for no-std targets.
+//!   *Note:* set `default-features = false` for no-std targets.
 
 // no_std only when freature "std" is missing
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -15,6 +15,13 @@
 pub trait AccessButtonSignal {
     /// returns true if there is any button activity
     fn is_button_signal(&self) -> bool;
+    /// requests `waker` to be woken as soon as there is button activity
+    ///
+    /// By default, `waker` is woken right away, so the caller checks
+    /// [`Self::is_button_signal`] in every step of the executor.
+    fn set_button_wake_request(&self, waker: &core::task::Waker) {
+        waker.wake_by_ref();
+    }
 }
 
 /// low level button state (each button can be pressed or not)
@@ -4,7 +4,24 @@
 //!
 //! * The LED matrix is visualized as ASCII graphics.
 //! * The arrow keys serve as buttons.
+//! * The number keys `1` to `8` toggle the digital inputs. Input 1 starts relay 3
+//!   for 10 minutes when it becomes active, and relay 1 is on while input 2 is active.
+//! * A simulated temperature sensor follows a sine curve between 17 °C and 23 °C
+//!   with a period of 10 minutes.
+//! * The most recent entry of the event log is shown below the help text.
+//! * With `--serial <path>`, the emulator answers the text commands of
+//!   [`greaheisl_lib::commands`] on the given serial device, e.g. one end of a pty pair
+//!   created by `socat -d -d pty,raw,echo=0 pty,raw,echo=0`.
+//!   Connect a terminal program to the other end.
+//! * With `--tcp <port>`, it answers them on a TCP port of the local host instead,
+//!   one connection at a time. This is how the client of the crate `greaheisl_host`
+//!   talks to the emulator through the binary [`greaheisl_lib::host_protocol`].
 //! 
+//! The number of relays can be given as the first command line argument;
+//! it defaults to the four relays of the Arduino relay shield.
+//! The seed of the random shifts in vacation mode can be given as the second argument;
+//! by default it is derived from the clock. It is shown so a run can be replayed.
+//!
 //! Unfortunately, standard terminals do not provide raw keyboard events. 
 //! Therefore, this program needs to be run in a terminal that supports the 
 //! [kitty keyboard protocol](https://sw.kovidgoyal.net/kitty/keyboard-protocol/).
@@ -18,13 +35,22 @@ use crossterm::event::{
     KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
 };
 use greaheisl_button_processor::AccessButtonSignal;
-use greaheisl_lib::system::{RtcTime, SignalFlags, NUM_RELAYS};
+use greaheisl_lib::event_log::EventLog;
+use greaheisl_lib::inputs::{InputAction, InputRule, MAX_INPUTS};
+use greaheisl_lib::statistics::RuntimeStats;
+use greaheisl_lib::system::{AccessRtc, RtcTime, SignalFlags, DEFAULT_NUM_RELAYS};
+use greaheisl_lib::OutputConfig;
+use greaheisl_lib::DEFAULT_NUM_SLOTS;
 
 use anyhow::Result;
 use bitvec::view::BitView;
-use chrono::Timelike;
-use num::Integer;
-use std::io::{stdout, Write};
+use chrono::{Datelike, Timelike};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{stdout, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 // #[macro_use]
@@ -38,6 +64,9 @@ use greaheisl_lib::system::buttons::ButtonFlags;
 
 // use greaheisl::show_clock;
 
+/// the number of relays the emulator has room for
+const MAX_RELAYS: usize = 8;
+
 /* This was for testing the C static library
 #[link(name = "greaheisl")]
 extern "C" {
@@ -83,50 +112,47 @@ fn write_matrix<F: std::fmt::Write>(writer: &mut F, matrix: &[u32]) -> Result<()
 #[derive(Delegate)]
 #[delegate(AccessTiming, target = "scheduler")]
 #[delegate(AccessExecutorSignals<SignalFlags>,target = "scheduler")]
-
 struct CliCallbacks<S> {
     buttons: Arc<Mutex<ButtonFlags>>,
     scheduler: S,
-    relay_states: Arc<Mutex<[bool; greaheisl_lib::system::NUM_RELAYS]>>,
-    time_shift_seconds: Mutex<i32>,
-}
-
-fn daytime_to_seconds(hour: u8, minute: u8, second: u8) -> i32 {
-    (hour as i32) * 3600 + (minute as i32) * 60 + (second as i32)
-}
-
-fn seconds_to_daytime(seconds: i32) -> (u8, u8, u8) {
-    let (minute, second) = seconds.div_rem(&60);
-    let (hour, minute) = minute.div_rem(&60);
-    let hour = hour % 24;
-    (hour as u8, minute as u8, second as u8)
+    relay_states: Arc<Mutex<[bool; MAX_RELAYS]>>,
+    relay_count: usize,
+    inputs: Arc<Mutex<u8>>,
+    start_instant: Instant,
+    time_shift: Mutex<chrono::Duration>,
+    settings_path: PathBuf,
+    statistics_path: PathBuf,
+    /// the bytes received over serial and not read yet
+    serial_input: Arc<Mutex<VecDeque<u8>>>,
+    /// the serial device or the TCP connection, if any
+    serial_output: SerialOutput,
 }
 
-impl<S> greaheisl_lib::system::AccessRtc for CliCallbacks<S> {
+/// The emulated clock is the UTC time of the PC, shifted by the
+/// difference between the time set by the user and the UTC time.
+impl<S> AccessRtc for CliCallbacks<S> {
     fn get_rtc(&self) -> RtcTime {
-        let time_real = chrono::Local::now().time();
-        let seconds_real = daytime_to_seconds(
-            time_real.hour() as u8,
-            time_real.minute() as u8,
-            time_real.second() as u8,
-        );
-        let (hour, minute, second) =
-            seconds_to_daytime(seconds_real + *self.time_shift_seconds.lock().unwrap());
+        let time = chrono::Utc::now().naive_utc() + *self.time_shift.lock().unwrap();
         RtcTime {
-            hour,
-            minute,
-            second,
+            year: time.year() as u16,
+            month: time.month() as u8,
+            day: time.day() as u8,
+            weekday: time.weekday().num_days_from_monday() as u8,
+            hour: time.hour() as u8,
+            minute: time.minute() as u8,
+            second: time.second() as u8,
         }
     }
     fn set_rtc(&self, time: &RtcTime) {
-        let time_real = chrono::Local::now().time();
-        let seconds_real = daytime_to_seconds(
-            time_real.hour() as u8,
-            time_real.minute() as u8,
-            time_real.second() as u8,
-        );
-        let seconds_set = daytime_to_seconds(time.hour, time.minute, time.second);
-        *self.time_shift_seconds.lock().unwrap() = seconds_set - seconds_real;
+        let Some(time_set) =
+            chrono::NaiveDate::from_ymd_opt(time.year as i32, time.month as u32, time.day as u32)
+                .and_then(|date| {
+                    date.and_hms_opt(time.hour as u32, time.minute as u32, time.second as u32)
+                })
+        else {
+            return;
+        };
+        *self.time_shift.lock().unwrap() = time_set - chrono::Utc::now().naive_utc();
     }
 }
 
@@ -135,7 +161,7 @@ impl<S> greaheisl_lib::system::AccessLedMatrix for CliCallbacks<S> {
         let mut s = String::new();
         write_matrix(&mut s, matrix).unwrap();
         stdout().queue(crossterm::cursor::MoveTo(0, 2)).unwrap();
-        write!(stdout(), "{}", s).unwrap();
+        print!("{}", s);
         stdout().flush().unwrap();
     }
 }
@@ -151,14 +177,143 @@ impl<S: AccessExecutorSignals<SignalFlags>> AccessButtonSignal for CliCallbacks<
     fn is_button_signal(&self) -> bool {
         self.get_executor_signals().contains(SignalFlags::Button)
     }
+    fn set_button_wake_request(&self, waker: &core::task::Waker) {
+        self.set_signal_wake_request(SignalFlags::Button, waker);
+    }
 }
 
-impl<S> greaheisl_lib::system::AccessOutputStates for CliCallbacks<S> {
-    fn set_relay_states(&self, relais_states: &[bool; greaheisl_lib::system::NUM_RELAYS]) {
+impl<S> greaheisl_lib::system::AccessOutputStates<MAX_RELAYS> for CliCallbacks<S> {
+    fn relay_count(&self) -> usize {
+        self.relay_count
+    }
+    fn set_relay_states(&self, relais_states: &[bool; MAX_RELAYS]) {
         *self.relay_states.lock().unwrap() = *relais_states;
     }
 }
 
+impl<S> greaheisl_lib::system::AccessDigitalInputs for CliCallbacks<S> {
+    fn get_input_flags(&self) -> u8 {
+        *self.inputs.lock().unwrap()
+    }
+}
+
+impl<S> greaheisl_lib::system::AccessAnalogInput for CliCallbacks<S> {
+    fn get_analog_value(&self) -> Option<i16> {
+        Some(simulated_temperature(self.start_instant.elapsed()))
+    }
+}
+
+/// the reading of the simulated sensor in tenths of a degree,
+/// given the time since the start of the emulator
+fn simulated_temperature(elapsed: Duration) -> i16 {
+    const PERIOD_SECS: f64 = 600.0;
+    let phase = elapsed.as_secs_f64() / PERIOD_SECS * std::f64::consts::TAU;
+    (200.0 + 30.0 * phase.sin()).round() as i16
+}
+
+/// The emulated non-volatile memory consists of files in the temporary directory,
+/// so settings and statistics survive restarts of the emulator.
+impl<S> greaheisl_lib::system::AccessPersistentStorage for CliCallbacks<S> {
+    fn load_settings(&self, buffer: &mut [u8]) -> usize {
+        load_file(&self.settings_path, buffer)
+    }
+    fn store_settings(&self, data: &[u8]) {
+        // losing the settings is not fatal for the emulator
+        let _ = std::fs::write(&self.settings_path, data);
+    }
+    fn load_statistics(&self, buffer: &mut [u8]) -> usize {
+        load_file(&self.statistics_path, buffer)
+    }
+    fn store_statistics(&self, data: &[u8]) {
+        let _ = std::fs::write(&self.statistics_path, data);
+    }
+}
+
+impl<S> greaheisl_lib::system::AccessSerial for CliCallbacks<S> {
+    fn read_serial(&self) -> Option<u8> {
+        self.serial_input.lock().unwrap().pop_front()
+    }
+    fn write_serial(&self, data: &[u8]) {
+        if let Some(serial_output) = self.serial_output.lock().unwrap().as_mut() {
+            // like a real serial port, drop what cannot be sent
+            let _ = serial_output
+                .write_all(data)
+                .and_then(|_| serial_output.flush());
+        }
+    }
+}
+
+/// where the emulator answers the commands received over serial
+enum HostLink {
+    /// a serial device, e.g. a pty
+    Serial(PathBuf),
+    /// a TCP port of the local host
+    Tcp(u16),
+}
+
+impl std::fmt::Display for HostLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostLink::Serial(path) => write!(f, "{}", path.display()),
+            HostLink::Tcp(port) => write!(f, "TCP port {}", port),
+        }
+    }
+}
+
+type SerialInput = Arc<Mutex<VecDeque<u8>>>;
+type SerialOutput = Arc<Mutex<Option<Box<dyn Write + Send>>>>;
+
+/// moves the bytes from `reader` to `serial_input` until the end of the stream
+fn receive(mut reader: impl Read, serial_input: &SerialInput) {
+    let mut buffer = [0u8; 64];
+    while let Ok(len @ 1..) = reader.read(&mut buffer) {
+        serial_input.lock().unwrap().extend(&buffer[..len]);
+    }
+}
+
+/// connects the emulated serial port, receiving in a background thread
+fn open_host_link(
+    link: &HostLink,
+    serial_input: SerialInput,
+    serial_output: SerialOutput,
+) -> Result<()> {
+    match link {
+        HostLink::Serial(path) => {
+            let device = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)?;
+            *serial_output.lock().unwrap() = Some(Box::new(device.try_clone()?));
+            std::thread::spawn(move || receive(device, &serial_input));
+        }
+        HostLink::Tcp(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", *port))?;
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let Ok(writer) = stream.try_clone() else {
+                        continue;
+                    };
+                    let _ = stream.set_nodelay(true);
+                    *serial_output.lock().unwrap() = Some(Box::new(writer));
+                    receive(stream, &serial_input);
+                    *serial_output.lock().unwrap() = None;
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+/// copies the beginning of a file into `buffer`; returns the number of bytes copied
+fn load_file(path: &Path, buffer: &mut [u8]) -> usize {
+    let Ok(data) = std::fs::read(path) else {
+        return 0;
+    };
+    let len = data.len().min(buffer.len());
+    buffer[..len].copy_from_slice(&data[..len]);
+    len
+}
+
 fn instantmillis_from_duration(duration: std::time::Duration) -> InstantMillis {
     InstantMillis::from_absolute(duration.as_millis() as u32)
 }
@@ -174,36 +329,85 @@ fn map_key_codes(key: crossterm::event::KeyCode) -> Option<ButtonFlags> {
     }
 }
 
-fn run() -> Result<()> {
+/// the example rules for the digital inputs
+fn example_input_rules() -> Vec<InputRule> {
+    vec![
+        InputRule {
+            input: 0,
+            relay: 2,
+            action: InputAction::Pulse(10 * 60 * 1000),
+        },
+        InputRule {
+            input: 1,
+            relay: 0,
+            action: InputAction::Hold,
+        },
+    ]
+}
+
+fn run(relay_count: usize, jitter_seed: Option<u64>, host_link: Option<&HostLink>) -> Result<()> {
     let start_instant = Instant::now();
     // let callbacks = CliCallbacks{ event: Arc::new(Mutex::new(ButtonEvent::None)) };
     let instant = instantmillis_from_duration(start_instant.elapsed());
     let executor = greaheisl_async::MiniExecutor::new(instant);
     let buttons = Arc::new(Mutex::new(ButtonFlags::none()));
-    let relay_states = Arc::new(Mutex::new([false; NUM_RELAYS]));
+    let relay_states = Arc::new(Mutex::new([false; MAX_RELAYS]));
+    let inputs = Arc::new(Mutex::new(0u8));
+    let serial_input = SerialInput::default();
+    let serial_output = SerialOutput::default();
+    if let Some(host_link) = host_link {
+        open_host_link(host_link, serial_input.clone(), serial_output.clone())?;
+    }
     let callbacks = CliCallbacks {
         buttons: buttons.clone(),
         scheduler: executor.scheduler().clone(),
         relay_states: relay_states.clone(),
-        time_shift_seconds: Mutex::new(0),
+        relay_count,
+        inputs: inputs.clone(),
+        start_instant,
+        time_shift: Mutex::new(chrono::Duration::zero()),
+        settings_path: std::env::temp_dir().join("greaheisl_emu_settings.bin"),
+        statistics_path: std::env::temp_dir().join("greaheisl_emu_statistics.bin"),
+        serial_input: serial_input.clone(),
+        serial_output,
+    };
+    let jitter_seed = jitter_seed.unwrap_or_else(|| {
+        greaheisl_lib::random::seed_from(&callbacks.get_rtc(), callbacks.get_instant())
+    });
+    let config = OutputConfig {
+        jitter_seed: Some(jitter_seed),
+        input_rules: example_input_rules(),
+        input_debounce: 50,
+        ..Default::default()
+    };
+    let event_log = Rc::new(EventLog::new());
+    let statistics = RefCell::new(RuntimeStats::default());
+    let task = {
+        let event_log = event_log.clone();
+        async move {
+            greaheisl_lib::run::<MAX_RELAYS, DEFAULT_NUM_SLOTS>(
+                callbacks,
+                config,
+                &event_log,
+                &statistics,
+            )
+            .await;
+        }
     };
-    let task = greaheisl_lib::run(callbacks);
     let mut executor = executor.build(task);
+    let mut shown_event = None;
     let mut next_delay_millis: DurationMillis = 100;
     stdout().execute(terminal::Clear(terminal::ClearType::All))?;
     stdout().queue(crossterm::cursor::MoveTo(0, 0)).unwrap();
-    write!(stdout(), "GREAHEISL BOARD EMULATOR\n\r").unwrap();
+    print!("GREAHEISL BOARD EMULATOR\n\r");
     stdout().queue(crossterm::cursor::MoveTo(0, 8)).unwrap();
-    write!(
-        stdout(),
-        "Use a terminal supporting the kitty keyboard protocol.\n\r"
-    )
-    .unwrap();
-    write!(
-        stdout(),
-        "Use arrow keys as buttons. Press Ctrl-C to exit.\n\r"
-    )
-    .unwrap();
+    print!("Use a terminal supporting the kitty keyboard protocol.\n\r");
+    print!("Use arrow keys as buttons, number keys to toggle inputs. Press Ctrl-C to exit.\n\r");
+    print!("Vacation mode seed: {}", jitter_seed);
+    if let Some(host_link) = host_link {
+        print!("  Serial commands on {}", host_link);
+    }
+    print!("\n\r");
     //stdout().execute(crossterm::cursor::SavePosition)?;
     loop {
         let mut signals = SignalFlags::none();
@@ -228,8 +432,12 @@ fn run() -> Result<()> {
                 }) => {
                     if let Some(pressed_button) = map_key_codes(code) {
                         let mut buttons = buttons.lock().unwrap();
-                        *buttons = *buttons | pressed_button;
-                        signals = signals | SignalFlags::Button;
+                        *buttons |= pressed_button;
+                        signals |= SignalFlags::Button;
+                    }
+                    if let KeyCode::Char(c @ '1'..='8') = code {
+                        *inputs.lock().unwrap() ^= 1 << (c as u8 - b'1');
+                        signals |= SignalFlags::Input;
                     }
                 }
                 Cev::Key(KeyEvent {
@@ -240,27 +448,43 @@ fn run() -> Result<()> {
                 }) => {
                     if let Some(released_button) = map_key_codes(code) {
                         let mut buttons = buttons.lock().unwrap();
-                        *buttons = *buttons & (!released_button);
-                        signals = signals | SignalFlags::Button;
+                        *buttons &= !released_button;
+                        signals |= SignalFlags::Button;
                     }
                 }
                 _ => {}
             }
         }
         stdout().queue(crossterm::cursor::MoveTo(0, 1)).unwrap();
-        write!(
-            stdout(),
-            "Button states: {:04b}",
-            buttons.lock().unwrap().bits()
-        )
-        .unwrap();
+        print!("Button states: {:04b}", buttons.lock().unwrap().bits());
         let relay_bits: String = relay_states
             .lock()
             .unwrap()
             .iter()
+            .take(relay_count)
             .map(|b| if *b { '1' } else { '0' })
             .collect();
-        write!(stdout(), "  Relay states: {}", relay_bits).unwrap();
+        print!("  Relay states: {}", relay_bits);
+        let input_flags = *inputs.lock().unwrap();
+        let input_bits: String = (0..MAX_INPUTS)
+            .map(|k| char::from(b'0' + (input_flags >> k & 1)))
+            .collect();
+        print!("  Input states: {}", input_bits);
+        let temperature = simulated_temperature(start_instant.elapsed());
+        print!("  Sensor: {:5.1}", temperature as f64 / 10.0);
+        let newest_event = event_log.newest();
+        if let Some(entry) = newest_event.filter(|_| newest_event != shown_event) {
+            stdout().queue(crossterm::cursor::MoveTo(0, 11)).unwrap();
+            stdout()
+                .queue(terminal::Clear(terminal::ClearType::CurrentLine))
+                .unwrap();
+            let time = entry.time;
+            print!(
+                "Last event: #{} {:02}:{:02}:{:02} UTC {:?}",
+                entry.sequence, time.hour, time.minute, time.second, entry.event
+            );
+            shown_event = newest_event;
+        }
         stdout().flush().unwrap();
         /* This was for testing the original C library interface
         let mut screen : [u32;3] = [0;3];
@@ -275,22 +499,50 @@ fn run() -> Result<()> {
         write!(stdout(),"{}",s)?;
         stdout().flush()?;
         */
+        if !serial_input.lock().unwrap().is_empty() {
+            signals |= SignalFlags::Serial;
+        }
         let instant = instantmillis_from_duration(start_instant.elapsed());
         let Some(delay_request) = executor.step(instant, signals) else {
             break;
         };
-        next_delay_millis = i32::min(delay_request, 2000);
+        // the bytes received over serial are only noticed here
+        let max_delay = if host_link.is_some() { 50 } else { 2000 };
+        next_delay_millis = i32::min(delay_request, max_delay);
     }
     Ok(())
 }
 
 fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let host_link = match args
+        .iter()
+        .position(|arg| arg == "--serial" || arg == "--tcp")
+    {
+        Some(index) => {
+            let Some(value) = args.get(index + 1) else {
+                anyhow::bail!("{} needs a value", args[index]);
+            };
+            let link = match args[index].as_str() {
+                "--serial" => HostLink::Serial(PathBuf::from(value)),
+                _ => HostLink::Tcp(value.parse()?),
+            };
+            args.drain(index..index + 2);
+            Some(link)
+        }
+        None => None,
+    };
+    let relay_count = match args.first() {
+        Some(arg) => arg.parse::<usize>()?.clamp(1, MAX_RELAYS),
+        None => DEFAULT_NUM_RELAYS,
+    };
+    let jitter_seed = args.get(1).map(|arg| arg.parse::<u64>()).transpose()?;
     terminal::enable_raw_mode()?;
     stdout().execute(PushKeyboardEnhancementFlags(
         KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
     ))?;
-    let res = run();
-    stdout().queue(crossterm::cursor::MoveTo(0, 11))?;
+    let res = run(relay_count, jitter_seed, host_link.as_ref());
+    stdout().queue(crossterm::cursor::MoveTo(0, 12))?;
     stdout().execute(PopKeyboardEnhancementFlags)?;
     terminal::disable_raw_mode()?;
     res
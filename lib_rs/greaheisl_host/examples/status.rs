@@ -0,0 +1,56 @@
+//! shows the state of the emulator, started with `--tcp <port>`
+//!
+//! Usage: `cargo run -p greaheisl_host --example status -- <port> [--sync-clock]`
+//!
+//! With `--sync-clock`, the clock of the emulator is set to the UTC time of the PC first.
+
+use greaheisl_host::Client;
+use greaheisl_lib::system::RtcTime;
+use greaheisl_lib::{StartMode, DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS};
+use std::time::SystemTime;
+
+/// the current UTC time of the PC
+fn system_time() -> RtcTime {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    RtcTime::from_minutes_since_epoch((seconds / 60) as i32, (seconds % 60) as u8)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let port: u16 = std::env::args()
+        .nth(1)
+        .ok_or("usage: status <port> [--sync-clock]")?
+        .parse()?;
+    let mut client = Client::connect_tcp(("127.0.0.1", port))?;
+    if std::env::args().any(|arg| arg == "--sync-clock") {
+        client.set_time(&system_time())?;
+    }
+    let info = client.info()?;
+    println!("{:?}", info);
+    let time = client.time()?;
+    println!(
+        "clock: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        time.year, time.month, time.day, time.hour, time.minute, time.second
+    );
+    let states = client.relay_states()?;
+    println!("relays: {:?}", states);
+    let settings = client.settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>()?;
+    for (relay, entries) in settings.scheduled_out.iter().enumerate() {
+        for (slot, entry) in entries.iter().enumerate().filter(|(_, e)| e.duration > 0) {
+            let start = match entry.start_mode {
+                StartMode::Clock => format!("{:02}:{:02}", entry.start_hour, entry.start_minute),
+                StartMode::Sunrise => format!("sunrise {:+} min", entry.sun_offset),
+                StartMode::Sunset => format!("sunset {:+} min", entry.sun_offset),
+            };
+            println!(
+                "relay {} entry {}: {} for {} s",
+                relay + 1,
+                slot + 1,
+                start,
+                entry.duration / 1000
+            );
+        }
+    }
+    Ok(())
+}
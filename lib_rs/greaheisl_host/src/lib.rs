@@ -0,0 +1,200 @@
+//! client for the binary host protocol of `greaheisl_lib`, for companion apps
+//!
+//! A [`Client`] sends requests over any byte stream connected to the device,
+//! e.g. a serial port, and waits for the responses.
+//! See [`greaheisl_lib::host_protocol`] for the protocol itself.
+//!
+//! For testing, the emulator answers the protocol on a TCP port of the local host
+//! if it is started with `--tcp <port>`.
+//!
+//! ## example
+//!
+//! ```no_run
+//! use greaheisl_host::Client;
+//! use greaheisl_lib::{DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS};
+//!
+//! let mut client = Client::connect_tcp(("127.0.0.1", 5555))?;
+//! let mut settings = client.settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>()?;
+//! settings.scheduled_out[0][0].start_hour = 7;
+//! settings.scheduled_out[0][0].duration = 30 * 60 * 1000;
+//! client.put_settings(&settings)?;
+//! # Ok::<(), greaheisl_host::Error>(())
+//! ```
+
+use greaheisl_lib::host_protocol::{
+    DeviceInfo, ErrorCode, FrameDecoder, Message, Request, Response,
+};
+use greaheisl_lib::storage::{decode_settings, encode_settings, StorageError};
+use greaheisl_lib::system::RtcTime;
+use greaheisl_lib::Settings;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// how long [`Client::connect_tcp`] waits for a response
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// why a request has failed
+#[derive(Debug)]
+pub enum Error {
+    /// the connection has failed or timed out
+    Io(io::Error),
+    /// the response cannot be decoded
+    Protocol(ErrorCode),
+    /// the device has rejected the request
+    Device(ErrorCode),
+    /// the device has answered with a response of another type
+    UnexpectedResponse,
+    /// the settings sent by the device cannot be decoded
+    Settings(StorageError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "connection failed: {}", error),
+            Error::Protocol(code) => write!(f, "bad response: {:?}", code),
+            Error::Device(code) => write!(f, "request rejected: {:?}", code),
+            Error::UnexpectedResponse => write!(f, "unexpected response"),
+            Error::Settings(error) => write!(f, "bad settings: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// talks to a device through the host protocol, one request at a time
+///
+/// The stream should time out on reading, so a lost response does not block forever.
+/// A request that failed with [`Error::Io`] can be repeated;
+/// late responses to earlier requests are skipped by their request id.
+pub struct Client<S: Read + Write> {
+    stream: BufReader<S>,
+    frames: FrameDecoder,
+    next_request_id: u16,
+}
+
+impl Client<TcpStream> {
+    /// connects to the emulator started with `--tcp <port>`
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S: Read + Write> Client<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            frames: FrameDecoder::new(),
+            next_request_id: 0,
+        }
+    }
+
+    /// sends a request and waits for its response
+    ///
+    /// [`Response::Error`] is returned as [`Error::Device`].
+    pub fn request(&mut self, request: Request) -> Result<Response, Error> {
+        let request_id = self.next_request_id;
+        self.next_request_id = request_id.wrapping_add(1);
+        let frame = Message {
+            request_id,
+            body: request,
+        }
+        .encode();
+        let stream = self.stream.get_mut();
+        stream.write_all(&frame)?;
+        stream.flush()?;
+        // a frame cut off by an earlier timeout is of no use
+        self.frames.reset();
+        loop {
+            let message = self.read_message()?;
+            let response = Message::<Response>::decode(&message).map_err(Error::Protocol)?;
+            if response.request_id != request_id {
+                continue;
+            }
+            return match response.body {
+                Response::Error(code) => Err(Error::Device(code)),
+                body => Ok(body),
+            };
+        }
+    }
+
+    /// reads until a frame is complete, skipping everything else
+    fn read_message(&mut self) -> Result<Vec<u8>, Error> {
+        let mut byte = [0u8];
+        loop {
+            self.stream.read_exact(&mut byte)?;
+            if let Some(message) = self.frames.push(byte[0]) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// the configuration of the device
+    pub fn info(&mut self) -> Result<DeviceInfo, Error> {
+        match self.request(Request::Info)? {
+            Response::Info(info) => Ok(info),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// the time of the real time clock, in UTC
+    pub fn time(&mut self) -> Result<RtcTime, Error> {
+        match self.request(Request::GetTime)? {
+            Response::Time(time) => Ok(time),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// sets the real time clock to `time`, in UTC; the day of the week is ignored
+    pub fn set_time(&mut self, time: &RtcTime) -> Result<(), Error> {
+        match self.request(Request::SetTime(*time))? {
+            Response::Ack => Ok(()),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// downloads the settings
+    ///
+    /// The numbers of relays and scheduled entries need not match those of the device,
+    /// see [`greaheisl_lib::storage`] for how the settings are adapted.
+    pub fn settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+        &mut self,
+    ) -> Result<Settings<NUM_RELAYS, NUM_SLOTS>, Error> {
+        match self.request(Request::GetSettings)? {
+            Response::Settings(blob) => decode_settings(&blob).map_err(Error::Settings),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// uploads the settings; running immediate timers are kept
+    pub fn put_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+        &mut self,
+        settings: &Settings<NUM_RELAYS, NUM_SLOTS>,
+    ) -> Result<(), Error> {
+        match self.request(Request::PutSettings(encode_settings(settings)))? {
+            Response::Ack => Ok(()),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// the states of the relays connected
+    pub fn relay_states(&mut self) -> Result<Vec<bool>, Error> {
+        match self.request(Request::GetRelays)? {
+            Response::Relays(states) => Ok(states),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Client, Error};
+use greaheisl_lib::host_protocol::{ErrorCode, FrameDecoder, Message, Request, Response};
+use greaheisl_lib::storage::encode_settings;
+use greaheisl_lib::{Settings, DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS};
+use std::io::{Cursor, Read, Write};
+
+/// a device answering with canned bytes, recording what it receives
+struct FakeDevice {
+    output: Cursor<Vec<u8>>,
+    received: Vec<u8>,
+}
+
+impl FakeDevice {
+    fn new(responses: &[&[u8]]) -> Self {
+        Self {
+            output: Cursor::new(responses.concat()),
+            received: Vec::new(),
+        }
+    }
+
+    fn requests(&self) -> Vec<Message<Request>> {
+        let mut frames = FrameDecoder::new();
+        self.received
+            .iter()
+            .filter_map(|byte| frames.push(*byte))
+            .map(|message| Message::decode(&message).unwrap())
+            .collect()
+    }
+}
+
+impl Read for FakeDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.output.read(buf)
+    }
+}
+
+impl Write for FakeDevice {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.received.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn frame(request_id: u16, body: Response) -> Vec<u8> {
+    Message { request_id, body }.encode()
+}
+
+#[test]
+fn text_and_stale_responses_are_skipped() {
+    let device = FakeDevice::new(&[
+        b"2024-05-01 07:30 relay 1 on (scheduled 1)\r\n",
+        &frame(41, Response::Ack),
+        &frame(0, Response::Relays(vec![true, false, false, true])),
+    ]);
+    let mut client = Client::new(device);
+    assert_eq!(
+        client.relay_states().unwrap(),
+        vec![true, false, false, true]
+    );
+    let requests = client.stream.get_ref().requests();
+    assert_eq!(
+        requests,
+        vec![Message {
+            request_id: 0,
+            body: Request::GetRelays,
+        }]
+    );
+}
+
+#[test]
+fn settings_are_exchanged_as_blobs() {
+    let mut settings = Settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>::default();
+    settings.scheduled_out[1][2].start_hour = 7;
+    settings.scheduled_out[1][2].duration = 15 * 60 * 1000;
+    let blob = encode_settings(&settings);
+    let device = FakeDevice::new(&[
+        &frame(0, Response::Settings(blob.clone())),
+        &frame(1, Response::Error(ErrorCode::InvalidValue)),
+    ]);
+    let mut client = Client::new(device);
+    let downloaded = client
+        .settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>()
+        .unwrap();
+    assert_eq!(encode_settings(&downloaded), blob);
+    let result = client.put_settings(&downloaded);
+    assert!(matches!(
+        result,
+        Err(Error::Device(ErrorCode::InvalidValue))
+    ));
+    let requests = client.stream.get_ref().requests();
+    assert_eq!(requests[1].body, Request::PutSettings(blob));
+}
+
+#[test]
+fn missing_response_is_an_io_error() {
+    let mut client = Client::new(FakeDevice::new(&[]));
+    assert!(matches!(client.info(), Err(Error::Io(_))));
+}
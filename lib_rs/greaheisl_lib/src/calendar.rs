@@ -0,0 +1,67 @@
+//! calendar calculations for the proleptic Gregorian calendar
+//!
+//! Days are counted from 1970-01-01. The conversions between
+//! civil dates and day counts follow the algorithms described by
+//! Howard Hinnant in
+//! [chrono-Compatible Low-Level Date Algorithms](https://howardhinnant.github.io/date_algorithms.html).
+
+/// the smallest year supported by the user interface
+pub const MIN_YEAR: u16 = 2000;
+/// the largest year supported by the user interface
+pub const MAX_YEAR: u16 = 2099;
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// number of days in the given month (1..=12)
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// checks whether year, month and day denote an existing day
+pub fn is_valid_date(year: u16, month: u8, day: u8) -> bool {
+    (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month)
+}
+
+/// number of days since 1970-01-01
+pub fn days_from_civil(year: u16, month: u8, day: u8) -> i32 {
+    let year = year as i32 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i32;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// inverse of [`days_from_civil`]; returns year, month and day
+pub fn civil_from_days(days: i32) -> (u16, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u16, month as u8, day as u8)
+}
+
+/// day of the week (0 = Monday, ..., 6 = Sunday) for a day count since 1970-01-01
+pub fn weekday_from_days(days: i32) -> u8 {
+    // 1970-01-01 was a Thursday
+    (days + 3).rem_euclid(7) as u8
+}
+
+/// number of minutes since 1970-01-01 00:00
+pub fn minutes_from_civil(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> i32 {
+    days_from_civil(year, month, day) * 24 * 60 + hour as i32 * 60 + minute as i32
+}
@@ -0,0 +1,496 @@
+//! a line-based text protocol to configure the device from a host computer
+//!
+//! The host sends one command per line, terminated by a carriage return, a line feed, or both,
+//! through [`AccessSerial`]. Keywords are not case sensitive.
+//! Every command is answered by zero or more lines of data,
+//! followed by a line with `OK` or with `ERR` and the reason, see [`CommandError`].
+//!
+//! | command                              | effect                                              |
+//! |--------------------------------------|-----------------------------------------------------|
+//! | `HELP`                               | lists the commands                                  |
+//! | `TIME`                               | shows the local time as `2024-05-01 07:30:00`       |
+//! | `TIME <date> <time>`                 | sets the clock to the given local time; seconds are optional |
+//! | `LIST [<relay>]`                     | lists the scheduled entries, of all relays or of one |
+//! | `EDIT <relay> <slot> <start> <duration> [<weekdays>]` | changes a scheduled entry          |
+//! | `CLEAR <relay> <slot>`               | deactivates a scheduled entry                       |
+//! | `START <relay> <duration>`           | starts the immediate timer; a duration of 0 stops it |
+//! | `RELAYS`                             | shows the relay states, e.g. `1001`                 |
+//!
+//! Relays and slots are counted from 1, like in the menus.
+//! A start is a time of day like `07:30`, or an offset in minutes from sunrise (`SR+30`)
+//! or sunset (`SS-15`). A duration is a number with the unit `S`, `M` (the default) or `H`,
+//! e.g. `90M`, up to a day. The days of the week are given by seven digits, Monday first,
+//! e.g. `1111100` for the workdays; if they are left out, they stay as they are.
+//! `LIST` prints each entry as `<relay> <slot> <start> <duration> <weekdays>`,
+//! so its lines can be passed to `EDIT` as they are.
+//!
+//! The commands work on the same settings as the menus, and changes are stored the same way.
+
+use crate::event_log::EventLog;
+use crate::host_protocol::{self, FrameDecoder, FRAME_START, FRAME_TIMEOUT};
+use crate::system::{
+    wait_event, wait_stop_or_event_timeout, AccessOutputStates, AccessPersistentStorage, AccessRtc,
+    AccessSerial, RtcTime, SignalFlags,
+};
+use crate::{
+    calendar, set_clock, update_settings, ImmediateOutEntry, OutputStatus, ScheduledOutEntry,
+    Settings, StartMode, Weekdays,
+};
+use alloc::string::String;
+use core::cell::{Cell, RefCell};
+use core::fmt::Write;
+use greaheisl_async::{AccessExecutorSignals, AccessTiming, DurationMillis, Notify};
+
+/// the longest line accepted, in bytes
+pub const MAX_LINE_LENGTH: usize = 64;
+
+/// the longest duration accepted
+pub const MAX_DURATION: DurationMillis = 24 * 60 * 60 * 1000;
+
+/// why a command has been rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// the keyword is not known
+    Unknown,
+    /// the arguments are missing or malformed
+    Syntax,
+    /// a relay, slot, date, time or duration is out of range
+    Range,
+    /// the line exceeds [`MAX_LINE_LENGTH`]
+    TooLong,
+}
+
+impl CommandError {
+    /// the reason given after `ERR`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommandError::Unknown => "unknown command",
+            CommandError::Syntax => "syntax",
+            CommandError::Range => "out of range",
+            CommandError::TooLong => "line too long",
+        }
+    }
+}
+
+/// the start of a scheduled entry, see [`ScheduledOutEntry::start_mode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Start {
+    Clock { hour: u8, minute: u8 },
+    Sunrise { offset: i16 },
+    Sunset { offset: i16 },
+}
+
+/// a parsed command line; relays and slots are indices counted from 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    GetTime,
+    /// sets the clock to the given local time
+    SetTime(RtcTime),
+    List {
+        relay: Option<usize>,
+    },
+    Edit {
+        relay: usize,
+        slot: usize,
+        start: Start,
+        duration: DurationMillis,
+        weekdays: Option<Weekdays>,
+    },
+    Clear {
+        relay: usize,
+        slot: usize,
+    },
+    Start {
+        relay: usize,
+        duration: DurationMillis,
+    },
+    Relays,
+}
+
+impl Command {
+    /// parses a line without its line terminator
+    pub fn parse(line: &str) -> Result<Self, CommandError> {
+        let mut words = line.split_ascii_whitespace();
+        let keyword = words.next().ok_or(CommandError::Syntax)?;
+        let mut args = [""; 5];
+        let mut num_args = 0;
+        for word in words {
+            *args.get_mut(num_args).ok_or(CommandError::Syntax)? = word;
+            num_args += 1;
+        }
+        let is = |name: &str| keyword.eq_ignore_ascii_case(name);
+        let command = match (num_args, args) {
+            (0, _) if is("HELP") => Command::Help,
+            (0, _) if is("TIME") => Command::GetTime,
+            (2, [date, time, ..]) if is("TIME") => Command::SetTime(parse_time(date, time)?),
+            (0, _) if is("LIST") => Command::List { relay: None },
+            (1, [relay, ..]) if is("LIST") => Command::List {
+                relay: Some(parse_index(relay)?),
+            },
+            (4..=5, [relay, slot, start, duration, weekdays]) if is("EDIT") => Command::Edit {
+                relay: parse_index(relay)?,
+                slot: parse_index(slot)?,
+                start: parse_start(start)?,
+                duration: parse_duration(duration)?,
+                weekdays: match num_args {
+                    5 => Some(parse_weekdays(weekdays)?),
+                    _ => None,
+                },
+            },
+            (2, [relay, slot, ..]) if is("CLEAR") => Command::Clear {
+                relay: parse_index(relay)?,
+                slot: parse_index(slot)?,
+            },
+            (2, [relay, duration, ..]) if is("START") => Command::Start {
+                relay: parse_index(relay)?,
+                duration: parse_duration(duration)?,
+            },
+            (0, _) if is("RELAYS") => Command::Relays,
+            _ if ["HELP", "TIME", "LIST", "EDIT", "CLEAR", "START", "RELAYS"]
+                .into_iter()
+                .any(is) =>
+            {
+                return Err(CommandError::Syntax)
+            }
+            _ => return Err(CommandError::Unknown),
+        };
+        Ok(command)
+    }
+}
+
+/// a relay or slot number counted from 1, as an index counted from 0
+fn parse_index(text: &str) -> Result<usize, CommandError> {
+    let number: usize = text.parse().map_err(|_| CommandError::Syntax)?;
+    number.checked_sub(1).ok_or(CommandError::Range)
+}
+
+/// two numbers separated by `separator`, e.g. `07:30`
+fn parse_pair(text: &str, separator: char) -> Result<(u8, u8), CommandError> {
+    let (first, second) = text.split_once(separator).ok_or(CommandError::Syntax)?;
+    let first = first.parse().map_err(|_| CommandError::Syntax)?;
+    let second = second.parse().map_err(|_| CommandError::Syntax)?;
+    Ok((first, second))
+}
+
+/// a date like `2024-05-01` and a time of day like `07:30` or `07:30:15`
+fn parse_time(date: &str, time: &str) -> Result<RtcTime, CommandError> {
+    let (year, month_day) = date.split_once('-').ok_or(CommandError::Syntax)?;
+    let year: u16 = year.parse().map_err(|_| CommandError::Syntax)?;
+    let (month, day) = parse_pair(month_day, '-')?;
+    let (hour, minute_second) = time.split_once(':').ok_or(CommandError::Syntax)?;
+    let hour: u8 = hour.parse().map_err(|_| CommandError::Syntax)?;
+    let (minute, second) = match minute_second.split_once(':') {
+        Some(_) => parse_pair(minute_second, ':')?,
+        None => (minute_second.parse().map_err(|_| CommandError::Syntax)?, 0),
+    };
+    if !(2000..=2099).contains(&year)
+        || !calendar::is_valid_date(year, month, day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(CommandError::Range);
+    }
+    let minutes = calendar::minutes_from_civil(year, month, day, hour, minute);
+    Ok(RtcTime::from_minutes_since_epoch(minutes, second))
+}
+
+/// `07:30`, `SR+30` or `SS-15`
+fn parse_start(text: &str) -> Result<Start, CommandError> {
+    let sun_offset = |offset: &str| -> Result<i16, CommandError> {
+        let offset: i16 = match offset {
+            "" => 0,
+            _ => offset.parse().map_err(|_| CommandError::Syntax)?,
+        };
+        if offset.abs() > ScheduledOutEntry::MAX_SUN_OFFSET {
+            return Err(CommandError::Range);
+        }
+        Ok(offset)
+    };
+    let prefix = text.get(..2).unwrap_or("");
+    if prefix.eq_ignore_ascii_case("SR") {
+        return Ok(Start::Sunrise {
+            offset: sun_offset(&text[2..])?,
+        });
+    }
+    if prefix.eq_ignore_ascii_case("SS") {
+        return Ok(Start::Sunset {
+            offset: sun_offset(&text[2..])?,
+        });
+    }
+    let (hour, minute) = parse_pair(text, ':')?;
+    if hour > 23 || minute > 59 {
+        return Err(CommandError::Range);
+    }
+    Ok(Start::Clock { hour, minute })
+}
+
+/// a number with an optional unit `S`, `M` or `H`, in milliseconds
+fn parse_duration(text: &str) -> Result<DurationMillis, CommandError> {
+    let (number, unit) = match text.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&text[..index], unit),
+        _ => (text, 'M'),
+    };
+    let unit: DurationMillis = match unit.to_ascii_uppercase() {
+        'S' => 1000,
+        'M' => 60 * 1000,
+        'H' => 60 * 60 * 1000,
+        _ => return Err(CommandError::Syntax),
+    };
+    let number: DurationMillis = number.parse().map_err(|_| CommandError::Syntax)?;
+    match number.checked_mul(unit) {
+        Some(duration @ 0..=MAX_DURATION) => Ok(duration),
+        _ => Err(CommandError::Range),
+    }
+}
+
+/// seven digits `0` or `1`, Monday first
+fn parse_weekdays(text: &str) -> Result<Weekdays, CommandError> {
+    if text.len() != 7 {
+        return Err(CommandError::Syntax);
+    }
+    let mut weekdays = Weekdays::none();
+    for (weekday, digit) in text.bytes().enumerate() {
+        match digit {
+            b'0' => {}
+            b'1' => weekdays |= Weekdays::from_weekday(weekday as u8),
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+    Ok(weekdays)
+}
+
+/// writes a duration in the largest unit that represents it exactly
+fn write_duration(out: &mut String, duration: DurationMillis) -> core::fmt::Result {
+    match duration {
+        d if d % (60 * 60 * 1000) == 0 && d > 0 => write!(out, "{}H", d / (60 * 60 * 1000)),
+        d if d % (60 * 1000) == 0 => write!(out, "{}M", d / (60 * 1000)),
+        d => write!(out, "{}S", d / 1000),
+    }
+}
+
+/// writes a line in the format accepted by `EDIT`
+pub(crate) fn write_entry(
+    out: &mut String,
+    relay: usize,
+    slot: usize,
+    entry: &ScheduledOutEntry,
+) -> core::fmt::Result {
+    write!(out, "{} {} ", relay + 1, slot + 1)?;
+    match entry.start_mode {
+        StartMode::Clock => write!(out, "{:02}:{:02} ", entry.start_hour, entry.start_minute)?,
+        StartMode::Sunrise => write!(out, "SR{:+} ", entry.sun_offset)?,
+        StartMode::Sunset => write!(out, "SS{:+} ", entry.sun_offset)?,
+    }
+    write_duration(out, entry.duration)?;
+    out.push(' ');
+    for weekday in 0..7 {
+        let is_set = entry.weekdays.contains(Weekdays::from_weekday(weekday));
+        out.push(if is_set { '1' } else { '0' });
+    }
+    out.push_str("\r\n");
+    Ok(())
+}
+
+/// runs a command, appending its lines of data to `out`
+///
+/// The settings are changed in place, through [`update_settings`].
+pub(crate) fn execute<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessTiming + AccessRtc + AccessPersistentStorage + AccessOutputStates<NUM_RELAYS>),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    command: Command,
+    out: &mut String,
+) -> Result<(), CommandError> {
+    let relay_count = sys.relay_count();
+    let check_relay = |relay: usize| {
+        if relay < relay_count {
+            Ok(relay)
+        } else {
+            Err(CommandError::Range)
+        }
+    };
+    let check_slot = |slot: usize| {
+        if slot < NUM_SLOTS {
+            Ok(slot)
+        } else {
+            Err(CommandError::Range)
+        }
+    };
+    match command {
+        Command::Help => {
+            out.push_str("HELP TIME LIST EDIT CLEAR START RELAYS\r\n");
+        }
+        Command::GetTime => {
+            let time = settings.borrow().time_zone.to_local(&sys.get_rtc());
+            let _ = write!(
+                out,
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}\r\n",
+                time.year, time.month, time.day, time.hour, time.minute, time.second
+            );
+        }
+        Command::SetTime(time) => {
+            let time_zone = settings.borrow().time_zone;
+            set_clock(sys, &time_zone.to_utc(&time), settings_changed, event_log);
+        }
+        Command::List { relay } => {
+            let relays = match relay {
+                Some(relay) => check_relay(relay)?..relay + 1,
+                None => 0..relay_count,
+            };
+            let settings = settings.borrow();
+            for relay in relays {
+                for (slot, entry) in settings.scheduled_out[relay].iter().enumerate() {
+                    let _ = write_entry(out, relay, slot, entry);
+                }
+            }
+        }
+        Command::Edit {
+            relay,
+            slot,
+            start,
+            duration,
+            weekdays,
+        } => {
+            let (relay, slot) = (check_relay(relay)?, check_slot(slot)?);
+            update_settings(sys, settings, settings_changed, event_log, |settings| {
+                let entry = &mut settings.scheduled_out[relay][slot];
+                match start {
+                    Start::Clock { hour, minute } => {
+                        entry.start_mode = StartMode::Clock;
+                        entry.start_hour = hour;
+                        entry.start_minute = minute;
+                    }
+                    Start::Sunrise { offset } => {
+                        entry.start_mode = StartMode::Sunrise;
+                        entry.sun_offset = offset;
+                    }
+                    Start::Sunset { offset } => {
+                        entry.start_mode = StartMode::Sunset;
+                        entry.sun_offset = offset;
+                    }
+                }
+                entry.duration = duration;
+                if let Some(weekdays) = weekdays {
+                    entry.weekdays = weekdays;
+                }
+            });
+        }
+        Command::Clear { relay, slot } => {
+            let (relay, slot) = (check_relay(relay)?, check_slot(slot)?);
+            update_settings(sys, settings, settings_changed, event_log, |settings| {
+                settings.scheduled_out[relay][slot] = ScheduledOutEntry::default();
+            });
+        }
+        Command::Start { relay, duration } => {
+            let relay = check_relay(relay)?;
+            let entry = (duration > 0).then(|| ImmediateOutEntry {
+                start: sys.get_instant(),
+                duration,
+            });
+            update_settings(sys, settings, settings_changed, event_log, |settings| {
+                settings.immediate_out[relay] = entry;
+            });
+        }
+        Command::Relays => {
+            for state in status.relay_states.get().into_iter().take(relay_count) {
+                out.push(if state { '1' } else { '0' });
+            }
+            out.push_str("\r\n");
+        }
+    }
+    Ok(())
+}
+
+/// the task answering the commands received through [`AccessSerial`]
+///
+/// A byte [`FRAME_START`] at the beginning of a line starts a frame
+/// of the binary [`crate::host_protocol`] instead.
+/// It is woken up by [`SignalFlags::Serial`].
+pub(crate) async fn run_commands<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessSerial
+          + AccessExecutorSignals<SignalFlags>
+          + AccessTiming
+          + AccessRtc
+          + AccessPersistentStorage
+          + AccessOutputStates<NUM_RELAYS>),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+) {
+    let mut line = [0u8; MAX_LINE_LENGTH];
+    // counts the bytes beyond the buffer as well
+    let mut len = 0usize;
+    let mut frames = FrameDecoder::new();
+    loop {
+        while let Some(byte) = sys.read_serial() {
+            if frames.is_receiving() || (len == 0 && byte == FRAME_START) {
+                let response = frames.push(byte).and_then(|message| {
+                    host_protocol::respond(
+                        sys,
+                        settings,
+                        settings_changed,
+                        status,
+                        event_log,
+                        &message,
+                    )
+                });
+                if let Some(response) = response {
+                    sys.write_serial(&response);
+                }
+                continue;
+            }
+            if byte != b'\r' && byte != b'\n' {
+                if let Some(slot) = line.get_mut(len) {
+                    *slot = byte;
+                }
+                len = len.saturating_add(1);
+                continue;
+            }
+            if len == 0 {
+                // an empty line, or the second half of a CR LF
+                continue;
+            }
+            let mut out = String::new();
+            let result = line
+                .get(..len)
+                .ok_or(CommandError::TooLong)
+                .and_then(|line| core::str::from_utf8(line).map_err(|_| CommandError::Syntax))
+                .and_then(Command::parse)
+                .and_then(|command| {
+                    execute(
+                        sys,
+                        settings,
+                        settings_changed,
+                        status,
+                        event_log,
+                        command,
+                        &mut out,
+                    )
+                });
+            len = 0;
+            match result {
+                Ok(()) => out.push_str("OK\r\n"),
+                Err(error) => {
+                    let _ = write!(out, "ERR {}\r\n", error.as_str());
+                }
+            }
+            sys.write_serial(out.as_bytes());
+        }
+        if !frames.is_receiving() {
+            wait_event(sys, SignalFlags::Serial).await;
+            continue;
+        }
+        let never = Cell::new(false);
+        let signals =
+            wait_stop_or_event_timeout(sys, &never, SignalFlags::Serial, FRAME_TIMEOUT).await;
+        if matches!(signals, Ok(signals) if signals.is_none()) {
+            // the rest of the frame is not coming
+            frames.reset();
+        }
+    }
+}
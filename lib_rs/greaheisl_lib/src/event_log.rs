@@ -0,0 +1,123 @@
+//! a log of what has happened to the relays and the settings
+//!
+//! The [`EventLog`] keeps the most recent [`LogEntry`]s in a ring buffer of fixed capacity,
+//! so older entries are overwritten once it is full.
+//! Every entry has a sequence number, counting all entries ever recorded.
+//! A gap in the sequence numbers shows that entries have been overwritten
+//! before they were read.
+
+use crate::system::RtcTime;
+use core::cell::{Cell, RefCell};
+
+/// the number of entries kept by default
+pub const EVENT_LOG_CAPACITY: usize = 64;
+
+/// why a relay has been switched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// the immediate timer
+    Immediate,
+    /// the scheduled entry with the given index
+    Scheduled { slot: u8 },
+    /// the timer on a given date
+    Dated,
+    /// the cycle timer
+    Cycle,
+    /// the thermostat
+    Thermostat,
+    /// a rule for the digital inputs
+    Input,
+    /// the override mode
+    Override,
+    /// the interlocks held the relay back
+    Interlock,
+}
+
+/// something worth recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// a relay has been switched on
+    RelayOn { relay: u8, cause: Cause },
+    /// a relay has been switched off; unless the override mode or the interlocks
+    /// did it, the cause is the one that had switched it on
+    RelayOff { relay: u8, cause: Cause },
+    /// the real time clock has been set; the time of the entry is the new time
+    ClockSet,
+    /// changed settings have been written to non-volatile memory
+    SettingsCommitted,
+}
+
+/// an [`Event`] with the time it was recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    /// counts all entries ever recorded, starting at 0
+    pub sequence: u32,
+    /// the time of the real time clock, in UTC
+    pub time: RtcTime,
+    pub event: Event,
+}
+
+/// ring buffer of the last `CAPACITY` [`LogEntry`]s
+///
+/// Entries are recorded through a shared reference, so the log can be
+/// handed to all tasks at once.
+pub struct EventLog<const CAPACITY: usize = EVENT_LOG_CAPACITY> {
+    entries: RefCell<[Option<LogEntry>; CAPACITY]>,
+    /// the sequence number of the next entry
+    next_sequence: Cell<u32>,
+}
+
+impl<const CAPACITY: usize> Default for EventLog<CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CAPACITY: usize> EventLog<CAPACITY> {
+    pub fn new() -> Self {
+        Self {
+            entries: RefCell::new([None; CAPACITY]),
+            next_sequence: Cell::new(0),
+        }
+    }
+
+    /// appends an entry, overwriting the oldest one if the log is full
+    pub fn record(&self, time: RtcTime, event: Event) {
+        let sequence = self.next_sequence.get();
+        self.entries.borrow_mut()[sequence as usize % CAPACITY] = Some(LogEntry {
+            sequence,
+            time,
+            event,
+        });
+        self.next_sequence.set(sequence.wrapping_add(1));
+    }
+
+    /// the sequence number the next entry will get
+    pub fn next_sequence(&self) -> u32 {
+        self.next_sequence.get()
+    }
+
+    /// the entry with the given sequence number, if it is still kept
+    pub fn get(&self, sequence: u32) -> Option<LogEntry> {
+        let entry = self.entries.borrow()[sequence as usize % CAPACITY]?;
+        (entry.sequence == sequence).then_some(entry)
+    }
+
+    /// the most recent entry, if any
+    pub fn newest(&self) -> Option<LogEntry> {
+        self.get(self.next_sequence().wrapping_sub(1))
+    }
+
+    /// the oldest entry kept with a sequence number of at least `sequence`
+    ///
+    /// Use this to read the log incrementally, continuing with
+    /// the sequence number following the entry returned.
+    pub fn first_since(&self, sequence: u32) -> Option<LogEntry> {
+        let end = self.next_sequence();
+        let oldest = end.saturating_sub(CAPACITY as u32);
+        if sequence >= end {
+            return None;
+        }
+        self.get(sequence.max(oldest))
+    }
+}
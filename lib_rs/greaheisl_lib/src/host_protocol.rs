@@ -0,0 +1,420 @@
+//! a binary protocol for companion apps on a host computer
+//!
+//! The host sends a [`Request`] in a frame and gets a [`Response`] in a frame.
+//! The frames share the connection with the text commands of [`crate::commands`].
+//! A frame starts with [`FRAME_START`], which never starts a line of text,
+//! and the host skips everything between frames, e.g. the entries of the event log
+//! printed by the sketch.
+//!
+//! | bytes | content                                                     |
+//! |-------|-------------------------------------------------------------|
+//! | 1     | [`FRAME_START`]                                             |
+//! | 2     | length `n` of the message, at most [`MAX_MESSAGE_SIZE`]     |
+//! | `n`   | message                                                     |
+//! | 2     | CRC-16/CCITT of the length and the message, see [`crc16`]   |
+//!
+//! A message consists of
+//!
+//! | bytes | content                                                     |
+//! |-------|-------------------------------------------------------------|
+//! | 1     | [`PROTOCOL_VERSION`]                                        |
+//! | 2     | request id, chosen by the host and copied into the response |
+//! | 1     | message type, see [`Request`] and [`Response`]              |
+//! | ...   | body, depending on the message type                         |
+//!
+//! All multi-byte integers are in little endian byte order, like in module [`storage`].
+//! The settings are transferred as a blob of [`storage::encode_settings`],
+//! so a host can exchange them with devices of other configurations and versions.
+//! The clock is transferred in UTC.
+//! Frames with a wrong checksum are dropped without a response, as are frames
+//! not completed within [`FRAME_TIMEOUT`]; the host is expected to repeat the request.
+
+use crate::event_log::EventLog;
+use crate::storage::{self, crc16, settings_blob_size, StorageReader, StorageWriter};
+use crate::system::{AccessOutputStates, AccessPersistentStorage, AccessRtc, RtcTime};
+use crate::{calendar, set_clock, update_settings, OutputStatus, Settings};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use greaheisl_async::{AccessTiming, DurationMillis, Notify};
+
+/// the first byte of every frame
+pub const FRAME_START: u8 = 0xA5;
+
+/// needs to be incremented whenever the layout of a message changes
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// the largest message accepted, in bytes
+pub const MAX_MESSAGE_SIZE: usize = 2048;
+
+/// how long the device waits for the rest of a frame before dropping it
+pub const FRAME_TIMEOUT: DurationMillis = 1000;
+
+/// the bytes of a message before the body
+const MESSAGE_HEADER_SIZE: usize = 4;
+
+// the settings of every configuration the menus can show must fit into a message
+static_assertions::const_assert!(
+    MESSAGE_HEADER_SIZE + settings_blob_size(9, 9) <= MAX_MESSAGE_SIZE
+);
+
+/// why a request has been rejected, sent in [`Response::Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// the message was written for another [`PROTOCOL_VERSION`]
+    UnsupportedVersion = 1,
+    /// the message type is not known
+    UnknownType = 2,
+    /// the body does not match the message type
+    Malformed = 3,
+    /// a value is out of range, e.g. a date or a settings blob
+    InvalidValue = 4,
+}
+
+impl ErrorCode {
+    fn from_u8(code: u8) -> Option<Self> {
+        [
+            ErrorCode::UnsupportedVersion,
+            ErrorCode::UnknownType,
+            ErrorCode::Malformed,
+            ErrorCode::InvalidValue,
+        ]
+        .into_iter()
+        .find(|error| *error as u8 == code)
+    }
+}
+
+/// what the host asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// type 0x01, answered by [`Response::Info`]
+    Info,
+    /// type 0x02, answered by [`Response::Time`]
+    GetTime,
+    /// type 0x03, sets the clock to the given time in UTC; answered by [`Response::Ack`]
+    SetTime(RtcTime),
+    /// type 0x04, answered by [`Response::Settings`]
+    GetSettings,
+    /// type 0x05, replaces the settings by a blob of [`storage::encode_settings`];
+    /// answered by [`Response::Ack`]
+    PutSettings(Vec<u8>),
+    /// type 0x06, answered by [`Response::Relays`]
+    GetRelays,
+}
+
+/// the configuration of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// the [`PROTOCOL_VERSION`] of the device
+    pub protocol_version: u8,
+    /// the number of relays the settings have room for
+    pub num_relays: u8,
+    /// the number of scheduled entries per relay
+    pub num_slots: u8,
+    /// the number of relays actually connected
+    pub relay_count: u8,
+}
+
+/// what the device answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// type 0x80, the request has been carried out
+    Ack,
+    /// type 0x81
+    Info(DeviceInfo),
+    /// type 0x82, the time of the real time clock in UTC
+    Time(RtcTime),
+    /// type 0x84, a blob of [`storage::encode_settings`]
+    Settings(Vec<u8>),
+    /// type 0x86, the states of the relays connected
+    Relays(Vec<bool>),
+    /// type 0xFF
+    Error(ErrorCode),
+}
+
+/// a [`Request`] or a [`Response`] together with its request id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message<T> {
+    pub request_id: u16,
+    pub body: T,
+}
+
+/// the body of a [`Message`]
+pub trait MessageBody: Sized {
+    /// the message type
+    fn message_type(&self) -> u8;
+    fn store(&self, writer: &mut StorageWriter);
+    fn load(message_type: u8, reader: &mut StorageReader) -> Result<Self, ErrorCode>;
+}
+
+fn store_time(writer: &mut StorageWriter, time: &RtcTime) {
+    writer.put_u16(time.year);
+    for value in [time.month, time.day, time.hour, time.minute, time.second] {
+        writer.put_u8(value);
+    }
+}
+
+fn load_time(reader: &mut StorageReader) -> Result<RtcTime, ErrorCode> {
+    let year = reader.get_u16().map_err(|_| ErrorCode::Malformed)?;
+    let mut get_u8 = || reader.get_u8().map_err(|_| ErrorCode::Malformed);
+    let (month, day, hour, minute, second) =
+        (get_u8()?, get_u8()?, get_u8()?, get_u8()?, get_u8()?);
+    if !calendar::is_valid_date(year, month, day) || hour > 23 || minute > 59 || second > 59 {
+        return Err(ErrorCode::InvalidValue);
+    }
+    // the day of the week is derived from the date
+    let minutes = calendar::minutes_from_civil(year, month, day, hour, minute);
+    Ok(RtcTime::from_minutes_since_epoch(minutes, second))
+}
+
+impl MessageBody for Request {
+    fn message_type(&self) -> u8 {
+        match self {
+            Request::Info => 0x01,
+            Request::GetTime => 0x02,
+            Request::SetTime(_) => 0x03,
+            Request::GetSettings => 0x04,
+            Request::PutSettings(_) => 0x05,
+            Request::GetRelays => 0x06,
+        }
+    }
+
+    fn store(&self, writer: &mut StorageWriter) {
+        match self {
+            Request::SetTime(time) => store_time(writer, time),
+            Request::PutSettings(blob) => writer.put_bytes(blob),
+            Request::Info | Request::GetTime | Request::GetSettings | Request::GetRelays => {}
+        }
+    }
+
+    fn load(message_type: u8, reader: &mut StorageReader) -> Result<Self, ErrorCode> {
+        Ok(match message_type {
+            0x01 => Request::Info,
+            0x02 => Request::GetTime,
+            0x03 => Request::SetTime(load_time(reader)?),
+            0x04 => Request::GetSettings,
+            0x05 => Request::PutSettings(reader.get_rest().to_vec()),
+            0x06 => Request::GetRelays,
+            _ => return Err(ErrorCode::UnknownType),
+        })
+    }
+}
+
+impl MessageBody for Response {
+    fn message_type(&self) -> u8 {
+        match self {
+            Response::Ack => 0x80,
+            Response::Info(_) => 0x81,
+            Response::Time(_) => 0x82,
+            Response::Settings(_) => 0x84,
+            Response::Relays(_) => 0x86,
+            Response::Error(_) => 0xFF,
+        }
+    }
+
+    fn store(&self, writer: &mut StorageWriter) {
+        match self {
+            Response::Ack => {}
+            Response::Info(info) => {
+                writer.put_u8(info.protocol_version);
+                writer.put_u8(info.num_relays);
+                writer.put_u8(info.num_slots);
+                writer.put_u8(info.relay_count);
+            }
+            Response::Time(time) => store_time(writer, time),
+            Response::Settings(blob) => writer.put_bytes(blob),
+            Response::Relays(states) => {
+                writer.put_u8(states.len() as u8);
+                for &state in states {
+                    writer.put_u8(state as u8);
+                }
+            }
+            Response::Error(code) => writer.put_u8(*code as u8),
+        }
+    }
+
+    fn load(message_type: u8, reader: &mut StorageReader) -> Result<Self, ErrorCode> {
+        let mut get_u8 = || reader.get_u8().map_err(|_| ErrorCode::Malformed);
+        Ok(match message_type {
+            0x80 => Response::Ack,
+            0x81 => Response::Info(DeviceInfo {
+                protocol_version: get_u8()?,
+                num_relays: get_u8()?,
+                num_slots: get_u8()?,
+                relay_count: get_u8()?,
+            }),
+            0x82 => Response::Time(load_time(reader)?),
+            0x84 => Response::Settings(reader.get_rest().to_vec()),
+            0x86 => {
+                let count = get_u8()?;
+                let states = (0..count)
+                    .map(|_| Ok(get_u8()? != 0))
+                    .collect::<Result<_, _>>()?;
+                Response::Relays(states)
+            }
+            0xFF => Response::Error(ErrorCode::from_u8(get_u8()?).ok_or(ErrorCode::Malformed)?),
+            _ => return Err(ErrorCode::UnknownType),
+        })
+    }
+}
+
+impl<T: MessageBody> Message<T> {
+    /// the complete frame of the message
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = StorageWriter::new(MESSAGE_HEADER_SIZE + 16);
+        writer.put_u8(PROTOCOL_VERSION);
+        writer.put_u16(self.request_id);
+        writer.put_u8(self.body.message_type());
+        self.body.store(&mut writer);
+        encode_frame(&writer.into_bytes())
+    }
+
+    /// decodes a message received by a [`FrameDecoder`]
+    pub fn decode(message: &[u8]) -> Result<Self, ErrorCode> {
+        let mut reader = StorageReader::new(message, PROTOCOL_VERSION);
+        let version = reader.get_u8().map_err(|_| ErrorCode::Malformed)?;
+        let request_id = reader.get_u16().map_err(|_| ErrorCode::Malformed)?;
+        if version != PROTOCOL_VERSION {
+            return Err(ErrorCode::UnsupportedVersion);
+        }
+        let message_type = reader.get_u8().map_err(|_| ErrorCode::Malformed)?;
+        let body = T::load(message_type, &mut reader)?;
+        if !reader.get_rest().is_empty() {
+            return Err(ErrorCode::Malformed);
+        }
+        Ok(Message { request_id, body })
+    }
+}
+
+/// wraps a message into a frame
+fn encode_frame(message: &[u8]) -> Vec<u8> {
+    let mut writer = StorageWriter::new(message.len() + 5);
+    writer.put_u8(FRAME_START);
+    writer.put_u16(message.len() as u16);
+    writer.put_bytes(message);
+    let mut frame = writer.into_bytes();
+    let crc = crc16(&frame[1..]);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// collects the bytes of a frame, one at a time
+///
+/// Bytes outside of frames are skipped.
+#[derive(Default)]
+pub struct FrameDecoder {
+    /// the bytes received after [`FRAME_START`]
+    buffer: Vec<u8>,
+    receiving: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// whether a frame has been started, but not completed
+    pub fn is_receiving(&self) -> bool {
+        self.receiving
+    }
+
+    /// drops an incomplete frame
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.receiving = false;
+    }
+
+    /// takes the next byte; returns the message once a frame with a valid checksum is complete
+    pub fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if !self.receiving {
+            self.receiving = byte == FRAME_START;
+            return None;
+        }
+        self.buffer.push(byte);
+        let [len_low, len_high, ..] = self.buffer[..] else {
+            return None;
+        };
+        let len = u16::from_le_bytes([len_low, len_high]) as usize;
+        if len > MAX_MESSAGE_SIZE {
+            self.reset();
+            return None;
+        }
+        if self.buffer.len() < 2 + len + 2 {
+            return None;
+        }
+        let (content, crc) = self.buffer.split_at(2 + len);
+        let message = (crc16(content).to_le_bytes() == crc).then(|| content[2..].to_vec());
+        self.reset();
+        message
+    }
+}
+
+/// carries out a request
+fn handle_request<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessTiming + AccessRtc + AccessPersistentStorage + AccessOutputStates<NUM_RELAYS>),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    request: Request,
+) -> Response {
+    match request {
+        Request::Info => Response::Info(DeviceInfo {
+            protocol_version: PROTOCOL_VERSION,
+            num_relays: NUM_RELAYS as u8,
+            num_slots: NUM_SLOTS as u8,
+            relay_count: sys.relay_count() as u8,
+        }),
+        Request::GetTime => Response::Time(sys.get_rtc()),
+        Request::SetTime(time) => {
+            set_clock(sys, &time, settings_changed, event_log);
+            Response::Ack
+        }
+        Request::GetSettings => Response::Settings(storage::encode_settings(&settings.borrow())),
+        Request::PutSettings(blob) => {
+            let Ok(mut new_settings) = storage::decode_settings(&blob) else {
+                return Response::Error(ErrorCode::InvalidValue);
+            };
+            update_settings(sys, settings, settings_changed, event_log, |settings| {
+                // running immediate timers are not part of the blob
+                new_settings.immediate_out = settings.immediate_out.clone();
+                *settings = new_settings;
+            });
+            Response::Ack
+        }
+        Request::GetRelays => {
+            let states = status.relay_states.get();
+            Response::Relays(states[..sys.relay_count()].to_vec())
+        }
+    }
+}
+
+/// answers a message received by a [`FrameDecoder`]; returns the frame of the response
+///
+/// Messages that cannot be decoded are answered by [`Response::Error`],
+/// provided they contain a request id.
+pub(crate) fn respond<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessTiming + AccessRtc + AccessPersistentStorage + AccessOutputStates<NUM_RELAYS>),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    message: &[u8],
+) -> Option<Vec<u8>> {
+    let response = match Message::<Request>::decode(message) {
+        Ok(Message { request_id, body }) => Message {
+            request_id,
+            body: handle_request(sys, settings, settings_changed, status, event_log, body),
+        },
+        Err(code) => {
+            if message.len() < 3 {
+                return None;
+            }
+            Message {
+                request_id: u16::from_le_bytes([message[1], message[2]]),
+                body: Response::Error(code),
+            }
+        }
+    };
+    Some(response.encode())
+}
@@ -0,0 +1,125 @@
+//! relays switched by digital inputs, e.g., float switches or motion sensors
+//!
+//! The levels of the inputs are read through [`crate::system::AccessDigitalInputs`].
+//! A change of level only counts once it has been stable for the debounce time,
+//! so contact bounce and short glitches are ignored.
+//! The [`InputRule`]s then request relays to be on, like the timers do.
+//! Hence, the override modes, the wear limits and the interlocks apply to them as well.
+
+use alloc::vec::Vec;
+use greaheisl_async::{DurationMillis, InstantMillis};
+
+/// the largest number of digital inputs, one bit each in the input flags
+pub const MAX_INPUTS: usize = 8;
+
+/// what an [`InputRule`] does with its relay
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputAction {
+    /// the relay is on while the input is active
+    Hold,
+    /// the relay is on for the given time in milliseconds after the input
+    /// becomes active; becoming active again restarts the time
+    Pulse(DurationMillis),
+}
+
+/// switches a relay depending on a digital input
+#[derive(Clone, Debug)]
+pub struct InputRule {
+    /// index of the input, below [`MAX_INPUTS`]
+    pub input: usize,
+    /// index of the relay
+    pub relay: usize,
+    pub action: InputAction,
+}
+
+/// the outcome of [`InputState::apply`]
+pub(crate) struct InputDecision<const NUM_RELAYS: usize> {
+    /// the relays requested to be on by the rules
+    pub states: [bool; NUM_RELAYS],
+    /// the time until a pending level change is accepted
+    /// or a pulse ends, if any
+    pub wake_up: Option<DurationMillis>,
+}
+
+/// debounces the inputs and evaluates the [`InputRule`]s over time
+pub(crate) struct InputState<const NUM_RELAYS: usize> {
+    rules: Vec<InputRule>,
+    debounce: DurationMillis,
+    /// the debounced levels, one bit per input
+    levels: u8,
+    /// since when each input has deviated from its debounced level
+    pending_since: [Option<InstantMillis>; MAX_INPUTS],
+    /// when the pulse of each rule has started; `None` for rules not pulsing
+    pulse_start: Vec<Option<InstantMillis>>,
+}
+
+impl<const NUM_RELAYS: usize> InputState<NUM_RELAYS> {
+    /// Rules referring to inputs or relays that do not exist are ignored.
+    pub fn new(rules: Vec<InputRule>, debounce: DurationMillis) -> Self {
+        let rules: Vec<_> = rules
+            .into_iter()
+            .filter(|rule| rule.input < MAX_INPUTS && rule.relay < NUM_RELAYS)
+            .collect();
+        Self {
+            pulse_start: alloc::vec![None; rules.len()],
+            rules,
+            debounce: debounce.max(0),
+            levels: 0,
+            pending_since: [None; MAX_INPUTS],
+        }
+    }
+
+    /// updates the debounced levels with the raw `input_flags`
+    /// and returns the relays requested to be on
+    ///
+    /// All inputs start inactive, so an input active at startup
+    /// counts as becoming active once it has been debounced.
+    pub fn apply(&mut self, input_flags: u8, now: InstantMillis) -> InputDecision<NUM_RELAYS> {
+        let mut wake_up: Option<DurationMillis> = None;
+        let mut wake_up_at_most = |time_left: DurationMillis| {
+            wake_up = Some(wake_up.map_or(time_left, |w| w.min(time_left)));
+        };
+        let mut rising = 0u8;
+        for (k, pending_since) in self.pending_since.iter_mut().enumerate() {
+            let mask = 1u8 << k;
+            if (input_flags ^ self.levels) & mask == 0 {
+                *pending_since = None;
+                continue;
+            }
+            let since = *pending_since.get_or_insert(now);
+            let time_left = self.debounce - (now - since);
+            if time_left > 0 {
+                wake_up_at_most(time_left);
+                continue;
+            }
+            self.levels ^= mask;
+            *pending_since = None;
+            if self.levels & mask != 0 {
+                rising |= mask;
+            }
+        }
+        let mut states = [false; NUM_RELAYS];
+        for (rule, pulse_start) in self.rules.iter().zip(self.pulse_start.iter_mut()) {
+            let mask = 1u8 << rule.input;
+            match rule.action {
+                InputAction::Hold => states[rule.relay] |= self.levels & mask != 0,
+                InputAction::Pulse(duration) => {
+                    if rising & mask != 0 {
+                        *pulse_start = Some(now);
+                    }
+                    let Some(start) = *pulse_start else {
+                        continue;
+                    };
+                    let time_left = duration - (now - start);
+                    if time_left > 0 {
+                        states[rule.relay] = true;
+                        wake_up_at_most(time_left);
+                    } else {
+                        *pulse_start = None;
+                    }
+                }
+            }
+        }
+        InputDecision { states, wake_up }
+    }
+}
@@ -1,11 +1,20 @@
 //! definition of the foreign function interface of this library
 
 //use static_assertions::const_assert_eq;
+use crate::event_log::{Cause, Event, EventLog, LogEntry};
+use crate::inputs::{InputAction, InputRule};
+use crate::interlocks::Interlocks;
+use crate::statistics::{RelayStats, RuntimeStats};
 use crate::system::buttons;
 use crate::system::{
-    AccessLedMatrix, AccessOutputStates, AccessRtc, RtcTime, SignalFlags, NUM_RELAYS,
+    AccessAnalogInput, AccessDigitalInputs, AccessLedMatrix, AccessOutputStates,
+    AccessPersistentStorage, AccessRtc, AccessSerial, RtcTime, SignalFlags,
 };
+use crate::wear::WearLimits;
+use crate::{OutputConfig, DEFAULT_NUM_SLOTS};
 use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
 #[cfg(any(not(feature = "std"),doc))]
 use core::ffi::c_void;
 use core::mem::MaybeUninit;
@@ -31,8 +40,50 @@ pub struct GreaheislCallbacks {
     pub set_led_matrix: Option<unsafe extern "C" fn(&[u32; 3])>,
     /// returns the states of the buttons as binary flags
     pub get_button_flags: Option<unsafe extern "C" fn() -> u8>,
-    /// sets the relay states (on or off)
-    pub set_relay_states: Option<unsafe extern "C" fn(&[bool; NUM_RELAYS])>,
+    /// sets the relay states (on or off), given by pointer and number of relays
+    ///
+    /// The number of relays is the `relay_count` passed to [`greaheisl_init`].
+    pub set_relay_states: Option<unsafe extern "C" fn(*const bool, usize)>,
+    /// copies the stored settings into the buffer given by pointer and capacity;
+    /// returns the number of bytes copied
+    ///
+    /// Can be `NULL` if there is no non-volatile memory. Then the
+    /// settings are lost when the device is switched off.
+    pub load_settings: Option<unsafe extern "C" fn(*mut u8, usize) -> usize>,
+    /// stores the settings given by pointer and length in non-volatile memory
+    ///
+    /// Can be `NULL` if there is no non-volatile memory.
+    pub store_settings: Option<unsafe extern "C" fn(*const u8, usize)>,
+    /// returns the levels of the digital inputs as binary flags, bit `k` set if input `k` is active
+    ///
+    /// Can be `NULL` if there are no digital inputs.
+    /// Pass the signal flag for inputs to [`greaheisl_step`] whenever a level changes.
+    pub get_input_flags: Option<unsafe extern "C" fn() -> u8>,
+    /// provides the reading of the analog sensor in tenths of its unit, e.g., 0.1 °C;
+    /// returns `false` if the sensor cannot be read
+    ///
+    /// Can be `NULL` if there is no analog sensor.
+    pub get_analog_value: Option<unsafe extern "C" fn(*mut i16) -> bool>,
+    /// copies the stored statistics into the buffer given by pointer and capacity;
+    /// returns the number of bytes copied
+    ///
+    /// Can be `NULL`. Then the statistics start from zero after every restart.
+    pub load_statistics: Option<unsafe extern "C" fn(*mut u8, usize) -> usize>,
+    /// stores the statistics given by pointer and length in non-volatile memory;
+    /// called about once an hour
+    ///
+    /// Can be `NULL`.
+    pub store_statistics: Option<unsafe extern "C" fn(*const u8, usize)>,
+    /// provides the next byte received over serial; returns `false` if none is waiting
+    ///
+    /// Can be `NULL` if there is no serial connection.
+    /// Pass the signal flag for serial to [`greaheisl_step`] whenever bytes have been received.
+    /// See [`crate::commands`] for the commands understood.
+    pub read_serial: Option<unsafe extern "C" fn(*mut u8) -> bool>,
+    /// sends the bytes given by pointer and length over serial
+    ///
+    /// Can be `NULL` if there is no serial connection.
+    pub write_serial: Option<unsafe extern "C" fn(*const u8, usize)>,
 }
 
 #[derive(Delegate)]
@@ -41,6 +92,7 @@ pub struct GreaheislCallbacks {
 struct CSystem<S> {
     callbacks: &'static GreaheislCallbacks,
     scheduler: S,
+    relay_count: usize,
 }
 
 impl<S> AccessRtc for CSystem<S> {
@@ -73,11 +125,84 @@ impl<S: AccessExecutorSignals<SignalFlags>> AccessButtonSignal for CSystem<S> {
     fn is_button_signal(&self) -> bool {
         self.get_executor_signals().contains(SignalFlags::Button)
     }
+    fn set_button_wake_request(&self, waker: &core::task::Waker) {
+        self.set_signal_wake_request(SignalFlags::Button, waker);
+    }
+}
+
+impl<S> AccessOutputStates<GREAHEISL_MAX_RELAYS> for CSystem<S> {
+    fn relay_count(&self) -> usize {
+        self.relay_count
+    }
+
+    fn set_relay_states(&self, relay_states: &[bool; GREAHEISL_MAX_RELAYS]) {
+        let set_relay_states = self.callbacks.set_relay_states.unwrap();
+        unsafe { set_relay_states(relay_states.as_ptr(), self.relay_count) }
+    }
+}
+
+impl<S> AccessPersistentStorage for CSystem<S> {
+    fn load_settings(&self, buffer: &mut [u8]) -> usize {
+        let Some(load_settings) = self.callbacks.load_settings else {
+            return 0;
+        };
+        let len = unsafe { load_settings(buffer.as_mut_ptr(), buffer.len()) };
+        len.min(buffer.len())
+    }
+
+    fn store_settings(&self, data: &[u8]) {
+        if let Some(store_settings) = self.callbacks.store_settings {
+            unsafe { store_settings(data.as_ptr(), data.len()) }
+        }
+    }
+
+    fn load_statistics(&self, buffer: &mut [u8]) -> usize {
+        let Some(load_statistics) = self.callbacks.load_statistics else {
+            return 0;
+        };
+        let len = unsafe { load_statistics(buffer.as_mut_ptr(), buffer.len()) };
+        len.min(buffer.len())
+    }
+
+    fn store_statistics(&self, data: &[u8]) {
+        if let Some(store_statistics) = self.callbacks.store_statistics {
+            unsafe { store_statistics(data.as_ptr(), data.len()) }
+        }
+    }
+}
+
+impl<S> AccessDigitalInputs for CSystem<S> {
+    fn get_input_flags(&self) -> u8 {
+        let Some(get_input_flags) = self.callbacks.get_input_flags else {
+            return 0;
+        };
+        unsafe { get_input_flags() }
+    }
 }
 
-impl<S> AccessOutputStates for CSystem<S> {
-    fn set_relay_states(&self, relay_states: &[bool; crate::system::NUM_RELAYS]) {
-        unsafe { (self.callbacks.set_relay_states.unwrap())(relay_states) }
+impl<S> AccessAnalogInput for CSystem<S> {
+    fn has_analog_input(&self) -> bool {
+        self.callbacks.get_analog_value.is_some()
+    }
+
+    fn get_analog_value(&self) -> Option<i16> {
+        let get_analog_value = self.callbacks.get_analog_value?;
+        let mut value = 0;
+        unsafe { get_analog_value(&mut value) }.then_some(value)
+    }
+}
+
+impl<S> AccessSerial for CSystem<S> {
+    fn read_serial(&self) -> Option<u8> {
+        let read_serial = self.callbacks.read_serial?;
+        let mut byte = 0;
+        unsafe { read_serial(&mut byte) }.then_some(byte)
+    }
+
+    fn write_serial(&self, data: &[u8]) {
+        if let Some(write_serial) = self.callbacks.write_serial {
+            unsafe { write_serial(data.as_ptr(), data.len()) }
+        }
     }
 }
 
@@ -116,9 +241,178 @@ pub unsafe extern "C" fn set_allocator_functions(
     crate::delegating_alloc::init_delegating_allocatator(aligned_alloc, free)
 }
 
-/// the executor provided to the outer framework
-pub type GreaheislExecutor = MiniExecutor<SignalFlags>;
+/// the executor provided to the outer framework,
+/// together with the event log and the statistics of its task
+pub struct GreaheislExecutor {
+    executor: MiniExecutor<SignalFlags>,
+    event_log: Rc<EventLog>,
+    statistics: Rc<RefCell<RuntimeStats<GREAHEISL_MAX_RELAYS>>>,
+}
+
+/// the maximum number of relays that can be passed to [`greaheisl_init`]
+///
+/// The settings always have room for this number of relays,
+/// so boards with different numbers of relays use the same library.
+/// It is chosen when the library is built, through the environment variable
+/// `GREAHEISL_MAX_RELAYS` with a value from 1 to 9. The default is 8.
+pub const GREAHEISL_MAX_RELAYS: usize = build_config(option_env!("GREAHEISL_MAX_RELAYS"), 8);
+
+/// the number of scheduled entries per relay
+///
+/// It is chosen when the library is built, through the environment variable
+/// `GREAHEISL_NUM_SLOTS` with a value from 1 to 9. The default is [`DEFAULT_NUM_SLOTS`].
+pub const GREAHEISL_NUM_SLOTS: usize =
+    build_config(option_env!("GREAHEISL_NUM_SLOTS"), DEFAULT_NUM_SLOTS);
+
+/// the value of an environment variable read at build time, or `default` if it is not set
+///
+/// The menus show relays and slots with a single digit,
+/// so the build fails for anything but a number from 1 to 9.
+const fn build_config(value: Option<&str>, default: usize) -> usize {
+    let Some(value) = value else {
+        return default;
+    };
+    match value.as_bytes() {
+        [digit @ b'1'..=b'9'] => (*digit - b'0') as usize,
+        _ => panic!("the number of relays and of slots must be a number from 1 to 9"),
+    }
+}
+
+/// restrictions on which relays may be on at the same time, see [`Interlocks`]
+#[derive(Clone)]
+#[repr(C)]
+pub struct GreaheislInterlocks {
+    /// pointer to an array of bit masks, bit `k` standing for relay `k`;
+    /// at most one relay of each group may be on at any time
+    pub exclusive_groups: *const u32,
+    /// the number of elements of `exclusive_groups`
+    pub num_exclusive_groups: usize,
+    /// the maximum number of relays that may be on at the same time; 0 means no limit
+    pub max_active: usize,
+    /// the minimum time in milliseconds between a relay switching off
+    /// and another relay of the same group switching on
+    pub dead_time: DurationMillis,
+}
+
+impl From<&GreaheislInterlocks> for Interlocks {
+    fn from(c_interlocks: &GreaheislInterlocks) -> Self {
+        let exclusive_groups = if c_interlocks.exclusive_groups.is_null() {
+            &[]
+        } else {
+            unsafe {
+                core::slice::from_raw_parts(
+                    c_interlocks.exclusive_groups,
+                    c_interlocks.num_exclusive_groups,
+                )
+            }
+        };
+        Interlocks {
+            exclusive_groups: exclusive_groups.to_vec(),
+            max_active: (c_interlocks.max_active != 0).then_some(c_interlocks.max_active),
+            dead_time: c_interlocks.dead_time.max(0),
+        }
+    }
+}
+
+/// the wear limits of a relay, see [`WearLimits`]
+#[derive(Clone)]
+#[repr(C)]
+pub struct GreaheislWearLimits {
+    /// the minimum time in milliseconds the relay stays on once switched on
+    pub min_on: DurationMillis,
+    /// the minimum time in milliseconds the relay stays off once switched off
+    pub min_off: DurationMillis,
+    /// the maximum number of times the relay may be switched on within any hour;
+    /// 0 means no limit
+    pub max_switches_per_hour: u16,
+}
+
+impl From<&GreaheislWearLimits> for WearLimits {
+    fn from(c_limits: &GreaheislWearLimits) -> Self {
+        WearLimits {
+            min_on: c_limits.min_on.max(0),
+            min_off: c_limits.min_off.max(0),
+            max_switches_per_hour: (c_limits.max_switches_per_hour != 0)
+                .then_some(c_limits.max_switches_per_hour),
+        }
+    }
+}
+
+/// a relay switched by a digital input, see [`InputRule`]
+#[derive(Clone)]
+#[repr(C)]
+pub struct GreaheislInputRule {
+    /// index of the input, i.e., of its bit in the input flags
+    pub input: u8,
+    /// index of the relay
+    pub relay: u8,
+    /// 0 to hold the relay on while the input is active; otherwise, the time
+    /// in milliseconds the relay stays on after the input becomes active
+    pub duration: DurationMillis,
+}
+
+impl From<&GreaheislInputRule> for InputRule {
+    fn from(c_rule: &GreaheislInputRule) -> Self {
+        InputRule {
+            input: c_rule.input as usize,
+            relay: c_rule.relay as usize,
+            action: if c_rule.duration > 0 {
+                InputAction::Pulse(c_rule.duration)
+            } else {
+                InputAction::Hold
+            },
+        }
+    }
+}
+
+/// restrictions on switching the relays, see [`OutputConfig`]
+#[derive(Clone)]
+#[repr(C)]
+pub struct GreaheislOutputConfig {
+    /// the interlock table, or `NULL` if the relays may be on in any combination
+    pub interlocks: *const GreaheislInterlocks,
+    /// pointer to an array with the wear limits of each relay, with `relay_count`
+    /// elements as passed to [`greaheisl_init`], or `NULL` if the relays are not limited
+    pub wear_limits: *const GreaheislWearLimits,
+    /// the minimum time in milliseconds between two relays switching on; 0 means no delay
+    pub stagger_interval: DurationMillis,
+    /// the seed of the random shifts in vacation mode;
+    /// 0 derives it from the real time clock and the instant at startup
+    pub jitter_seed: u64,
+    /// pointer to an array with the rules for the digital inputs, or `NULL` if there are none
+    pub input_rules: *const GreaheislInputRule,
+    /// the number of elements of `input_rules`
+    pub num_input_rules: usize,
+    /// the time in milliseconds a digital input must keep its new level
+    /// before the change counts; 0 means no debouncing
+    pub input_debounce: DurationMillis,
+}
 
+impl GreaheislOutputConfig {
+    fn to_output_config(&self, relay_count: usize) -> OutputConfig {
+        let interlocks = unsafe { self.interlocks.as_ref() }
+            .map(Interlocks::from)
+            .unwrap_or_default();
+        let wear_limits = if self.wear_limits.is_null() {
+            &[]
+        } else {
+            unsafe { core::slice::from_raw_parts(self.wear_limits, relay_count) }
+        };
+        let input_rules = if self.input_rules.is_null() {
+            &[]
+        } else {
+            unsafe { core::slice::from_raw_parts(self.input_rules, self.num_input_rules) }
+        };
+        OutputConfig {
+            interlocks,
+            wear_limits: wear_limits.iter().map(WearLimits::from).collect(),
+            stagger_interval: self.stagger_interval.max(0),
+            jitter_seed: (self.jitter_seed != 0).then_some(self.jitter_seed),
+            input_rules: input_rules.iter().map(InputRule::from).collect(),
+            input_debounce: self.input_debounce.max(0),
+        }
+    }
+}
 
 /// initialization of the executor
 ///
@@ -126,6 +420,10 @@ pub type GreaheislExecutor = MiniExecutor<SignalFlags>;
 ///
 /// arguments:
 /// * `callbacks`: a structure of with valid function pointers to the callback functions
+/// * `relay_count`: the number of relays connected, from 1 to [`GREAHEISL_MAX_RELAYS`];
+///   other values are clamped to this range
+/// * `config`: restrictions on switching the relays, or `NULL` if they may be switched freely;
+///   the tables are copied, so they need not outlive this call
 /// * `instant`: the current time in milliseconds (with an arbitrary offset)
 ///
 /// Returns a pointer to the executor. Note that the executor
@@ -133,16 +431,36 @@ pub type GreaheislExecutor = MiniExecutor<SignalFlags>;
 #[no_mangle]
 pub extern "C" fn greaheisl_init(
     callbacks: &'static GreaheislCallbacks,
+    relay_count: usize,
+    config: Option<&GreaheislOutputConfig>,
     instant: u32,
 ) -> *mut GreaheislExecutor {
     let gh = MiniExecutor::new(InstantMillis::from_absolute(instant));
+    let relay_count = relay_count.clamp(1, GREAHEISL_MAX_RELAYS);
     let sys = CSystem {
         callbacks,
         scheduler: gh.scheduler().clone(),
+        relay_count,
+    };
+    let config = config
+        .map(|config| config.to_output_config(relay_count))
+        .unwrap_or_default();
+    let event_log = Rc::new(EventLog::new());
+    let statistics = Rc::new(RefCell::new(RuntimeStats::default()));
+    let task = {
+        let event_log = event_log.clone();
+        let statistics = statistics.clone();
+        async move {
+            run::<GREAHEISL_MAX_RELAYS, GREAHEISL_NUM_SLOTS>(sys, config, &event_log, &statistics)
+                .await;
+        }
     };
-    let task = run(sys);
-    let gh = gh.build(task);
-    Box::into_raw(Box::new(gh))
+    let executor = gh.build(task);
+    Box::into_raw(Box::new(GreaheislExecutor {
+        executor,
+        event_log,
+        statistics,
+    }))
 }
 
 /// lets the executor perform one step
@@ -163,13 +481,146 @@ pub extern "C" fn greaheisl_step(
     signals: u8,
 ) -> DurationMillis {
     let signals = SignalFlags::from(signals);
-    if let Some(delay_request) = handle.step(InstantMillis::from_absolute(instant), signals) {
+    let instant = InstantMillis::from_absolute(instant);
+    if let Some(delay_request) = handle.executor.step(instant, signals) {
         delay_request
     } else {
         DurationMillis::MAX
     }
 }
 
+/// what an entry of the event log records, see [`Event`]
+#[derive(Clone, Copy)]
+#[repr(u8)]
+pub enum GreaheislEventKind {
+    RelayOn,
+    RelayOff,
+    ClockSet,
+    SettingsCommitted,
+}
+
+/// why a relay has been switched, see [`Cause`]
+#[derive(Clone, Copy)]
+#[repr(u8)]
+pub enum GreaheislCause {
+    /// the entry does not refer to a relay
+    None,
+    Immediate,
+    Scheduled,
+    Dated,
+    Cycle,
+    Thermostat,
+    Input,
+    Override,
+    Interlock,
+}
+
+/// an entry of the event log, see [`LogEntry`]
+#[derive(Clone)]
+#[repr(C)]
+pub struct GreaheislLogEntry {
+    /// counts all entries ever recorded; a gap means that entries have been overwritten
+    pub sequence: u32,
+    /// the time of the real time clock when the entry was recorded, in UTC
+    pub time: RtcTime,
+    pub kind: GreaheislEventKind,
+    /// index of the relay switched, if any
+    pub relay: u8,
+    pub cause: GreaheislCause,
+    /// index of the scheduled entry, if the cause is [`GreaheislCause::Scheduled`]
+    pub slot: u8,
+}
+
+impl From<&LogEntry> for GreaheislLogEntry {
+    fn from(entry: &LogEntry) -> Self {
+        let (kind, relay, cause) = match entry.event {
+            Event::RelayOn { relay, cause } => (GreaheislEventKind::RelayOn, relay, Some(cause)),
+            Event::RelayOff { relay, cause } => (GreaheislEventKind::RelayOff, relay, Some(cause)),
+            Event::ClockSet => (GreaheislEventKind::ClockSet, 0, None),
+            Event::SettingsCommitted => (GreaheislEventKind::SettingsCommitted, 0, None),
+        };
+        let (cause, slot) = match cause {
+            None => (GreaheislCause::None, 0),
+            Some(Cause::Immediate) => (GreaheislCause::Immediate, 0),
+            Some(Cause::Scheduled { slot }) => (GreaheislCause::Scheduled, slot),
+            Some(Cause::Dated) => (GreaheislCause::Dated, 0),
+            Some(Cause::Cycle) => (GreaheislCause::Cycle, 0),
+            Some(Cause::Thermostat) => (GreaheislCause::Thermostat, 0),
+            Some(Cause::Input) => (GreaheislCause::Input, 0),
+            Some(Cause::Override) => (GreaheislCause::Override, 0),
+            Some(Cause::Interlock) => (GreaheislCause::Interlock, 0),
+        };
+        GreaheislLogEntry {
+            sequence: entry.sequence,
+            time: entry.time,
+            kind,
+            relay,
+            cause,
+            slot,
+        }
+    }
+}
+
+/// reads the event log, one entry at a time
+///
+/// arguments:
+/// * `handle`: pointer to the executor, as obtained from [`greaheisl_init`]
+/// * `sequence`: the sequence number to continue from, initially 0;
+///   advanced past the entry returned
+/// * `entry`: receives the oldest entry kept whose sequence number is at least `*sequence`
+///
+/// Returns `false` if there is no such entry (yet), or if `entry` is null.
+/// Call this between calls of [`greaheisl_step`], e.g., to print new entries over serial.
+///
+/// # Safety
+///
+/// `entry` must be null or point to memory valid for writing a [`GreaheislLogEntry`].
+#[no_mangle]
+pub unsafe extern "C" fn greaheisl_event_log_read(
+    handle: &GreaheislExecutor,
+    sequence: &mut u32,
+    entry: *mut GreaheislLogEntry,
+) -> bool {
+    if entry.is_null() {
+        return false;
+    }
+    let Some(log_entry) = handle.event_log.first_since(*sequence) else {
+        return false;
+    };
+    *sequence = log_entry.sequence + 1;
+    unsafe { entry.write(GreaheislLogEntry::from(&log_entry)) };
+    true
+}
+
+/// provides the on-times and the switch count of a relay
+///
+/// arguments:
+/// * `handle`: pointer to the executor, as obtained from [`greaheisl_init`]
+/// * `relay`: index of the relay
+/// * `stats`: receives the counters of the relay
+///
+/// Returns `false` if `relay` is not below [`GREAHEISL_MAX_RELAYS`], or if `stats` is null.
+/// Call this between calls of [`greaheisl_step`].
+///
+/// # Safety
+///
+/// `stats` must be null or point to memory valid for writing a [`RelayStats`].
+#[no_mangle]
+pub unsafe extern "C" fn greaheisl_relay_stats(
+    handle: &GreaheislExecutor,
+    relay: usize,
+    stats: *mut RelayStats,
+) -> bool {
+    if stats.is_null() {
+        return false;
+    }
+    let Some(relay_stats) = handle.statistics.borrow().relays.get(relay).copied() else {
+        return false;
+    };
+    unsafe { stats.write(relay_stats) };
+    true
+}
+
 /*
 #[no_mangle]
 pub extern "C" fn show_clock(imat: *mut u32, hours: u8, minutes: u8) {
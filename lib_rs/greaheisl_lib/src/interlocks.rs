@@ -0,0 +1,128 @@
+//! restrictions on which relays may be on at the same time
+//!
+//! The restrictions are given by the hardware connected to the relays,
+//! e.g., a motor driven in opposite directions by two relays,
+//! so they are not part of the [`crate::Settings`] edited by the user.
+//! They are enforced right before the relay states are passed to
+//! [`crate::system::AccessOutputStates::set_relay_states`].
+//!
+//! ## priority rule
+//!
+//! If requested relay states conflict, relays that are already on
+//! stay on, and among the relays about to be switched on,
+//! the one with the lower index wins.
+
+use alloc::vec::Vec;
+use greaheisl_async::{DurationMillis, InstantMillis};
+
+/// the interlock table
+#[derive(Clone, Debug, Default)]
+pub struct Interlocks {
+    /// groups of mutually exclusive relays
+    ///
+    /// Each group is a bit mask, with bit `k` standing for relay `k`.
+    /// At most one relay of a group may be on at any time.
+    pub exclusive_groups: Vec<u32>,
+    /// the maximum number of relays that may be on at the same time
+    pub max_active: Option<usize>,
+    /// the minimum time in milliseconds between a relay switching off
+    /// and another relay of the same exclusive group switching on
+    pub dead_time: DurationMillis,
+}
+
+impl Interlocks {
+    /// checks whether relays `a` and `b` share an exclusive group
+    pub fn are_exclusive(&self, a: usize, b: usize) -> bool {
+        a != b
+            && self
+                .exclusive_groups
+                .iter()
+                .any(|group| group & (1 << a) != 0 && group & (1 << b) != 0)
+    }
+}
+
+/// enforces [`Interlocks`] over time
+pub(crate) struct InterlockState<const NUM_RELAYS: usize> {
+    interlocks: Interlocks,
+    /// the states passed to the previous call to [`Self::record`]
+    states: [bool; NUM_RELAYS],
+    /// when each relay was switched off the last time
+    switched_off: [Option<InstantMillis>; NUM_RELAYS],
+}
+
+impl<const NUM_RELAYS: usize> InterlockState<NUM_RELAYS> {
+    pub fn new(interlocks: Interlocks) -> Self {
+        Self {
+            interlocks,
+            states: [false; NUM_RELAYS],
+            switched_off: [None; NUM_RELAYS],
+        }
+    }
+
+    /// turns the requested relay states into permitted ones
+    ///
+    /// Returns the permitted states, and the time until a relay
+    /// held back by the dead time may be switched on, if any.
+    /// The relays count as on as recorded by [`Self::record`].
+    pub fn enforce(
+        &self,
+        requested: &[bool; NUM_RELAYS],
+        now: InstantMillis,
+    ) -> ([bool; NUM_RELAYS], Option<DurationMillis>) {
+        let mut permitted = [false; NUM_RELAYS];
+        let mut num_active = 0;
+        let max_active = self.interlocks.max_active.unwrap_or(NUM_RELAYS);
+        // relays already on keep their priority
+        for k in 0..NUM_RELAYS {
+            if requested[k] && self.states[k] && num_active < max_active {
+                permitted[k] = true;
+                num_active += 1;
+            }
+        }
+        // relays switched off right now count for the dead time as well
+        let switched_off: [Option<InstantMillis>; NUM_RELAYS] = core::array::from_fn(|j| {
+            if self.states[j] && !permitted[j] {
+                Some(now)
+            } else {
+                self.switched_off[j]
+            }
+        });
+        let mut wake_up: Option<DurationMillis> = None;
+        for k in 0..NUM_RELAYS {
+            if !requested[k] || permitted[k] || num_active >= max_active {
+                continue;
+            }
+            let partners = || (0..NUM_RELAYS).filter(|&j| self.interlocks.are_exclusive(k, j));
+            if partners().any(|j| permitted[j]) {
+                continue;
+            }
+            let waiting = partners()
+                .filter_map(|j| switched_off[j])
+                .map(|off| self.interlocks.dead_time - (now - off))
+                .max()
+                .filter(|&time_left| time_left > 0);
+            if let Some(time_left) = waiting {
+                wake_up = Some(wake_up.map_or(time_left, |w| w.min(time_left)));
+                continue;
+            }
+            permitted[k] = true;
+            num_active += 1;
+        }
+        (permitted, wake_up)
+    }
+
+    /// records the states actually passed to the relays at `now`
+    ///
+    /// These may differ from the permitted ones, e.g. if a relay
+    /// is held back by [`crate::stagger`]. Such a relay does not count as on.
+    pub fn record(&mut self, states: &[bool; NUM_RELAYS], now: InstantMillis) {
+        for ((was_on, is_on), switched_off) in
+            self.states.iter().zip(states).zip(&mut self.switched_off)
+        {
+            if *was_on && !is_on {
+                *switched_off = Some(now);
+            }
+        }
+        self.states = *states;
+    }
+}
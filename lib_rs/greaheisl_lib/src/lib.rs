@@ -9,7 +9,7 @@
 //! ## Features
 //!
 //! - `std`: (default) uses standard library.
-//!    *Note:* set `default-features = false` for no-std targets.
+//!   *Note:* set `default-features = false` for no-std targets.
 
 // no_std only when freature "std" is missing
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -19,16 +19,42 @@ extern crate alloc;
 // #[macro_use]
 // pub mod greaheisl_async;
 
+pub mod calendar;
+
+pub mod commands;
+
+pub mod event_log;
+
+pub mod host_protocol;
+
+pub mod inputs;
+
 pub mod interface_c;
 
+pub mod interlocks;
+
+pub mod random;
+
+pub mod storage;
+
+pub mod solar;
+
+mod stagger;
+
+pub mod statistics;
+
 pub mod system;
 
+pub mod timezone;
+
 // only needed when we build for embedded device
 #[cfg(not(feature = "std"))]
 mod delegating_alloc;
 
 pub mod ui;
 
+pub mod wear;
+
 // panic handling is only needed when we build for embedded device
 #[cfg(not(feature = "std"))]
 mod panic_handling;
@@ -38,27 +64,176 @@ mod panic_handling;
 #[cfg(test)]
 mod tests;
 
-use crate::system::{AccessOutputStates, AccessRtc, NUM_RELAYS};
-use core::cell::RefCell;
-use greaheisl_async::{join2, AccessTiming, Timer};
+use crate::commands::run_commands;
+use crate::event_log::{Cause, Event, EventLog};
+use crate::inputs::{InputRule, InputState};
+use crate::interlocks::{InterlockState, Interlocks};
+use crate::random::Rng;
+use crate::solar::{Location, SunTable, SunTimes};
+use crate::stagger::StaggerState;
+use crate::statistics::{RuntimeStats, STATISTICS_STORE_INTERVAL};
+use crate::system::{
+    AccessAnalogInput, AccessDigitalInputs, AccessOutputStates, AccessPersistentStorage, AccessRtc,
+    RtcTime, SignalFlags,
+};
+use crate::timezone::TimeZone;
+use crate::wear::{WearLimits, WearState};
+use alloc::vec::Vec;
+use bitmask_enum::bitmask;
+use core::cell::{Cell, RefCell};
+use enum_iterator::Sequence;
+use greaheisl_async::{join2, select2, with_timeout, AccessExecutorSignals, AccessTiming, Notify};
 use greaheisl_async::{DurationMillis, InstantMillis};
 use system::System;
 use ui::run_ui;
-// use serde::{Serialize, Deserialize};
 
-const MAX_SCHEDULED_ENTRIES: usize = 3;
+pub use crate::system::DEFAULT_NUM_RELAYS;
+
+/// number of scheduled entries per relay if not configured otherwise
+pub const DEFAULT_NUM_SLOTS: usize = 3;
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// the bound of the random shifts in [`OverrideMode::Vacation`] if not configured otherwise
+pub const DEFAULT_VACATION_JITTER: DurationMillis = 30 * 60 * 1000;
 
 /// main structure containing the current settings
-#[derive(Clone, Default)] // later also ,Serialize,Deserialize)]
-pub struct Settings {
+///
+/// `NUM_RELAYS` is the number of relays, and `NUM_SLOTS`
+/// the number of scheduled entries per relay.
+/// The menus show both numbers with a single digit,
+/// so neither may exceed 9.
+///
+/// See module [`storage`] for how the settings are kept in non-volatile memory.
+#[derive(Clone)]
+pub struct Settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize> {
     /// timers starting immediately
     pub immediate_out: [Option<ImmediateOutEntry>; NUM_RELAYS],
     /// timers starting daily on scheduled time
-    pub scheduled_out: [[ScheduledOutEntry; MAX_SCHEDULED_ENTRIES]; NUM_RELAYS],
+    pub scheduled_out: [[ScheduledOutEntry; NUM_SLOTS]; NUM_RELAYS],
+    /// timers starting once on a given date
+    pub dated_out: [Option<DatedOutEntry>; NUM_RELAYS],
+    /// timers switching on and off repeatedly
+    pub cycle_out: [CycleOutEntry; NUM_RELAYS],
+    /// relays switched by the reading of the analog sensor
+    pub thermostat: [ThermostatEntry; NUM_RELAYS],
+    /// manual overrides of the timers
+    pub override_mode: [OverrideMode; NUM_RELAYS],
+    /// the largest shift of the scheduled timers of a relay in [`OverrideMode::Vacation`]
+    pub vacation_jitter: [DurationMillis; NUM_RELAYS],
+    /// where the device is, for timers starting at sunrise or sunset
+    pub location: Location,
+    /// the local time the timers and the clock display refer to
+    pub time_zone: TimeZone,
+}
+
+// not derived, as `Default` is only implemented for arrays of up to 32 elements
+impl<const NUM_RELAYS: usize, const NUM_SLOTS: usize> Default for Settings<NUM_RELAYS, NUM_SLOTS> {
+    fn default() -> Self {
+        Self {
+            immediate_out: core::array::from_fn(|_| None),
+            scheduled_out: core::array::from_fn(|_| core::array::from_fn(|_| Default::default())),
+            dated_out: core::array::from_fn(|_| None),
+            cycle_out: core::array::from_fn(|_| Default::default()),
+            thermostat: core::array::from_fn(|_| Default::default()),
+            override_mode: [OverrideMode::Auto; NUM_RELAYS],
+            vacation_jitter: [DEFAULT_VACATION_JITTER; NUM_RELAYS],
+            location: Location::default(),
+            time_zone: TimeZone::default(),
+        }
+    }
+}
+
+impl<const NUM_RELAYS: usize, const NUM_SLOTS: usize> Settings<NUM_RELAYS, NUM_SLOTS> {
+    /// takes over the entries which differ between `original` and `edited`
+    ///
+    /// The other entries are left as they are, so changes made by others
+    /// while a copy of the settings was being edited are not undone.
+    pub(crate) fn merge_changes(&mut self, original: &Self, edited: &Self) {
+        fn merge<T: Clone + PartialEq>(current: &mut [T], original: &[T], edited: &[T]) {
+            for ((current, original), edited) in current.iter_mut().zip(original).zip(edited) {
+                if edited != original {
+                    current.clone_from(edited);
+                }
+            }
+        }
+        // destructured, so that new fields cannot be forgotten
+        let Self {
+            immediate_out,
+            scheduled_out,
+            dated_out,
+            cycle_out,
+            thermostat,
+            override_mode,
+            vacation_jitter,
+            location,
+            time_zone,
+        } = edited;
+        merge(
+            &mut self.immediate_out,
+            &original.immediate_out,
+            immediate_out,
+        );
+        for (relay, entries) in scheduled_out.iter().enumerate() {
+            merge(
+                &mut self.scheduled_out[relay],
+                &original.scheduled_out[relay],
+                entries,
+            );
+        }
+        merge(&mut self.dated_out, &original.dated_out, dated_out);
+        merge(&mut self.cycle_out, &original.cycle_out, cycle_out);
+        merge(&mut self.thermostat, &original.thermostat, thermostat);
+        merge(
+            &mut self.override_mode,
+            &original.override_mode,
+            override_mode,
+        );
+        merge(
+            &mut self.vacation_jitter,
+            &original.vacation_jitter,
+            vacation_jitter,
+        );
+        if *location != original.location {
+            self.location = *location;
+        }
+        if *time_zone != original.time_zone {
+            self.time_zone = *time_zone;
+        }
+    }
+}
+
+/// manual mode of a relay, taking precedence over all timers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Sequence)]
+pub enum OverrideMode {
+    /// the relay is switched by the timers
+    #[default]
+    Auto,
+    /// the relay stays on
+    ForcedOn,
+    /// the relay stays off, whatever the timers say
+    ForcedOff,
+    /// the relay is switched by the timers, but the windows of
+    /// the scheduled timers are shifted at random, see [`WindowShift`]
+    ///
+    /// This is a variant of [`Self::Auto`] on purpose: the forced modes ignore
+    /// the timers, and with them the shifts, so they exclude it anyway.
+    Vacation,
+}
+
+impl OverrideMode {
+    /// the relay state given the state requested by the timers
+    pub fn apply(self, timer_state: bool) -> bool {
+        match self {
+            OverrideMode::Auto | OverrideMode::Vacation => timer_state,
+            OverrideMode::ForcedOn => true,
+            OverrideMode::ForcedOff => false,
+        }
+    }
 }
 
 /// setting for timers starting immediately
-#[derive(Debug, Clone)] //later also ,Serialize,Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ImmediateOutEntry {
     /// a time stamp when the timer was set
     pub start: InstantMillis,
@@ -66,8 +241,20 @@ pub struct ImmediateOutEntry {
     pub duration: DurationMillis,
 }
 
+/// what the start time of a [`ScheduledOutEntry`] refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Sequence)]
+pub enum StartMode {
+    /// the timer starts at a fixed time of the day
+    #[default]
+    Clock,
+    /// the timer starts at an offset from sunrise
+    Sunrise,
+    /// the timer starts at an offset from sunset
+    Sunset,
+}
+
 /// setting for scheduled timers
-#[derive(Debug, Clone, Default)] // later also ,Serialize,Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ScheduledOutEntry {
     /// hour of the specified daily the start time
     pub start_hour: u8,
@@ -75,31 +262,620 @@ pub struct ScheduledOutEntry {
     pub start_minute: u8,
     /// specified duration
     pub duration: DurationMillis,
+    /// the days of the week on which the timer starts
+    pub weekdays: Weekdays,
+    /// whether the timer starts at the given time, or relative to the sun
+    pub start_mode: StartMode,
+    /// minutes from sunrise or sunset to the start, negative for before;
+    /// ignored for [`StartMode::Clock`]
+    pub sun_offset: i16,
+}
+
+impl ScheduledOutEntry {
+    /// the largest magnitude of [`Self::sun_offset`]
+    pub const MAX_SUN_OFFSET: i16 = 180;
+
+    /// the start in minutes after midnight of a day with the given sunrise and sunset
+    ///
+    /// Returns `None` if the timer refers to a sunrise or sunset
+    /// that does not happen on that day.
+    pub fn start_of_day(&self, sun_times: &SunTimes) -> Option<i32> {
+        let sun_event = match self.start_mode {
+            StartMode::Clock => {
+                return Some(self.start_hour as i32 * 60 + self.start_minute as i32);
+            }
+            StartMode::Sunrise => sun_times.sunrise?,
+            StartMode::Sunset => sun_times.sunset?,
+        };
+        Some(sun_event as i32 + self.sun_offset as i32)
+    }
+    /// checks whether `rtc_time` lies within one of the windows `[start, start + duration)`
+    ///
+    /// The start refers to local time in `time_zone`, while `rtc_time` is in UTC.
+    /// A window may extend past midnight, in which case it belongs to
+    /// the day of the week on which it started. The same holds for a window
+    /// starting before midnight due to a negative [`Self::sun_offset`].
+    /// See [`timezone`] for windows starting in a skipped or repeated hour.
+    pub fn is_active(
+        &self,
+        rtc_time: &RtcTime,
+        time_zone: &TimeZone,
+        sun_table: &SunTable,
+    ) -> bool {
+        self.time_left(rtc_time, time_zone, sun_table).is_some()
+    }
+    /// the time left until the end of the window containing `rtc_time`, if any
+    pub fn time_left(
+        &self,
+        rtc_time: &RtcTime,
+        time_zone: &TimeZone,
+        sun_table: &SunTable,
+    ) -> Option<DurationMillis> {
+        self.time_left_shifted(rtc_time, time_zone, sun_table, |_| WindowShift::default())
+    }
+    /// like [`Self::time_left`], but with the window of each day
+    /// shifted by `shift(day)`, where `day` counts the days since 1970-01-01
+    ///
+    /// The shifts must be less than a day.
+    pub fn time_left_shifted(
+        &self,
+        rtc_time: &RtcTime,
+        time_zone: &TimeZone,
+        sun_table: &SunTable,
+        shift: impl Fn(i32) -> WindowShift,
+    ) -> Option<DurationMillis> {
+        if self.duration <= 0 {
+            return None;
+        }
+        let today = time_zone.to_local(rtc_time).days_since_epoch();
+        // windows started on earlier days may still be running
+        let max_days_back = self.duration as i64 / MILLIS_PER_DAY + 2;
+        // a window starting before midnight belongs to the next day
+        (-2..=max_days_back as i32)
+            .filter_map(|days_back| {
+                let day = today - days_back;
+                if !self
+                    .weekdays
+                    .contains(Weekdays::from_weekday(calendar::weekday_from_days(day)))
+                {
+                    return None;
+                }
+                let shift = shift(day);
+                let start_of_day = self.start_of_day(&sun_table.get(day))? + shift.start;
+                let duration = self.duration + shift.duration * MILLIS_PER_MINUTE;
+                window_time_left(
+                    rtc_time,
+                    time_zone.local_to_utc_minutes(day * 24 * 60 + start_of_day),
+                    duration.max(MILLIS_PER_MINUTE),
+                )
+            })
+            .max()
+    }
+}
+
+impl Default for ScheduledOutEntry {
+    fn default() -> Self {
+        Self {
+            start_hour: 0,
+            start_minute: 0,
+            duration: 0,
+            weekdays: Weekdays::full(),
+            start_mode: StartMode::Clock,
+            sun_offset: 0,
+        }
+    }
+}
+
+/// the random shift of the window of a scheduled timer on one day,
+/// in [`OverrideMode::Vacation`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowShift {
+    /// minutes by which the start is moved, negative for earlier
+    pub start: i32,
+    /// minutes by which the duration is changed, negative for shorter
+    pub duration: i32,
+}
+
+impl WindowShift {
+    /// the largest bound of the shifts
+    pub const MAX_BOUND: DurationMillis = 3 * 60 * 60 * 1000;
+
+    /// draws the shift of the window starting on `day`
+    /// of slot `slot` of relay `relay`
+    ///
+    /// Both components lie within `bound`, which is rounded down to full minutes
+    /// and limited to [`Self::MAX_BOUND`]. The shift depends on nothing but
+    /// the arguments, so every evaluation on a day sees the same window,
+    /// while the windows of the next day are drawn fresh.
+    pub fn draw(seed: u64, day: i32, relay: usize, slot: usize, bound: DurationMillis) -> Self {
+        let bound = bound.clamp(0, Self::MAX_BOUND) / MILLIS_PER_MINUTE;
+        let key = (day as u32 as u64) << 16 | (relay as u64 & 0xff) << 8 | slot as u64 & 0xff;
+        let mut rng = Rng::new(seed ^ Rng::new(key).next_u64());
+        Self {
+            start: rng.symmetric(bound),
+            duration: rng.symmetric(bound),
+        }
+    }
+}
+
+/// setting for a timer starting once at a given date and time
+///
+/// The entry is removed from the [`Settings`] as soon as the timer has run out,
+/// or if the time for running it has passed.
+/// An entry with zero duration is inactive, but kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatedOutEntry {
+    /// year of the start date
+    pub year: u16,
+    /// month of the start date
+    pub month: u8,
+    /// day of the month of the start date
+    pub day: u8,
+    /// hour of the start time
+    pub start_hour: u8,
+    /// minute of the start time
+    pub start_minute: u8,
+    /// specified duration
+    pub duration: DurationMillis,
+}
+
+impl DatedOutEntry {
+    /// the start in minutes since 1970-01-01 00:00 local time
+    pub fn start_minutes(&self) -> i32 {
+        calendar::minutes_from_civil(
+            self.year,
+            self.month,
+            self.day,
+            self.start_hour,
+            self.start_minute,
+        )
+    }
+    /// checks whether `rtc_time` lies within `[start, start + duration)`
+    ///
+    /// The start refers to local time in `time_zone`, while `rtc_time` is in UTC.
+    pub fn is_active(&self, rtc_time: &RtcTime, time_zone: &TimeZone) -> bool {
+        self.time_left(rtc_time, time_zone).is_some()
+    }
+    /// the time left until the end of the timer, if it is running at `rtc_time`
+    pub fn time_left(&self, rtc_time: &RtcTime, time_zone: &TimeZone) -> Option<DurationMillis> {
+        window_time_left(
+            rtc_time,
+            time_zone.local_to_utc_minutes(self.start_minutes()),
+            self.duration,
+        )
+    }
+    /// checks whether the timer has run out at `rtc_time`
+    ///
+    /// Entries with zero duration never run out.
+    pub fn has_ended(&self, rtc_time: &RtcTime, time_zone: &TimeZone) -> bool {
+        let start_minutes = time_zone.local_to_utc_minutes(self.start_minutes());
+        self.duration > 0 && rtc_time.millis_since_minute(start_minutes) >= self.duration as i64
+    }
+}
+
+/// setting for a timer switching on and off repeatedly, e.g., 5 minutes on, 25 minutes off
+///
+/// The cycles start at the phase anchor, a local time of day, and follow
+/// each other without gaps. They restart at the anchor every day, which only
+/// makes a difference if the period does not divide 24 hours.
+/// An entry with zero on-duration is inactive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CycleOutEntry {
+    /// how long the relay is on at the beginning of each cycle
+    pub on_duration: DurationMillis,
+    /// how long the relay is off at the end of each cycle
+    pub off_duration: DurationMillis,
+    /// hour of the phase anchor
+    pub anchor_hour: u8,
+    /// minute of the phase anchor
+    pub anchor_minute: u8,
+    /// the time of day outside which the relay stays off, or `None` for the whole day
+    pub window: Option<TimeWindow>,
+}
+
+impl CycleOutEntry {
+    /// whether the timer switches the relay on at `rtc_time`,
+    /// and the time until the current phase of the cycle ends
+    ///
+    /// The anchor and the window refer to local time in `time_zone`,
+    /// while `rtc_time` is in UTC. Returns `None` for an inactive entry.
+    pub fn state(
+        &self,
+        rtc_time: &RtcTime,
+        time_zone: &TimeZone,
+    ) -> Option<(bool, DurationMillis)> {
+        if self.on_duration <= 0 {
+            return None;
+        }
+        let local = time_zone.to_local(rtc_time);
+        let anchor = |day: i32| {
+            let minutes = self.anchor_hour as i32 * 60 + self.anchor_minute as i32;
+            time_zone.local_to_utc_minutes(day * 24 * 60 + minutes)
+        };
+        let today = local.days_since_epoch();
+        let mut elapsed = rtc_time.millis_since_minute(anchor(today));
+        if elapsed < 0 {
+            // the cycles started at yesterday's anchor are still running
+            elapsed = rtc_time.millis_since_minute(anchor(today - 1));
+        }
+        let on_duration = self.on_duration as i64;
+        let period = on_duration + self.off_duration.max(0) as i64;
+        let phase = elapsed.rem_euclid(period);
+        let in_window = self
+            .window
+            .as_ref()
+            .map_or(true, |window| window.contains(local.hour, local.minute));
+        if phase < on_duration {
+            Some((in_window, (on_duration - phase) as DurationMillis))
+        } else {
+            Some((false, (period - phase) as DurationMillis))
+        }
+    }
+}
+
+/// when a [`ThermostatEntry`] switches its relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Sequence)]
+pub enum ThermostatMode {
+    /// the reading of the sensor is ignored
+    #[default]
+    Off,
+    /// the relay is switched by the reading at any time
+    Always,
+    /// the relay is switched by the reading, but only while one of its
+    /// scheduled timers is running; the scheduled timers no longer
+    /// switch the relay on by themselves
+    Scheduled,
+}
+
+/// setting for switching a relay by the reading of the analog sensor,
+/// e.g., as a thermostat or hygrostat
+///
+/// The thresholds are given in tenths of the unit of the sensor, like
+/// [`AccessAnalogInput::get_analog_value`]. The gap between them is the hysteresis.
+/// If the on-threshold lies below the off-threshold, the relay switches on
+/// as the reading falls, like a heating. Otherwise, it switches on
+/// as the reading rises, like a cooling or a dehumidifier.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThermostatEntry {
+    pub mode: ThermostatMode,
+    /// the reading at which the relay switches on
+    pub on_threshold: i16,
+    /// the reading at which the relay switches off
+    pub off_threshold: i16,
+}
+
+impl ThermostatEntry {
+    /// whether the relay should be on at the reading `value`,
+    /// given whether it has been requested to be on so far
+    ///
+    /// Between the thresholds, the request stays as it is.
+    /// Without a reading, e.g. if the sensor has failed, the relay is not requested.
+    pub fn demand(&self, was_on: bool, value: Option<i16>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        let (on, off) = (self.on_threshold, self.off_threshold);
+        if on <= off {
+            value <= on || (was_on && value < off)
+        } else {
+            value >= on || (was_on && value > off)
+        }
+    }
+}
+
+/// a daily period `[start, end)` of local time, with minute resolution
+///
+/// The period extends past midnight if the end lies before the start,
+/// and it covers the whole day if the end equals the start.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start_hour: u8,
+    pub start_minute: u8,
+    pub end_hour: u8,
+    pub end_minute: u8,
+}
+
+impl TimeWindow {
+    /// checks whether the given time of day lies within the window
+    pub fn contains(&self, hour: u8, minute: u8) -> bool {
+        let time = hour as u16 * 60 + minute as u16;
+        let start = self.start_hour as u16 * 60 + self.start_minute as u16;
+        let end = self.end_hour as u16 * 60 + self.end_minute as u16;
+        if start < end {
+            (start..end).contains(&time)
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+/// the time left until the end of the window `[start, start + duration)`
+/// if `rtc_time` lies within, both in UTC
+fn window_time_left(
+    rtc_time: &RtcTime,
+    start_minutes: i32,
+    duration: DurationMillis,
+) -> Option<DurationMillis> {
+    let elapsed = rtc_time.millis_since_minute(start_minutes);
+    if (0..duration as i64).contains(&elapsed) {
+        Some(duration - elapsed as DurationMillis)
+    } else {
+        None
+    }
+}
+
+/// a set of days of the week
+///
+/// The bit positions correspond to the numbering used by [`system::RtcTime::weekday`].
+#[bitmask(u8)]
+pub enum Weekdays {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekdays {
+    /// Monday to Friday
+    pub const fn workdays() -> Self {
+        Self::Monday
+            .or(Self::Tuesday)
+            .or(Self::Wednesday)
+            .or(Self::Thursday)
+            .or(Self::Friday)
+    }
+    /// Saturday and Sunday
+    pub const fn weekend() -> Self {
+        Self::Saturday.or(Self::Sunday)
+    }
+    /// the set containing only the given day of the week (0 = Monday)
+    pub fn from_weekday(weekday: u8) -> Self {
+        Self::from(1u8 << (weekday % 7))
+    }
 }
 
 /// main "task"
 ///
 /// This is the entry point. From here all other tasks are fork off.
 /// * the tasks driving the UI
+/// * the task answering the commands received over serial, see [`commands`]
 /// * the task driving the relays.
-pub async fn run(sys: impl System) {
-    let settings = RefCell::new(Settings::default());
-    join2(run_ui(&sys, &settings), watch_output(&sys, &settings)).await;
+///
+/// The number of relays and the number of scheduled entries per relay
+/// are given by `NUM_RELAYS` and `NUM_SLOTS`, see [`Settings`].
+/// The relays are never switched in violation of the [`OutputConfig`].
+/// Switching the relays, setting the clock and storing the settings
+/// are recorded in the `event_log`.
+/// The on-times of the relays are counted in `statistics`, which are
+/// replaced by the statistics stored in non-volatile memory first.
+pub async fn run<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: impl System<NUM_RELAYS>,
+    config: OutputConfig,
+    event_log: &EventLog,
+    statistics: &RefCell<RuntimeStats<NUM_RELAYS>>,
+) {
+    let settings = RefCell::new(storage::load_settings::<NUM_RELAYS, NUM_SLOTS>(&sys));
+    *statistics.borrow_mut() = storage::load_statistics(&sys);
+    let settings_changed = Notify::new();
+    let status = OutputStatus::new();
+    join2(
+        join2(
+            run_ui(
+                &sys,
+                &settings,
+                &settings_changed,
+                &status,
+                event_log,
+                statistics,
+            ),
+            run_commands(&sys, &settings, &settings_changed, &status, event_log),
+        ),
+        watch_output(
+            &sys,
+            &settings,
+            &settings_changed,
+            &status,
+            event_log,
+            statistics,
+            config,
+        ),
+    )
+    .await;
+}
+
+/// changes the current settings in place through `modify`
+///
+/// Writes to non-volatile memory only if the stored part of the settings has changed,
+/// and records this in the `event_log`.
+/// Notifies [`watch_output`] through `settings_changed`.
+pub(crate) fn update_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessPersistentStorage + AccessRtc),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    event_log: &EventLog,
+    modify: impl FnOnce(&mut Settings<NUM_RELAYS, NUM_SLOTS>),
+) {
+    if store_modified_settings(sys, settings, modify) {
+        event_log.record(sys.get_rtc(), Event::SettingsCommitted);
+    }
+    settings_changed.notify();
+}
+
+/// changes the current settings in place through `modify`,
+/// and writes them to non-volatile memory if the stored part has changed
+///
+/// Returns `true` if it has. Unlike [`update_settings`], this neither records
+/// an event nor notifies anybody, for changes the device makes on its own.
+fn store_modified_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &impl AccessPersistentStorage,
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    modify: impl FnOnce(&mut Settings<NUM_RELAYS, NUM_SLOTS>),
+) -> bool {
+    let mut settings = settings.borrow_mut();
+    let old_blob = storage::encode_settings(&settings);
+    modify(&mut settings);
+    let new_blob = storage::encode_settings(&settings);
+    let changed = new_blob != old_blob;
+    if changed {
+        sys.store_settings(&new_blob);
+    }
+    changed
+}
+
+/// sets the real time clock to `time`, in UTC
+///
+/// Records this in the `event_log` and notifies [`watch_output`] through `settings_changed`.
+pub(crate) fn set_clock(
+    sys: &impl AccessRtc,
+    time: &RtcTime,
+    settings_changed: &Notify,
+    event_log: &EventLog,
+) {
+    sys.set_rtc(time);
+    event_log.record(sys.get_rtc(), Event::ClockSet);
+    settings_changed.notify();
+}
+
+/// restrictions on switching the relays, given by the hardware connected to them
+#[derive(Clone, Debug, Default)]
+pub struct OutputConfig {
+    /// which relays may be on at the same time
+    pub interlocks: Interlocks,
+    /// the wear limits of each relay, by index; relays without an entry are not limited
+    pub wear_limits: Vec<WearLimits>,
+    /// the minimum time in milliseconds between two relays switching on;
+    /// 0 lets all relays switch on at once
+    pub stagger_interval: DurationMillis,
+    /// the seed of the random shifts in [`OverrideMode::Vacation`];
+    /// `None` derives it from the real time clock and the instant at startup
+    pub jitter_seed: Option<u64>,
+    /// the relays switched by the digital inputs
+    pub input_rules: Vec<InputRule>,
+    /// the time in milliseconds a digital input must keep its new level
+    /// before the change counts; 0 accepts every change at once
+    pub input_debounce: DurationMillis,
+}
+
+/// what [`watch_output`] reports to the user interface
+pub struct OutputStatus<const NUM_RELAYS: usize> {
+    /// the relays whose transition is currently deferred by their [`WearLimits`]
+    pub(crate) deferred: Cell<[bool; NUM_RELAYS]>,
+    /// the states the relays have been set to
+    pub(crate) relay_states: Cell<[bool; NUM_RELAYS]>,
+}
+
+impl<const NUM_RELAYS: usize> OutputStatus<NUM_RELAYS> {
+    pub(crate) fn new() -> Self {
+        Self {
+            deferred: Cell::new([false; NUM_RELAYS]),
+            relay_states: Cell::new([false; NUM_RELAYS]),
+        }
+    }
 }
 
-/// determines the frequency how often we check if relays need to change state
-const OUTPUT_UPDATE_DELAY: DurationMillis = 2000;
+/// the resolution of the real time clock
+const MILLIS_PER_MINUTE: DurationMillis = 60 * 1000;
+
+/// how often [`watch_output`] reads the analog sensor while a thermostat is in use
+pub const THERMOSTAT_INTERVAL: DurationMillis = 10 * 1000;
 
-/// regularly check whether the relays states need to change
-async fn watch_output(
-    sys: &(impl AccessOutputStates + AccessTiming + AccessRtc),
-    settings: &RefCell<Settings>,
+/// switches the relays whenever their states need to change
+///
+/// The states of scheduled and dated timers are derived from the
+/// real time clock alone. Hence, a timer whose start has been missed,
+/// e.g. because the device was switched off or the clock has been set,
+/// still runs for the remainder of its window.
+///
+/// Timers refer to local time in [`Settings::time_zone`], the real time clock to UTC.
+/// Timers starting at sunrise or sunset are resolved once a day through a [`SunTable`].
+///
+/// A relay is on if its [`OverrideMode`] is [`OverrideMode::ForcedOn`],
+/// or if it is [`OverrideMode::Auto`] and any of its immediate, scheduled,
+/// dated or cycle timers is running. Timers keep running while overridden.
+/// In [`OverrideMode::Vacation`], the windows of the scheduled timers
+/// are shifted by a [`WindowShift`] drawn from [`OutputConfig::jitter_seed`].
+/// The [`OutputConfig::input_rules`] and the thermostats count like timers,
+/// except for a [`ThermostatMode::Scheduled`] thermostat, which replaces the scheduled timers.
+/// Finally, the [`WearLimits`] may defer transitions,
+/// and the [`Interlocks`] may hold relays back, even forced ones.
+/// Relays due to be switched on at the same time are switched on
+/// one after the other, [`OutputConfig::stagger_interval`] apart.
+/// Every switching of a relay is recorded in the `event_log`, with its [`Cause`],
+/// and counted in the `statistics`, together with the on-times.
+///
+/// Between two evaluations, the task sleeps until the next instant
+/// at which a relay state can change: the end of a running timer or of a phase
+/// of a cycle timer, or the next full minute of the real time clock, when scheduled timers start,
+/// or the end of a deferral, a dead time or a stagger interval imposed by the [`OutputConfig`],
+/// or of a pulse or the debounce time of a digital input.
+/// While a thermostat is in use, the analog sensor is read every [`THERMOSTAT_INTERVAL`].
+/// It is woken up early through `settings_changed`, which must be notified
+/// whenever the settings or the real time clock are changed,
+/// and by [`SignalFlags::Input`].
+async fn watch_output<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessOutputStates<NUM_RELAYS>
+          + AccessTiming
+          + AccessExecutorSignals<SignalFlags>
+          + AccessRtc
+          + AccessPersistentStorage
+          + AccessDigitalInputs
+          + AccessAnalogInput),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    statistics: &RefCell<RuntimeStats<NUM_RELAYS>>,
+    config: OutputConfig,
 ) {
     let mut old_relays_state = [false; NUM_RELAYS];
-    let mut scheduled_out_stop =
-        [[Option::<InstantMillis>::None; MAX_SCHEDULED_ENTRIES]; NUM_RELAYS];
+    let mut counted_until = sys.get_instant();
+    let mut statistics_stored_at = counted_until;
+    // why each relay has last been switched on; placeholders until it is
+    let mut on_causes = [Cause::Override; NUM_RELAYS];
+    let mut wear_state = WearState::<NUM_RELAYS>::new(config.wear_limits);
+    let mut interlock_state = InterlockState::<NUM_RELAYS>::new(config.interlocks);
+    let mut stagger_state = StaggerState::<NUM_RELAYS>::new(config.stagger_interval);
+    let mut input_state = InputState::<NUM_RELAYS>::new(config.input_rules, config.input_debounce);
+    let mut sun_table: Option<SunTable> = None;
+    let mut thermostat_demand = [false; NUM_RELAYS];
+    let jitter_seed = config
+        .jitter_seed
+        .unwrap_or_else(|| random::seed_from(&sys.get_rtc(), sys.get_instant()));
     loop {
+        let rtc_time = sys.get_rtc();
+        let time_zone = settings.borrow().time_zone;
+        // resolve sunrise and sunset once a day, or when the location has changed
+        let local_time = time_zone.to_local(&rtc_time);
+        let today = local_time.days_since_epoch();
+        // count the on-times up to now, and store them once in a while
+        {
+            let now = sys.get_instant();
+            let since_midnight = local_time.millis_since_minute(today * 24 * 60) as DurationMillis;
+            let mut statistics = statistics.borrow_mut();
+            let is_new_day = statistics.count_until(
+                &old_relays_state,
+                now - counted_until,
+                today,
+                since_midnight,
+            );
+            counted_until = now;
+            if is_new_day || now - statistics_stored_at >= STATISTICS_STORE_INTERVAL {
+                sys.store_statistics(&storage::encode_statistics(&statistics));
+                statistics_stored_at = now;
+            }
+        }
+        let location = settings.borrow().location;
+        let sun_table = match &mut sun_table {
+            Some(table) if table.is_current(&location, &time_zone, today) => table,
+            table => table.insert(SunTable::new(location, time_zone, today)),
+        };
         let mut new_relays_state = [false; NUM_RELAYS];
+        // the first reason found for each relay to be on, or to be kept off against a timer
+        let mut causes: [Option<Cause>; NUM_RELAYS] = [None; NUM_RELAYS];
+        let mut next_change = MILLIS_PER_MINUTE - rtc_time.second as DurationMillis * 1000;
         // check immediate entries
         {
             let mut settings = settings.borrow_mut();
@@ -107,47 +883,179 @@ async fn watch_output(
                 let mut entry_copy = entry.clone();
                 normalize_immediate_out_entry(&mut entry_copy, sys.get_instant());
                 new_relays_state[i] = entry_copy.is_some();
-                if entry_copy.is_none() {
+                if let Some(entry_copy) = entry_copy {
+                    causes[i] = Some(Cause::Immediate);
+                    next_change = next_change.min(entry_copy.duration);
+                } else {
                     // this timer is no longer active and the entry can be deleted
                     *entry = None;
                 }
             }
         }
-        // check scheduled entries
+        // check scheduled entries, remembering the first running slot of each relay
+        let mut scheduled: [Option<u8>; NUM_RELAYS] = [None; NUM_RELAYS];
         {
-            let rtc_time = sys.get_rtc();
-            let scheduled_entries = &settings.borrow().scheduled_out;
-            for i_relays in 0..NUM_RELAYS {
-                for i_scheduled in 0..MAX_SCHEDULED_ENTRIES {
-                    if let Some(stop_instant) = scheduled_out_stop[i_relays][i_scheduled] {
-                        let time_left = stop_instant - sys.get_instant();
-                        if time_left >= 0 {
-                            new_relays_state[i_relays] = true;
+            let settings = settings.borrow();
+            for (i, entries) in settings.scheduled_out.iter().enumerate() {
+                let vacation = settings.override_mode[i] == OverrideMode::Vacation;
+                let bound = settings.vacation_jitter[i];
+                for (slot, entry) in entries.iter().enumerate() {
+                    let shift = |day| {
+                        if vacation {
+                            WindowShift::draw(jitter_seed, day, i, slot, bound)
                         } else {
-                            scheduled_out_stop[i_relays][i_scheduled] = None;
+                            WindowShift::default()
                         }
-                    } else {
-                        let entry = &scheduled_entries[i_relays][i_scheduled];
-                        if entry.duration > 0
-                            && entry.start_hour == rtc_time.hour
-                            && entry.start_minute == rtc_time.minute
-                        {
-                            scheduled_out_stop[i_relays][i_scheduled] =
-                                Some(sys.get_instant() + entry.duration);
-                            new_relays_state[i_relays] = true;
+                    };
+                    if let Some(time_left) =
+                        entry.time_left_shifted(&rtc_time, &time_zone, sun_table, shift)
+                    {
+                        scheduled[i].get_or_insert(slot as u8);
+                        next_change = next_change.min(time_left);
+                    }
+                }
+            }
+        }
+        // check dated entries
+        {
+            let mut expired = [false; NUM_RELAYS];
+            for (i, entry) in settings.borrow().dated_out.iter().enumerate() {
+                let Some(entry) = entry else {
+                    continue;
+                };
+                if let Some(time_left) = entry.time_left(&rtc_time, &time_zone) {
+                    new_relays_state[i] = true;
+                    causes[i].get_or_insert(Cause::Dated);
+                    next_change = next_change.min(time_left);
+                }
+                expired[i] = entry.has_ended(&rtc_time, &time_zone);
+            }
+            if expired.contains(&true) {
+                // disarm the timers that will never fire again, leaving the other settings alone;
+                // the log has the relay switched off already, so this is no commit of the user
+                store_modified_settings(sys, settings, |settings| {
+                    for (entry, expired) in settings.dated_out.iter_mut().zip(expired) {
+                        if expired {
+                            *entry = None;
                         }
                     }
+                });
+            }
+        }
+        // check cycle entries; a window boundary is always at a full minute
+        for (i, entry) in settings.borrow().cycle_out.iter().enumerate() {
+            if let Some((is_on, time_left)) = entry.state(&rtc_time, &time_zone) {
+                if is_on {
+                    new_relays_state[i] = true;
+                    causes[i].get_or_insert(Cause::Cycle);
                 }
+                next_change = next_change.min(time_left);
             }
         }
+        // check thermostats; they decide on their own whether scheduled timers switch the relay
+        {
+            let value = sys.get_analog_value();
+            for (i, entry) in settings.borrow().thermostat.iter().enumerate() {
+                thermostat_demand[i] = entry.demand(thermostat_demand[i], value);
+                let scheduled_cause = scheduled[i].map(|slot| Cause::Scheduled { slot });
+                let thermostat_cause = thermostat_demand[i].then_some(Cause::Thermostat);
+                let cause = match entry.mode {
+                    ThermostatMode::Off => scheduled_cause,
+                    ThermostatMode::Always => scheduled_cause.or(thermostat_cause),
+                    ThermostatMode::Scheduled => scheduled_cause.and(thermostat_cause),
+                };
+                if let Some(cause) = cause {
+                    new_relays_state[i] = true;
+                    causes[i].get_or_insert(cause);
+                }
+                if entry.mode != ThermostatMode::Off {
+                    next_change = next_change.min(THERMOSTAT_INTERVAL);
+                }
+            }
+        }
+        // check digital inputs
+        let inputs = input_state.apply(sys.get_input_flags(), sys.get_instant());
+        for (i, input_state) in inputs.states.into_iter().enumerate() {
+            if input_state {
+                new_relays_state[i] = true;
+                causes[i].get_or_insert(Cause::Input);
+            }
+        }
+        if let Some(wake_up) = inputs.wake_up {
+            next_change = next_change.min(wake_up);
+        }
+        // the override modes have the last word
+        for (i, mode) in settings.borrow().override_mode.into_iter().enumerate() {
+            let state = mode.apply(new_relays_state[i]);
+            if state != new_relays_state[i] {
+                new_relays_state[i] = state;
+                causes[i] = Some(Cause::Override);
+            }
+        }
+        let now = sys.get_instant();
+        let wear = wear_state.apply(&new_relays_state, now);
+        status.deferred.set(wear.deferred);
+        let (new_relays_state, dead_time_left) = interlock_state.enforce(&wear.states, now);
+        for (i, cause) in causes.iter_mut().enumerate() {
+            if wear.states[i] && !new_relays_state[i] {
+                *cause = Some(Cause::Interlock);
+            }
+        }
+        let (new_relays_state, stagger_time_left) =
+            stagger_state.apply(&new_relays_state, &old_relays_state, now);
+        interlock_state.record(&new_relays_state, now);
+        for wake_up in [wear.wake_up, dead_time_left, stagger_time_left]
+            .into_iter()
+            .flatten()
+        {
+            next_change = next_change.min(wake_up);
+        }
         if old_relays_state != new_relays_state {
             sys.set_relay_states(&new_relays_state);
+            status.relay_states.set(new_relays_state);
+            wear_state.record(&new_relays_state, now);
+            for (i, (&old, &new)) in old_relays_state.iter().zip(&new_relays_state).enumerate() {
+                let relay = i as u8;
+                let event = match (old, new) {
+                    (false, true) => {
+                        statistics.borrow_mut().count_switch(i);
+                        on_causes[i] = causes[i].unwrap_or(on_causes[i]);
+                        Event::RelayOn {
+                            relay,
+                            cause: on_causes[i],
+                        }
+                    }
+                    (true, false) => {
+                        let cause = match causes[i] {
+                            Some(cause @ (Cause::Override | Cause::Interlock)) => cause,
+                            _ => on_causes[i],
+                        };
+                        Event::RelayOff { relay, cause }
+                    }
+                    _ => continue,
+                };
+                event_log.record(rtc_time, event);
+            }
             old_relays_state = new_relays_state;
         }
-        Timer::new(sys, OUTPUT_UPDATE_DELAY).wait().await;
+        wait_for_change(sys, settings_changed, next_change).await;
     }
 }
 
+/// waits until notified through `settings_changed`, until a digital input
+/// has changed, or until `duration` has passed
+async fn wait_for_change(
+    sys: &(impl AccessTiming + AccessExecutorSignals<SignalFlags>),
+    settings_changed: &Notify,
+    duration: DurationMillis,
+) {
+    select2(
+        settings_changed.wait(),
+        with_timeout(sys, duration, system::wait_event(sys, SignalFlags::Input)),
+    )
+    .await;
+}
+
 fn normalize_immediate_out_entry(entry: &mut Option<ImmediateOutEntry>, now: InstantMillis) {
     let Some(ImmediateOutEntry { start, duration }) = entry else {
         return;
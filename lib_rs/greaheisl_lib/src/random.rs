@@ -0,0 +1,47 @@
+//! a small deterministic pseudo random number generator
+//!
+//! The generator is [SplitMix64](https://prng.di.unimi.it/splitmix64.c).
+//! It is not suitable for cryptography, but it is fast, needs only eight bytes
+//! of state, and produces the same sequence for the same seed on every target.
+//! This lets the emulator and the tests replay the random choices of the device.
+
+use crate::system::RtcTime;
+use greaheisl_async::InstantMillis;
+
+/// pseudo random number generator with 64 bits of state
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    /// the next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// a number drawn uniformly from `-bound..=bound`
+    ///
+    /// Negative bounds count like positive ones.
+    pub fn symmetric(&mut self, bound: i32) -> i32 {
+        let bound = bound.unsigned_abs() as u64;
+        // the bias of the modulo is negligible for bounds far below 2^64
+        ((self.next_u64() % (2 * bound + 1)) as i64 - bound as i64) as i32
+    }
+}
+
+/// a seed derived from the real time clock and the instant
+///
+/// The instant counts the milliseconds since the device was started,
+/// so seeds differ between devices switched on at the same minute.
+pub fn seed_from(rtc: &RtcTime, instant: InstantMillis) -> u64 {
+    let minutes = rtc.minutes_since_epoch() as u32 as u64;
+    let rtc_part = minutes * 60 + rtc.second as u64;
+    Rng::new(rtc_part << 32 | instant.into_inner() as u64).next_u64()
+}
@@ -0,0 +1,137 @@
+//! offline calculation of sunrise and sunset
+//!
+//! Implements the "sunrise equation" with the usual approximations
+//! for the equation of center and the equation of time, which is accurate
+//! to about a minute outside the polar regions. Only the date and the
+//! [`Location`] of the device are needed, so there is no need for a network.
+
+use crate::timezone::TimeZone;
+use libm::{acos, asin, cos, round, sin};
+
+/// number of days from 1970-01-01 to 2000-01-01, the day of the epoch J2000
+const DAYS_UNTIL_J2000: i32 = 10957;
+const MINUTES_PER_DAY: f64 = 24.0 * 60.0;
+
+/// the geographic location of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// latitude in hundredths of a degree, positive towards north
+    pub latitude: i16,
+    /// longitude in hundredths of a degree, positive towards east
+    pub longitude: i16,
+}
+
+/// the center of Germany
+impl Default for Location {
+    fn default() -> Self {
+        Self {
+            latitude: 5116,
+            longitude: 1045,
+        }
+    }
+}
+
+impl Location {
+    /// the valid range of [`Self::latitude`]
+    pub const LATITUDE_RANGE: core::ops::RangeInclusive<i16> = -9000..=9000;
+    /// the valid range of [`Self::longitude`]
+    pub const LONGITUDE_RANGE: core::ops::RangeInclusive<i16> = -18000..=18000;
+
+    /// checks whether all fields are within their valid ranges
+    pub fn is_valid(&self) -> bool {
+        Self::LATITUDE_RANGE.contains(&self.latitude)
+            && Self::LONGITUDE_RANGE.contains(&self.longitude)
+    }
+}
+
+/// sunrise and sunset of a day, in minutes after local midnight
+///
+/// A value is `None` if the sun does not rise or set on that day,
+/// i.e., during polar day or polar night.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SunTimes {
+    pub sunrise: Option<i16>,
+    pub sunset: Option<i16>,
+}
+
+/// calculates sunrise and sunset at `location` on the given day (days since 1970-01-01)
+///
+/// The times are converted into local time with the offset of `time_zone`
+/// in effect at sunrise and sunset, respectively.
+pub fn sun_times(location: &Location, time_zone: &TimeZone, day: i32) -> SunTimes {
+    let latitude = (location.latitude as f64 / 100.0).to_radians();
+    let longitude = location.longitude as f64 / 100.0;
+    // noon UTC of the given day, in days since the epoch J2000
+    let noon = (day - DAYS_UNTIL_J2000) as f64;
+    // approximate local solar noon
+    let mean_noon = noon - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_noon).to_radians();
+    let center = 1.9148 * sin(mean_anomaly)
+        + 0.0200 * sin(2.0 * mean_anomaly)
+        + 0.0003 * sin(3.0 * mean_anomaly);
+    let ecliptic_longitude = (mean_anomaly.to_degrees() + center + 180.0 + 102.9372).to_radians();
+    let solar_noon =
+        mean_noon + 0.0053 * sin(mean_anomaly) - 0.0069 * sin(2.0 * ecliptic_longitude);
+    let sin_declination = sin(ecliptic_longitude) * sin(23.4397f64.to_radians());
+    let cos_declination = cos(asin(sin_declination));
+    // the upper limb of the sun touches the horizon, including refraction
+    let cos_hour_angle = (sin((-0.833f64).to_radians()) - sin(latitude) * sin_declination)
+        / (cos(latitude) * cos_declination);
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return SunTimes::default();
+    }
+    let half_day = acos(cos_hour_angle).to_degrees() / 360.0;
+    let to_minutes = |time: f64| {
+        let midnight = day * 24 * 60;
+        let utc_minutes = midnight + round((time - noon + 0.5) * MINUTES_PER_DAY) as i32;
+        (time_zone.utc_to_local_minutes(utc_minutes) - midnight) as i16
+    };
+    SunTimes {
+        sunrise: Some(to_minutes(solar_noon - half_day)),
+        sunset: Some(to_minutes(solar_noon + half_day)),
+    }
+}
+
+/// sunrise and sunset of the days around today, resolved once a day
+///
+/// A scheduled window may have started on one of the preceding days
+/// and still be running, or it may belong to tomorrow but start before
+/// midnight, see [`crate::ScheduledOutEntry::time_left`].
+#[derive(Debug, Clone)]
+pub struct SunTable {
+    location: Location,
+    time_zone: TimeZone,
+    today: i32,
+    /// from tomorrow back to the day before yesterday
+    times: [SunTimes; Self::NUM_DAYS],
+}
+
+impl SunTable {
+    /// tomorrow, and windows are at most a day long, so they started at most two days ago
+    const NUM_DAYS: usize = 4;
+
+    pub fn new(location: Location, time_zone: TimeZone, today: i32) -> Self {
+        Self {
+            location,
+            time_zone,
+            today,
+            times: core::array::from_fn(|i| sun_times(&location, &time_zone, today + 1 - i as i32)),
+        }
+    }
+
+    /// checks whether the table needs no update for `location`, `time_zone` and `today`
+    pub fn is_current(&self, location: &Location, time_zone: &TimeZone, today: i32) -> bool {
+        self.location == *location && self.time_zone == *time_zone && self.today == today
+    }
+
+    /// sunrise and sunset on the given day
+    ///
+    /// Days not covered by the table are calculated on the fly.
+    pub fn get(&self, day: i32) -> SunTimes {
+        usize::try_from(self.today + 1 - day)
+            .ok()
+            .and_then(|i| self.times.get(i))
+            .copied()
+            .unwrap_or_else(|| sun_times(&self.location, &self.time_zone, day))
+    }
+}
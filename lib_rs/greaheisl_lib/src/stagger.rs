@@ -0,0 +1,62 @@
+//! staggered switching of relays, to avoid inrush current spikes
+//!
+//! Relays about to be switched on wait in a queue and are switched on
+//! one at a time, at least the stagger interval apart. Relays are
+//! switched off right away, which never adds to the load.
+
+use alloc::collections::VecDeque;
+use greaheisl_async::{DurationMillis, InstantMillis};
+
+/// the per-relay transition queue
+pub(crate) struct StaggerState<const NUM_RELAYS: usize> {
+    interval: DurationMillis,
+    /// the indices of the relays waiting to be switched on, in order
+    queue: VecDeque<usize>,
+    /// when a relay was switched on the last time
+    last_switch_on: Option<InstantMillis>,
+}
+
+impl<const NUM_RELAYS: usize> StaggerState<NUM_RELAYS> {
+    pub fn new(interval: DurationMillis) -> Self {
+        Self {
+            interval,
+            queue: VecDeque::with_capacity(NUM_RELAYS),
+            last_switch_on: None,
+        }
+    }
+
+    /// moves the `current` relay states one step towards the `target`
+    ///
+    /// Returns the new states and the time until the next relay
+    /// in the queue may be switched on, if any.
+    pub fn apply(
+        &mut self,
+        target: &[bool; NUM_RELAYS],
+        current: &[bool; NUM_RELAYS],
+        now: InstantMillis,
+    ) -> ([bool; NUM_RELAYS], Option<DurationMillis>) {
+        let mut states = *target;
+        // relays no longer about to be switched on leave the queue
+        self.queue.retain(|&k| target[k] && !current[k]);
+        for k in 0..NUM_RELAYS {
+            if target[k] && !current[k] {
+                states[k] = false;
+                if !self.queue.contains(&k) {
+                    self.queue.push_back(k);
+                }
+            }
+        }
+        while let Some(&k) = self.queue.front() {
+            if let Some(last) = self.last_switch_on {
+                let time_left = self.interval - (now - last);
+                if time_left > 0 {
+                    return (states, Some(time_left));
+                }
+            }
+            self.queue.pop_front();
+            states[k] = true;
+            self.last_switch_on = Some(now);
+        }
+        (states, None)
+    }
+}
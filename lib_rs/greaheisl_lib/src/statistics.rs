@@ -0,0 +1,104 @@
+//! accumulated on-times and switch counts of the relays
+//!
+//! The counters are maintained by the task driving the relays.
+//! The days refer to the local time of the real time clock,
+//! so setting the clock to another day starts a new day as well.
+//! An on-period spanning midnight is split between the two days.
+//! The counters are written to non-volatile memory every [`STATISTICS_STORE_INTERVAL`]
+//! and at the start of each day, see [`crate::storage::encode_statistics`].
+//! The on-time since the last write is lost when the device is switched off.
+
+use greaheisl_async::DurationMillis;
+
+/// how often the counters are written to non-volatile memory, at most
+pub const STATISTICS_STORE_INTERVAL: DurationMillis = 60 * 60 * 1000;
+
+/// the counters of a relay
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayStats {
+    /// the on-time in milliseconds since the counters were started
+    pub total_on: u64,
+    /// the on-time in milliseconds today
+    pub today: DurationMillis,
+    /// the on-time in milliseconds yesterday
+    pub yesterday: DurationMillis,
+    /// the number of times the relay has been switched on
+    pub switch_count: u32,
+}
+
+/// the counters of all relays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeStats<const NUM_RELAYS: usize> {
+    pub relays: [RelayStats; NUM_RELAYS],
+    /// the day [`RelayStats::today`] refers to, counted like
+    /// [`crate::system::RtcTime::days_since_epoch`]
+    pub day: i32,
+}
+
+impl<const NUM_RELAYS: usize> Default for RuntimeStats<NUM_RELAYS> {
+    fn default() -> Self {
+        Self {
+            relays: [RelayStats::default(); NUM_RELAYS],
+            day: 0,
+        }
+    }
+}
+
+impl<const NUM_RELAYS: usize> RuntimeStats<NUM_RELAYS> {
+    /// adds `elapsed` milliseconds to the on-time of the relays on in `states`
+    pub fn add_on_time(&mut self, states: &[bool; NUM_RELAYS], elapsed: DurationMillis) {
+        let elapsed = elapsed.max(0);
+        for (stats, _) in self.relays.iter_mut().zip(states).filter(|(_, &on)| on) {
+            stats.total_on += elapsed as u64;
+            stats.today = stats.today.saturating_add(elapsed);
+        }
+    }
+
+    /// adds `elapsed` milliseconds to the on-time of the relays on in `states`,
+    /// and moves on to `day`, see [`Self::roll_over`]
+    ///
+    /// `elapsed` ends `since_midnight` milliseconds into `day`. If that is a new day,
+    /// the on-time before midnight still counts for the current day.
+    /// Returns `true` if `day` is a new one.
+    pub fn count_until(
+        &mut self,
+        states: &[bool; NUM_RELAYS],
+        elapsed: DurationMillis,
+        day: i32,
+        since_midnight: DurationMillis,
+    ) -> bool {
+        let elapsed = elapsed.max(0);
+        let after_midnight = if day == self.day {
+            0
+        } else {
+            elapsed.min(since_midnight.max(0))
+        };
+        self.add_on_time(states, elapsed - after_midnight);
+        let is_new_day = self.roll_over(day);
+        self.add_on_time(states, after_midnight);
+        is_new_day
+    }
+
+    /// counts a relay being switched on
+    pub fn count_switch(&mut self, relay: usize) {
+        let stats = &mut self.relays[relay];
+        stats.switch_count = stats.switch_count.saturating_add(1);
+    }
+
+    /// moves on to the given day; returns `true` if it is a new one
+    ///
+    /// Today's on-time becomes yesterday's if `day` follows the current day,
+    /// otherwise yesterday's on-time is unknown and set to 0.
+    pub fn roll_over(&mut self, day: i32) -> bool {
+        if day == self.day {
+            return false;
+        }
+        for stats in self.relays.iter_mut() {
+            stats.yesterday = if day == self.day + 1 { stats.today } else { 0 };
+            stats.today = 0;
+        }
+        self.day = day;
+        true
+    }
+}
@@ -0,0 +1,762 @@
+//! binary encoding of the [`Settings`] for non-volatile memory
+//!
+//! The settings are stored as a "blob" of bytes with the following layout:
+//!
+//! | bytes | content                                            |
+//! |-------|----------------------------------------------------|
+//! | 2     | magic number `b"GH"`                               |
+//! | 1     | format version [`STORAGE_VERSION`]                 |
+//! | ...   | payload, see the [`Persist`] implementations       |
+//! | 2     | CRC-16/CCITT of all preceding bytes                |
+//!
+//! All multi-byte integers are stored in little endian byte order.
+//! Blobs written by an older version of the format are still accepted;
+//! fields missing in that version are set to their defaults.
+//! Blobs written with a different number of relays or scheduled entries
+//! per relay are accepted as well; surplus entries are dropped and
+//! missing entries are set to their defaults.
+//! Timers started in immediate mode refer to the millisecond counter
+//! of the running system, so they are not stored.
+//!
+//! The [`RuntimeStats`] are stored in a blob of their own, since they change
+//! much more often. It has the same layout, with the magic number `b"GS"`
+//! and the format version [`STATISTICS_VERSION`].
+
+use crate::calendar::is_valid_date;
+use crate::solar::Location;
+use crate::statistics::{RelayStats, RuntimeStats};
+use crate::system::AccessPersistentStorage;
+use crate::timezone::{DstRule, TimeZone};
+use crate::{
+    CycleOutEntry, DatedOutEntry, OverrideMode, ScheduledOutEntry, Settings, StartMode,
+    ThermostatEntry, ThermostatMode, TimeWindow, Weekdays, DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+use greaheisl_async::DurationMillis;
+
+/// identifies a blob of stored settings
+const STORAGE_MAGIC: [u8; 2] = *b"GH";
+/// needs to be incremented whenever the layout of the payload changes
+///
+/// * version 1: initial layout
+/// * version 2: [`ScheduledOutEntry::weekdays`] added
+/// * version 3: [`Settings::dated_out`] added
+/// * version 4: number of relays and scheduled entries per relay added;
+///   before, they were fixed to [`DEFAULT_NUM_RELAYS`] and [`DEFAULT_NUM_SLOTS`]
+/// * version 5: [`Settings::override_mode`] added
+/// * version 6: [`ScheduledOutEntry::start_mode`], [`ScheduledOutEntry::sun_offset`]
+///   and [`Settings::location`] added
+/// * version 7: [`TimeZone::dst`] added; the difference to UTC moved
+///   from the location to [`Settings::time_zone`], in the same place
+/// * version 8: [`Settings::cycle_out`] added
+/// * version 9: [`OverrideMode::Vacation`] and [`Settings::vacation_jitter`] added
+/// * version 10: [`Settings::thermostat`] added
+pub const STORAGE_VERSION: u8 = 10;
+const HEADER_SIZE: usize = STORAGE_MAGIC.len() + 1;
+/// identifies a blob of stored statistics
+const STATISTICS_MAGIC: [u8; 2] = *b"GS";
+/// needs to be incremented whenever the layout of the statistics payload changes
+///
+/// * version 1: initial layout
+pub const STATISTICS_VERSION: u8 = 1;
+const CHECKSUM_SIZE: usize = 2;
+/// the largest number of relays and of scheduled entries per relay
+/// a blob may have been written with, see [`Settings`]
+const MAX_STORED_CONFIG: usize = 9;
+
+/// the total size of a settings blob encoded with the current version in bytes
+pub const fn settings_blob_size(num_relays: usize, num_slots: usize) -> usize {
+    HEADER_SIZE
+        + 2
+        + num_relays * (num_slots * ScheduledOutEntry::STORED_SIZE)
+        + num_relays * <Option<DatedOutEntry>>::STORED_SIZE
+        + num_relays * OverrideMode::STORED_SIZE
+        + Location::STORED_SIZE
+        + TimeZone::STORED_SIZE
+        + num_relays * CycleOutEntry::STORED_SIZE
+        + num_relays * 4
+        + num_relays * ThermostatEntry::STORED_SIZE
+        + CHECKSUM_SIZE
+}
+
+/// reasons why a stored blob cannot be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// fewer bytes than expected
+    TooShort,
+    /// more bytes than expected
+    TooLong,
+    /// the blob does not start with the magic number
+    BadMagic,
+    /// the blob was written by an incompatible version of the library
+    UnsupportedVersion,
+    /// the checksum does not match the content
+    BadChecksum,
+    /// the content is out of the valid range
+    InvalidValue,
+}
+
+/// appends data to a blob
+pub struct StorageWriter {
+    buffer: Vec<u8>,
+}
+
+impl StorageWriter {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+    pub fn put_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+    pub fn put_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn put_i16(&mut self, value: i16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn put_i32(&mut self, value: i32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn put_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn put_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn put_bytes(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+    /// the bytes written so far
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+/// reads data from a blob
+pub struct StorageReader<'a> {
+    data: &'a [u8],
+    version: u8,
+}
+
+impl<'a> StorageReader<'a> {
+    pub(crate) fn new(data: &'a [u8], version: u8) -> Self {
+        Self { data, version }
+    }
+    /// the format version the blob was written with
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], StorageError> {
+        if self.data.len() < N {
+            return Err(StorageError::TooShort);
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        Ok(head.try_into().unwrap())
+    }
+    pub fn skip(&mut self, len: usize) -> Result<(), StorageError> {
+        if self.data.len() < len {
+            return Err(StorageError::TooShort);
+        }
+        self.data = &self.data[len..];
+        Ok(())
+    }
+    pub fn get_u8(&mut self) -> Result<u8, StorageError> {
+        Ok(self.take::<1>()?[0])
+    }
+    pub fn get_u16(&mut self) -> Result<u16, StorageError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+    pub fn get_i16(&mut self) -> Result<i16, StorageError> {
+        Ok(i16::from_le_bytes(self.take()?))
+    }
+    pub fn get_i32(&mut self) -> Result<i32, StorageError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+    pub fn get_u32(&mut self) -> Result<u32, StorageError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+    pub fn get_u64(&mut self) -> Result<u64, StorageError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+    /// the bytes not read yet, which are consumed
+    pub fn get_rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.data)
+    }
+}
+
+/// a type that can be written to and read from a blob
+pub trait Persist: Sized {
+    /// number of bytes written by [`Persist::store`] with the current version
+    const STORED_SIZE: usize;
+    fn store(&self, writer: &mut StorageWriter);
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError>;
+}
+
+impl<T: Persist, const N: usize> Persist for [T; N] {
+    const STORED_SIZE: usize = N * T::STORED_SIZE;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        for item in self {
+            item.store(writer);
+        }
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let mut result: [Option<T>; N] = core::array::from_fn(|_| None);
+        for item in result.iter_mut() {
+            *item = Some(T::load(reader)?);
+        }
+        Ok(result.map(Option::unwrap))
+    }
+}
+
+/// stored as a presence flag followed by the value, or by zeros if there is no value
+impl<T: Persist> Persist for Option<T> {
+    const STORED_SIZE: usize = 1 + T::STORED_SIZE;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        match self {
+            Some(value) => {
+                writer.put_u8(1);
+                value.store(writer);
+            }
+            None => {
+                for _ in 0..Self::STORED_SIZE {
+                    writer.put_u8(0);
+                }
+            }
+        }
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        match reader.get_u8()? {
+            0 => {
+                reader.skip(T::STORED_SIZE)?;
+                Ok(None)
+            }
+            1 => Ok(Some(T::load(reader)?)),
+            _ => Err(StorageError::InvalidValue),
+        }
+    }
+}
+
+/// stored with the number of relays and scheduled entries per relay in front
+impl<const NUM_RELAYS: usize, const NUM_SLOTS: usize> Persist for Settings<NUM_RELAYS, NUM_SLOTS> {
+    const STORED_SIZE: usize =
+        settings_blob_size(NUM_RELAYS, NUM_SLOTS) - HEADER_SIZE - CHECKSUM_SIZE;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(NUM_RELAYS as u8);
+        writer.put_u8(NUM_SLOTS as u8);
+        self.scheduled_out.store(writer);
+        self.dated_out.store(writer);
+        self.override_mode.store(writer);
+        self.location.store(writer);
+        self.time_zone.store(writer);
+        self.cycle_out.store(writer);
+        for jitter in self.vacation_jitter {
+            writer.put_i32(jitter);
+        }
+        self.thermostat.store(writer);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let (stored_relays, stored_slots) = if reader.version() >= 4 {
+            (reader.get_u8()? as usize, reader.get_u8()? as usize)
+        } else {
+            (DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS)
+        };
+        let mut settings = Settings::default();
+        for i_relay in 0..stored_relays {
+            for i_slot in 0..stored_slots {
+                let entry = ScheduledOutEntry::load(reader)?;
+                if i_relay < NUM_RELAYS && i_slot < NUM_SLOTS {
+                    settings.scheduled_out[i_relay][i_slot] = entry;
+                }
+            }
+        }
+        if reader.version() >= 3 {
+            for i_relay in 0..stored_relays {
+                let entry = <Option<DatedOutEntry>>::load(reader)?;
+                if i_relay < NUM_RELAYS {
+                    settings.dated_out[i_relay] = entry;
+                }
+            }
+        }
+        if reader.version() >= 5 {
+            for i_relay in 0..stored_relays {
+                let mode = OverrideMode::load(reader)?;
+                if i_relay < NUM_RELAYS {
+                    settings.override_mode[i_relay] = mode;
+                }
+            }
+        }
+        if reader.version() >= 6 {
+            settings.location = Location::load(reader)?;
+            settings.time_zone = TimeZone::load(reader)?;
+        }
+        if reader.version() >= 8 {
+            for i_relay in 0..stored_relays {
+                let entry = CycleOutEntry::load(reader)?;
+                if i_relay < NUM_RELAYS {
+                    settings.cycle_out[i_relay] = entry;
+                }
+            }
+        }
+        if reader.version() >= 9 {
+            for i_relay in 0..stored_relays {
+                let jitter: DurationMillis = reader.get_i32()?;
+                if jitter < 0 {
+                    return Err(StorageError::InvalidValue);
+                }
+                if i_relay < NUM_RELAYS {
+                    settings.vacation_jitter[i_relay] = jitter;
+                }
+            }
+        }
+        if reader.version() >= 10 {
+            for i_relay in 0..stored_relays {
+                let entry = ThermostatEntry::load(reader)?;
+                if i_relay < NUM_RELAYS {
+                    settings.thermostat[i_relay] = entry;
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl Persist for ScheduledOutEntry {
+    const STORED_SIZE: usize = 10;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(self.start_hour);
+        writer.put_u8(self.start_minute);
+        writer.put_i32(self.duration);
+        writer.put_u8(self.weekdays.bits());
+        self.start_mode.store(writer);
+        writer.put_i16(self.sun_offset);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let start_hour = reader.get_u8()?;
+        let start_minute = reader.get_u8()?;
+        let duration: DurationMillis = reader.get_i32()?;
+        let weekdays = if reader.version() >= 2 {
+            Weekdays::from(reader.get_u8()?)
+        } else {
+            Weekdays::full()
+        };
+        let (start_mode, sun_offset) = if reader.version() >= 6 {
+            (StartMode::load(reader)?, reader.get_i16()?)
+        } else {
+            (StartMode::Clock, 0)
+        };
+        if start_hour > 23
+            || start_minute > 59
+            || duration < 0
+            || weekdays.truncate() != weekdays
+            || sun_offset.abs() > ScheduledOutEntry::MAX_SUN_OFFSET
+        {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(ScheduledOutEntry {
+            start_hour,
+            start_minute,
+            duration,
+            weekdays,
+            start_mode,
+            sun_offset,
+        })
+    }
+}
+
+impl Persist for StartMode {
+    const STORED_SIZE: usize = 1;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(match self {
+            StartMode::Clock => 0,
+            StartMode::Sunrise => 1,
+            StartMode::Sunset => 2,
+        });
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        match reader.get_u8()? {
+            0 => Ok(StartMode::Clock),
+            1 => Ok(StartMode::Sunrise),
+            2 => Ok(StartMode::Sunset),
+            _ => Err(StorageError::InvalidValue),
+        }
+    }
+}
+
+impl Persist for Location {
+    const STORED_SIZE: usize = 4;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_i16(self.latitude);
+        writer.put_i16(self.longitude);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let location = Location {
+            latitude: reader.get_i16()?,
+            longitude: reader.get_i16()?,
+        };
+        if !location.is_valid() {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(location)
+    }
+}
+
+/// before version 7, only the difference to UTC was stored, without daylight saving time
+impl Persist for TimeZone {
+    const STORED_SIZE: usize = 3;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_i16(self.utc_offset);
+        writer.put_u8(match self.dst {
+            DstRule::None => 0,
+            DstRule::Eu => 1,
+            DstRule::Us => 2,
+        });
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let utc_offset = reader.get_i16()?;
+        let dst = if reader.version() >= 7 {
+            match reader.get_u8()? {
+                0 => DstRule::None,
+                1 => DstRule::Eu,
+                2 => DstRule::Us,
+                _ => return Err(StorageError::InvalidValue),
+            }
+        } else {
+            DstRule::None
+        };
+        let time_zone = TimeZone { utc_offset, dst };
+        if !time_zone.is_valid() {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(time_zone)
+    }
+}
+
+impl Persist for OverrideMode {
+    const STORED_SIZE: usize = 1;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(match self {
+            OverrideMode::Auto => 0,
+            OverrideMode::ForcedOn => 1,
+            OverrideMode::ForcedOff => 2,
+            OverrideMode::Vacation => 3,
+        });
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        match reader.get_u8()? {
+            0 => Ok(OverrideMode::Auto),
+            1 => Ok(OverrideMode::ForcedOn),
+            2 => Ok(OverrideMode::ForcedOff),
+            3 => Ok(OverrideMode::Vacation),
+            _ => Err(StorageError::InvalidValue),
+        }
+    }
+}
+
+impl Persist for DatedOutEntry {
+    const STORED_SIZE: usize = 10;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u16(self.year);
+        writer.put_u8(self.month);
+        writer.put_u8(self.day);
+        writer.put_u8(self.start_hour);
+        writer.put_u8(self.start_minute);
+        writer.put_i32(self.duration);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let year = reader.get_u16()?;
+        let month = reader.get_u8()?;
+        let day = reader.get_u8()?;
+        let start_hour = reader.get_u8()?;
+        let start_minute = reader.get_u8()?;
+        let duration: DurationMillis = reader.get_i32()?;
+        if !is_valid_date(year, month, day) || start_hour > 23 || start_minute > 59 || duration < 0
+        {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(DatedOutEntry {
+            year,
+            month,
+            day,
+            start_hour,
+            start_minute,
+            duration,
+        })
+    }
+}
+
+impl Persist for CycleOutEntry {
+    const STORED_SIZE: usize = 10 + <Option<TimeWindow>>::STORED_SIZE;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_i32(self.on_duration);
+        writer.put_i32(self.off_duration);
+        writer.put_u8(self.anchor_hour);
+        writer.put_u8(self.anchor_minute);
+        self.window.store(writer);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let on_duration: DurationMillis = reader.get_i32()?;
+        let off_duration: DurationMillis = reader.get_i32()?;
+        let anchor_hour = reader.get_u8()?;
+        let anchor_minute = reader.get_u8()?;
+        let window = <Option<TimeWindow>>::load(reader)?;
+        if on_duration < 0 || off_duration < 0 || anchor_hour > 23 || anchor_minute > 59 {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(CycleOutEntry {
+            on_duration,
+            off_duration,
+            anchor_hour,
+            anchor_minute,
+            window,
+        })
+    }
+}
+
+impl Persist for TimeWindow {
+    const STORED_SIZE: usize = 4;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(self.start_hour);
+        writer.put_u8(self.start_minute);
+        writer.put_u8(self.end_hour);
+        writer.put_u8(self.end_minute);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let window = TimeWindow {
+            start_hour: reader.get_u8()?,
+            start_minute: reader.get_u8()?,
+            end_hour: reader.get_u8()?,
+            end_minute: reader.get_u8()?,
+        };
+        if window.start_hour > 23
+            || window.start_minute > 59
+            || window.end_hour > 23
+            || window.end_minute > 59
+        {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(window)
+    }
+}
+
+impl Persist for ThermostatEntry {
+    const STORED_SIZE: usize = 5;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(match self.mode {
+            ThermostatMode::Off => 0,
+            ThermostatMode::Always => 1,
+            ThermostatMode::Scheduled => 2,
+        });
+        writer.put_i16(self.on_threshold);
+        writer.put_i16(self.off_threshold);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let mode = match reader.get_u8()? {
+            0 => ThermostatMode::Off,
+            1 => ThermostatMode::Always,
+            2 => ThermostatMode::Scheduled,
+            _ => return Err(StorageError::InvalidValue),
+        };
+        Ok(ThermostatEntry {
+            mode,
+            on_threshold: reader.get_i16()?,
+            off_threshold: reader.get_i16()?,
+        })
+    }
+}
+
+/// stored with the number of relays in front
+impl<const NUM_RELAYS: usize> Persist for RuntimeStats<NUM_RELAYS> {
+    const STORED_SIZE: usize = statistics_blob_size(NUM_RELAYS) - HEADER_SIZE - CHECKSUM_SIZE;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u8(NUM_RELAYS as u8);
+        writer.put_i32(self.day);
+        self.relays.store(writer);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let stored_relays = reader.get_u8()? as usize;
+        let mut statistics = RuntimeStats {
+            day: reader.get_i32()?,
+            ..Default::default()
+        };
+        for i_relay in 0..stored_relays {
+            let stats = RelayStats::load(reader)?;
+            if i_relay < NUM_RELAYS {
+                statistics.relays[i_relay] = stats;
+            }
+        }
+        Ok(statistics)
+    }
+}
+
+impl Persist for RelayStats {
+    const STORED_SIZE: usize = 20;
+
+    fn store(&self, writer: &mut StorageWriter) {
+        writer.put_u64(self.total_on);
+        writer.put_i32(self.today);
+        writer.put_i32(self.yesterday);
+        writer.put_u32(self.switch_count);
+    }
+
+    fn load(reader: &mut StorageReader) -> Result<Self, StorageError> {
+        let stats = RelayStats {
+            total_on: reader.get_u64()?,
+            today: reader.get_i32()?,
+            yesterday: reader.get_i32()?,
+            switch_count: reader.get_u32()?,
+        };
+        if stats.today < 0 || stats.yesterday < 0 {
+            return Err(StorageError::InvalidValue);
+        }
+        Ok(stats)
+    }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// encodes `payload` with header and checksum
+fn encode_blob(magic: [u8; 2], version: u8, payload: &impl Persist, size: usize) -> Vec<u8> {
+    let mut writer = StorageWriter::new(size);
+    writer.buffer.extend_from_slice(&magic);
+    writer.put_u8(version);
+    payload.store(&mut writer);
+    let crc = crc16(&writer.buffer);
+    writer.put_u16(crc);
+    writer.buffer
+}
+
+/// checks header and checksum and decodes the payload of a blob created by [`encode_blob`]
+fn decode_blob<T: Persist>(blob: &[u8], magic: [u8; 2], version: u8) -> Result<T, StorageError> {
+    if blob.len() < HEADER_SIZE + CHECKSUM_SIZE {
+        return Err(StorageError::TooShort);
+    }
+    if blob[..magic.len()] != magic {
+        return Err(StorageError::BadMagic);
+    }
+    let stored_version = blob[magic.len()];
+    if stored_version == 0 || stored_version > version {
+        return Err(StorageError::UnsupportedVersion);
+    }
+    let (content, checksum) = blob.split_at(blob.len() - CHECKSUM_SIZE);
+    if crc16(content) != StorageReader::new(checksum, stored_version).get_u16()? {
+        return Err(StorageError::BadChecksum);
+    }
+    let mut reader = StorageReader::new(&content[HEADER_SIZE..], stored_version);
+    let payload = T::load(&mut reader)?;
+    if !reader.data.is_empty() {
+        return Err(StorageError::TooLong);
+    }
+    Ok(payload)
+}
+
+/// encodes the settings as a blob of [`settings_blob_size`] bytes
+pub fn encode_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    settings: &Settings<NUM_RELAYS, NUM_SLOTS>,
+) -> Vec<u8> {
+    let size = settings_blob_size(NUM_RELAYS, NUM_SLOTS);
+    encode_blob(STORAGE_MAGIC, STORAGE_VERSION, settings, size)
+}
+
+/// decodes a blob created by [`encode_settings`]
+pub fn decode_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    blob: &[u8],
+) -> Result<Settings<NUM_RELAYS, NUM_SLOTS>, StorageError> {
+    decode_blob(blob, STORAGE_MAGIC, STORAGE_VERSION)
+}
+
+/// reads the settings from non-volatile memory
+///
+/// Falls back to the default settings if nothing has been stored yet
+/// or if the stored blob is corrupt.
+pub fn load_settings<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &impl AccessPersistentStorage,
+) -> Settings<NUM_RELAYS, NUM_SLOTS> {
+    // leave room for blobs written with any other configuration
+    let size = usize::max(
+        settings_blob_size(NUM_RELAYS, NUM_SLOTS),
+        settings_blob_size(MAX_STORED_CONFIG, MAX_STORED_CONFIG),
+    );
+    let mut blob = vec![0u8; size];
+    let len = sys.load_settings(&mut blob);
+    decode_settings(&blob[..len.min(size)]).unwrap_or_default()
+}
+
+/// the total size of a statistics blob encoded with the current version in bytes
+pub const fn statistics_blob_size(num_relays: usize) -> usize {
+    HEADER_SIZE + 1 + 4 + num_relays * RelayStats::STORED_SIZE + CHECKSUM_SIZE
+}
+
+/// encodes the statistics as a blob of [`statistics_blob_size`] bytes
+pub fn encode_statistics<const NUM_RELAYS: usize>(
+    statistics: &RuntimeStats<NUM_RELAYS>,
+) -> Vec<u8> {
+    let size = statistics_blob_size(NUM_RELAYS);
+    encode_blob(STATISTICS_MAGIC, STATISTICS_VERSION, statistics, size)
+}
+
+/// decodes a blob created by [`encode_statistics`]
+pub fn decode_statistics<const NUM_RELAYS: usize>(
+    blob: &[u8],
+) -> Result<RuntimeStats<NUM_RELAYS>, StorageError> {
+    decode_blob(blob, STATISTICS_MAGIC, STATISTICS_VERSION)
+}
+
+/// reads the statistics from non-volatile memory
+///
+/// Starts from zero if nothing has been stored yet or if the stored blob is corrupt.
+pub fn load_statistics<const NUM_RELAYS: usize>(
+    sys: &impl AccessPersistentStorage,
+) -> RuntimeStats<NUM_RELAYS> {
+    // leave room for blobs written with any other configuration
+    let size = usize::max(
+        statistics_blob_size(NUM_RELAYS),
+        statistics_blob_size(MAX_STORED_CONFIG),
+    );
+    let mut blob = vec![0u8; size];
+    let len = sys.load_statistics(&mut blob);
+    decode_statistics(&blob[..len.min(size)]).unwrap_or_default()
+}
@@ -1,18 +1,32 @@
 //! Module with hardware related functionality
 
+use crate::calendar;
 use bitmask_enum::bitmask;
+use core::future::poll_fn;
+use core::task::Poll;
 use greaheisl_async::DurationMillis;
 use greaheisl_async::Scheduler;
 use greaheisl_async::{
-    sleep_at_most, yield_now, AccessExecutorSignals, AccessTiming, ReceiveBool, Stopped,
+    with_timeout, AccessExecutorSignals, AccessTiming, ReceiveBool, SignalSet, Stopped,
 };
 use greaheisl_button_processor::{AccessButtonSignal, AccessButtonState};
 
 pub mod buttons;
 
-/// time of day in human readable notation, with second resolution
+/// date and time of day in human readable notation, with second resolution
+///
+/// The real time clock runs in UTC, see [`crate::timezone`] for the conversion to local time.
 #[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RtcTime {
+    /// year including the century, e.g. 2024
+    pub year: u16,
+    /// valid numbers restricted to 1..12
+    pub month: u8,
+    /// day of the month; valid numbers restricted to 1..31
+    pub day: u8,
+    /// day of the week; valid numbers restricted to 0 (Monday) .. 6 (Sunday)
+    pub weekday: u8,
     /// valid numbers restricted to 0..23
     pub hour: u8,
     /// valid numbers restricted to 0..59
@@ -21,7 +35,39 @@ pub struct RtcTime {
     pub second: u8,
 }
 
-/// time of day from the real time clock
+impl RtcTime {
+    /// the inverse of [`Self::minutes_since_epoch`], with the given second
+    pub fn from_minutes_since_epoch(minutes: i32, second: u8) -> Self {
+        let days = minutes.div_euclid(24 * 60);
+        let minute_of_day = minutes.rem_euclid(24 * 60);
+        let (year, month, day) = calendar::civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            weekday: calendar::weekday_from_days(days),
+            hour: (minute_of_day / 60) as u8,
+            minute: (minute_of_day % 60) as u8,
+            second,
+        }
+    }
+    /// number of days since 1970-01-01
+    pub fn days_since_epoch(&self) -> i32 {
+        calendar::days_from_civil(self.year, self.month, self.day)
+    }
+    /// number of minutes since 1970-01-01 00:00
+    pub fn minutes_since_epoch(&self) -> i32 {
+        calendar::minutes_from_civil(self.year, self.month, self.day, self.hour, self.minute)
+    }
+    /// milliseconds elapsed since the given minute, counted like [`Self::minutes_since_epoch`]
+    ///
+    /// The result is negative if the minute lies in the future.
+    pub fn millis_since_minute(&self, minutes: i32) -> i64 {
+        (self.minutes_since_epoch() as i64 - minutes as i64) * 60_000 + self.second as i64 * 1000
+    }
+}
+
+/// date and time of day from the real time clock, in UTC
 pub trait AccessRtc {
     fn get_rtc(&self) -> RtcTime;
     fn set_rtc(&self, time: &RtcTime);
@@ -32,39 +78,113 @@ pub trait AccessLedMatrix {
     fn set_led_matrix(&self, matrix: &[u32; 3]);
 }
 
-/// number of output relays
-pub const NUM_RELAYS: usize = 4;
+/// number of output relays of the Arduino relay shield
+pub const DEFAULT_NUM_RELAYS: usize = 4;
 
 /// to switch on the device connected to the relay,
 /// set the corresponding relay state to true
-pub trait AccessOutputStates {
+///
+/// `NUM_RELAYS` is the number of relays the settings have room for.
+/// The number of relays actually connected may be smaller.
+pub trait AccessOutputStates<const NUM_RELAYS: usize> {
+    /// number of relays actually connected, at most `NUM_RELAYS`
+    ///
+    /// Only these relays are offered in the menus.
+    fn relay_count(&self) -> usize {
+        NUM_RELAYS
+    }
     fn set_relay_states(&self, relais_states: &[bool; NUM_RELAYS]);
 }
 
+/// non-volatile memory for the settings and the statistics, e.g., EEPROM or flash
+pub trait AccessPersistentStorage {
+    /// copies the stored settings blob into `buffer`
+    ///
+    /// Returns the number of bytes copied, which may be
+    /// zero if nothing has been stored yet.
+    fn load_settings(&self, buffer: &mut [u8]) -> usize;
+    /// overwrites the stored settings blob with `data`
+    fn store_settings(&self, data: &[u8]);
+    /// copies the stored statistics blob into `buffer`, see [`crate::statistics`]
+    ///
+    /// Returns the number of bytes copied. By default, there is no room
+    /// for the statistics, and they start from zero after every restart.
+    fn load_statistics(&self, _buffer: &mut [u8]) -> usize {
+        0
+    }
+    /// overwrites the stored statistics blob with `data`
+    fn store_statistics(&self, _data: &[u8]) {}
+}
+
+/// digital inputs, e.g., float switches or motion sensors, see [`crate::inputs`]
+pub trait AccessDigitalInputs {
+    /// the raw levels of the inputs, bit `k` set if input `k` is active
+    ///
+    /// Debouncing is done by the library.
+    fn get_input_flags(&self) -> u8;
+}
+
+/// an analog sensor, e.g., for temperature or humidity, see [`crate::ThermostatEntry`]
+pub trait AccessAnalogInput {
+    /// whether a sensor is connected at all
+    fn has_analog_input(&self) -> bool {
+        true
+    }
+    /// the current reading in tenths of the unit of the sensor, e.g., 0.1 °C,
+    /// or `None` if the sensor cannot be read
+    fn get_analog_value(&self) -> Option<i16>;
+}
+
+/// a byte stream to a host computer, e.g., over a serial port, see [`crate::commands`]
+///
+/// By default, nothing is ever received.
+pub trait AccessSerial {
+    /// the next byte received, or `None` if no byte is waiting
+    fn read_serial(&self) -> Option<u8> {
+        None
+    }
+    /// sends `data`
+    fn write_serial(&self, _data: &[u8]) {}
+}
+
 /// All the functionality provided by means of callbacks
 ///
 /// Note that you do not need to implement the trait `Callbacks` explicitly,
 /// thanks to the blanket implementation.
-pub trait Callbacks:
+pub trait Callbacks<const NUM_RELAYS: usize>:
     AccessButtonState<ButtonFlags = buttons::ButtonFlags>
     + AccessRtc
     + AccessLedMatrix
-    + AccessOutputStates
+    + AccessOutputStates<NUM_RELAYS>
+    + AccessPersistentStorage
+    + AccessDigitalInputs
+    + AccessAnalogInput
+    + AccessSerial
 {
 }
-impl<T> Callbacks for T where
+impl<T, const NUM_RELAYS: usize> Callbacks<NUM_RELAYS> for T where
     T: AccessButtonState<ButtonFlags = buttons::ButtonFlags>
         + AccessRtc
         + AccessLedMatrix
-        + AccessOutputStates
+        + AccessOutputStates<NUM_RELAYS>
+        + AccessPersistentStorage
+        + AccessDigitalInputs
+        + AccessAnalogInput
+        + AccessSerial
 {
 }
 
 /// All the system functionality united in one trait
-pub trait System: Callbacks + Scheduler<SignalFlags> + AccessButtonSignal {}
-impl<T> System for T where T: Scheduler<SignalFlags> + Callbacks + AccessButtonSignal {}
+pub trait System<const NUM_RELAYS: usize>:
+    Callbacks<NUM_RELAYS> + Scheduler<SignalFlags> + AccessButtonSignal
+{
+}
+impl<T, const NUM_RELAYS: usize> System<NUM_RELAYS> for T where
+    T: Scheduler<SignalFlags> + Callbacks<NUM_RELAYS> + AccessButtonSignal
+{
+}
 
-/// Right now we only support one kind of event
+/// the kinds of events passed to the executor
 ///
 /// Note that we allow spurious events.
 /// For example, it is not a problem if the `Button` flag is set even though
@@ -76,6 +196,10 @@ impl<T> System for T where T: Scheduler<SignalFlags> + Callbacks + AccessButtonS
 pub enum SignalFlags {
     /// set this flag if the state of any of the buttons changes
     Button,
+    /// set this flag if the level of any of the digital inputs changes
+    Input,
+    /// set this flag if bytes have been received through [`AccessSerial`]
+    Serial,
 }
 
 impl Default for SignalFlags {
@@ -84,21 +208,39 @@ impl Default for SignalFlags {
     }
 }
 
+impl SignalSet for SignalFlags {
+    fn union(self, other: Self) -> Self {
+        self | other
+    }
+    fn intersects(self, other: Self) -> bool {
+        self & other != 0
+    }
+}
+
 /// Waits until one of the set bits in `event_signal`
 /// matches with one of the set bits in the singal flags.
 /// Returns the matching signal flags.
+///
+/// Within [`greaheisl_async::join2`] or the like, the waiting branch is not polled
+/// in the steps without any of these signals,
+/// see [`AccessExecutorSignals::set_signal_wake_request`].
 pub async fn wait_event(
     sys: &impl AccessExecutorSignals<SignalFlags>,
     event_signal: SignalFlags,
 ) -> SignalFlags {
-    loop {
-        yield_now().await;
-        let sig = sys.get_executor_signals();
-        let inters = sig & event_signal;
-        if inters != 0 {
-            return inters;
+    let mut requested = false;
+    poll_fn(|cx| {
+        if requested {
+            let inters = sys.get_executor_signals() & event_signal;
+            if inters != 0 {
+                return Poll::Ready(inters);
+            }
         }
-    }
+        requested = true;
+        sys.set_signal_wake_request(event_signal, cx.waker());
+        Poll::Pending
+    })
+    .await
 }
 
 /// Waits until one of the set bits in `event_signal`
@@ -107,20 +249,32 @@ pub async fn wait_event(
 /// signal flags.
 /// Also stops waiting if the stop signal becomes true.
 /// In that case the function returns `Err(Stopped)`.
+///
+/// Like [`wait_event`], the waiting branch is only polled in the steps with
+/// any of these signals, unless it is woken otherwise. So the stop signal must be
+/// set by something which wakes it, e.g. another branch of [`greaheisl_async::join2`]
+/// as it completes.
 pub async fn wait_stop_or_event(
     sys: &impl AccessExecutorSignals<SignalFlags>,
     stop_signal: &impl ReceiveBool,
     event_signal: SignalFlags,
 ) -> Result<SignalFlags, Stopped> {
-    while !stop_signal.get() {
-        yield_now().await;
-        let sig = sys.get_executor_signals();
-        let inters = sig & event_signal;
-        if inters != 0 {
-            return Ok(inters);
+    let mut requested = false;
+    poll_fn(|cx| {
+        if requested {
+            let inters = sys.get_executor_signals() & event_signal;
+            if inters != 0 {
+                return Poll::Ready(Ok(inters));
+            }
         }
-    }
-    Err(Stopped)
+        if stop_signal.get() {
+            return Poll::Ready(Err(Stopped));
+        }
+        requested = true;
+        sys.set_signal_wake_request(event_signal, cx.waker());
+        Poll::Pending
+    })
+    .await
 }
 
 /// Waits until one of the set bits in `event_signal`
@@ -137,20 +291,11 @@ pub async fn wait_stop_or_event_timeout(
     event_signal: SignalFlags,
     timeout: DurationMillis,
 ) -> Result<SignalFlags, Stopped> {
-    let start_time = sys.get_instant();
-    let mut time_left = timeout;
-    while !stop_signal.get() {
-        sleep_at_most(sys, time_left).await;
-        let sig = sys.get_executor_signals();
-        let inters = sig & event_signal;
-        if inters != 0 {
-            return Ok(inters);
-        }
-        let time_passed = sys.get_instant() - start_time;
-        time_left = timeout - time_passed;
-        if time_left <= 0 {
-            return Ok(SignalFlags::none());
-        }
-    }
-    Err(Stopped)
+    with_timeout(
+        sys,
+        timeout,
+        wait_stop_or_event(sys, stop_signal, event_signal),
+    )
+    .await
+    .unwrap_or(Ok(SignalFlags::none()))
 }
@@ -37,7 +37,7 @@ mod timing {
             },
             async {
                 let mut counter = 0;
-                while stop_signal.get() != true {
+                while !stop_signal.get() {
                     println!("Fast counting: {}", counter);
                     counter += 1;
                     Timer::new(&scheduler, 230).wait().await;
@@ -76,6 +76,8 @@ mod timing {
     struct ButtonTestSys {
         scheduler: Rc<RefCell<MiniScheduler<SignalFlags>>>,
         buttons: Rc<Cell<ButtonFlags>>,
+        /// number of calls to `is_button_signal`
+        button_checks: Rc<Cell<usize>>,
     }
 
     impl AccessButtonState for ButtonTestSys {
@@ -87,8 +89,12 @@ mod timing {
 
     impl AccessButtonSignal for ButtonTestSys {
         fn is_button_signal(&self) -> bool {
+            self.button_checks.set(self.button_checks.get() + 1);
             self.get_executor_signals().contains(SignalFlags::Button)
         }
+        fn set_button_wake_request(&self, waker: &core::task::Waker) {
+            self.set_signal_wake_request(SignalFlags::Button, waker);
+        }
     }
 
     /* This does not work due to lifetime issues
@@ -156,6 +162,7 @@ mod timing {
         let sys = ButtonTestSys {
             scheduler: executor.scheduler().clone(),
             buttons: buttons.clone(),
+            button_checks: Rc::default(),
         };
         let task = button_test_task_main(sys);
         let mut executor = executor.build(task);
@@ -174,4 +181,1717 @@ mod timing {
             fake_time += timestep;
         }
     }
+
+    #[test]
+    fn button_waiter_is_only_polled_with_button_signal() {
+        let mut fake_time = InstantMillis::from_absolute(0);
+        let executor = MiniExecutor::new(fake_time);
+        let button_checks = Rc::new(Cell::new(0));
+        let sys = ButtonTestSys {
+            scheduler: executor.scheduler().clone(),
+            buttons: Rc::new(Cell::new(ButtonFlags::none())),
+            button_checks: button_checks.clone(),
+        };
+        let task = button_test_task_main(sys);
+        let mut executor = executor.build(task);
+        // the button processor starts waiting for button activity
+        executor.step(fake_time, SignalFlags::none());
+        let checks = button_checks.get();
+        for signal_flags in [SignalFlags::none(), SignalFlags::Input, SignalFlags::Serial] {
+            fake_time += 10;
+            executor.step(fake_time, signal_flags);
+        }
+        assert_eq!(button_checks.get(), checks);
+        fake_time += 10;
+        executor.step(fake_time, SignalFlags::Button | SignalFlags::Input);
+        assert!(button_checks.get() > checks);
+    }
+}
+
+mod storage {
+    use crate::solar::Location;
+    use crate::statistics::RuntimeStats;
+    use crate::storage::{
+        crc16, decode_settings, encode_settings, encode_statistics, load_settings, load_statistics,
+        settings_blob_size, StorageError,
+    };
+    use crate::system::AccessPersistentStorage;
+    use crate::timezone::{DstRule, TimeZone};
+    use crate::{
+        CycleOutEntry, DatedOutEntry, OverrideMode, ScheduledOutEntry, Settings, StartMode,
+        ThermostatEntry, ThermostatMode, TimeWindow, Weekdays, DEFAULT_NUM_RELAYS,
+        DEFAULT_NUM_SLOTS,
+    };
+
+    type DefaultSettings = Settings<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>;
+    const SETTINGS_BLOB_SIZE: usize = settings_blob_size(DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS);
+
+    fn example_settings() -> DefaultSettings {
+        let mut settings = DefaultSettings::default();
+        settings.scheduled_out[1][2] = ScheduledOutEntry {
+            start_hour: 7,
+            start_minute: 30,
+            duration: 1000 * 60 * 45,
+            weekdays: Weekdays::workdays(),
+            ..Default::default()
+        };
+        settings.scheduled_out[3][0] = ScheduledOutEntry {
+            start_hour: 23,
+            start_minute: 59,
+            duration: 1000 * 60 * 60 * 24,
+            weekdays: Weekdays::Sunday,
+            ..Default::default()
+        };
+        settings.scheduled_out[0][1] = ScheduledOutEntry {
+            duration: 1000 * 60 * 60 * 4,
+            start_mode: StartMode::Sunset,
+            sun_offset: -30,
+            ..Default::default()
+        };
+        settings.dated_out[2] = Some(DatedOutEntry {
+            year: 2024,
+            month: 2,
+            day: 29,
+            start_hour: 18,
+            start_minute: 5,
+            duration: 1000 * 60 * 90,
+        });
+        settings.cycle_out[1] = CycleOutEntry {
+            on_duration: 1000 * 60 * 10,
+            off_duration: 1000 * 60 * 110,
+            anchor_hour: 8,
+            anchor_minute: 0,
+            window: Some(TimeWindow {
+                start_hour: 8,
+                start_minute: 0,
+                end_hour: 20,
+                end_minute: 0,
+            }),
+        };
+        settings.thermostat[3] = ThermostatEntry {
+            mode: ThermostatMode::Scheduled,
+            on_threshold: -25,
+            off_threshold: 40,
+        };
+        settings.override_mode[0] = OverrideMode::ForcedOff;
+        settings.override_mode[2] = OverrideMode::Vacation;
+        settings.override_mode[3] = OverrideMode::ForcedOn;
+        settings.vacation_jitter[2] = 1000 * 60 * 60;
+        settings.location = Location {
+            latitude: 5252,
+            longitude: 1340,
+        };
+        settings.time_zone = TimeZone {
+            utc_offset: -5 * 60,
+            dst: DstRule::Us,
+        };
+        settings
+    }
+
+    #[test]
+    fn roundtrip() {
+        let settings = example_settings();
+        let blob = encode_settings(&settings);
+        assert_eq!(blob.len(), SETTINGS_BLOB_SIZE);
+        let decoded: DefaultSettings = decode_settings(&blob).unwrap();
+        assert_eq!(encode_settings(&decoded), blob);
+        let entry = &decoded.scheduled_out[1][2];
+        assert_eq!(
+            (entry.start_hour, entry.start_minute, entry.duration),
+            (7, 30, 1000 * 60 * 45)
+        );
+        assert_eq!(entry.weekdays, Weekdays::workdays());
+        let entry = &decoded.scheduled_out[0][1];
+        assert_eq!(
+            (entry.start_mode, entry.sun_offset),
+            (StartMode::Sunset, -30)
+        );
+        assert_eq!(decoded.location, example_settings().location);
+        assert_eq!(decoded.time_zone, example_settings().time_zone);
+        let entry = &decoded.cycle_out[1];
+        assert_eq!(
+            (entry.on_duration, entry.off_duration),
+            (1000 * 60 * 10, 1000 * 60 * 110)
+        );
+        assert_eq!(entry.window, example_settings().cycle_out[1].window);
+        assert_eq!(decoded.cycle_out[0].on_duration, 0);
+        let entry = decoded.dated_out[2].as_ref().unwrap();
+        assert_eq!((entry.year, entry.month, entry.day), (2024, 2, 29));
+        assert!(decoded.dated_out[0].is_none());
+        assert_eq!(
+            decoded.override_mode,
+            [
+                OverrideMode::ForcedOff,
+                OverrideMode::Auto,
+                OverrideMode::Vacation,
+                OverrideMode::ForcedOn
+            ]
+        );
+        assert_eq!(decoded.vacation_jitter[2], 1000 * 60 * 60);
+        let entry = &decoded.thermostat[3];
+        assert_eq!(
+            (entry.mode, entry.on_threshold, entry.off_threshold),
+            (ThermostatMode::Scheduled, -25, 40)
+        );
+        assert_eq!(decoded.thermostat[0].mode, ThermostatMode::Off);
+        assert_eq!(decoded.vacation_jitter[0], crate::DEFAULT_VACATION_JITTER);
+    }
+
+    #[test]
+    fn version_1_blob_is_migrated() {
+        // version 1 had no weekdays; every entry was active daily
+        let mut blob = vec![b'G', b'H', 1];
+        for i in 0..DEFAULT_NUM_RELAYS * DEFAULT_NUM_SLOTS {
+            blob.extend_from_slice(&[i as u8, 15]);
+            blob.extend_from_slice(&(1000 * 60 * i as i32).to_le_bytes());
+        }
+        let crc = crc16(&blob);
+        blob.extend_from_slice(&crc.to_le_bytes());
+        let settings: DefaultSettings = decode_settings(&blob).unwrap();
+        let entry = &settings.scheduled_out[1][2];
+        assert_eq!((entry.start_hour, entry.start_minute), (5, 15));
+        assert_eq!(entry.duration, 1000 * 60 * 5);
+        assert!(entry.weekdays.is_full());
+    }
+
+    #[test]
+    fn other_configurations_are_adapted() {
+        let blob = encode_settings(&example_settings());
+        let smaller: Settings<2, 2> = decode_settings(&blob).unwrap();
+        assert_eq!(smaller.scheduled_out[1][1].duration, 0);
+        assert!(smaller.dated_out.iter().all(Option::is_none));
+        let larger: Settings<8, 5> = decode_settings(&blob).unwrap();
+        assert_eq!(larger.scheduled_out[1][2].duration, 1000 * 60 * 45);
+        assert_eq!(larger.scheduled_out[3][0].weekdays, Weekdays::Sunday);
+        assert!(larger.dated_out[2].is_some());
+        assert!(larger.dated_out[4].is_none());
+        let blob = encode_settings(&larger);
+        assert_eq!(blob.len(), settings_blob_size(8, 5));
+        let decoded: DefaultSettings = decode_settings(&blob).unwrap();
+        assert_eq!(
+            encode_settings(&decoded),
+            encode_settings(&example_settings())
+        );
+    }
+
+    #[test]
+    fn corrupt_blobs_are_rejected() {
+        let blob = encode_settings(&example_settings());
+        let decode = decode_settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>;
+        assert_eq!(decode(&[]).err(), Some(StorageError::TooShort));
+        assert!(decode(&blob[..blob.len() - 1]).is_err());
+        let mut bad = blob.clone();
+        bad[0] = b'X';
+        assert_eq!(decode(&bad).err(), Some(StorageError::BadMagic));
+        let mut bad = blob.clone();
+        bad[2] += 1;
+        assert_eq!(decode(&bad).err(), Some(StorageError::UnsupportedVersion));
+        let mut bad = blob.clone();
+        bad[10] ^= 0x01;
+        assert_eq!(decode(&bad).err(), Some(StorageError::BadChecksum));
+        // erased flash memory
+        let erased = [0xFFu8; SETTINGS_BLOB_SIZE];
+        assert!(decode(&erased).is_err());
+    }
+
+    /// non-volatile memory which fills the buffer as far as it goes, like the C callbacks
+    struct Flash {
+        settings: Vec<u8>,
+        statistics: Vec<u8>,
+    }
+
+    impl AccessPersistentStorage for Flash {
+        fn load_settings(&self, buffer: &mut [u8]) -> usize {
+            let len = self.settings.len().min(buffer.len());
+            buffer[..len].copy_from_slice(&self.settings[..len]);
+            len
+        }
+        fn store_settings(&self, _data: &[u8]) {}
+        fn load_statistics(&self, buffer: &mut [u8]) -> usize {
+            let len = self.statistics.len().min(buffer.len());
+            buffer[..len].copy_from_slice(&self.statistics[..len]);
+            len
+        }
+    }
+
+    #[test]
+    fn blobs_of_larger_configurations_are_loaded() {
+        let mut settings = Settings::<8, 3>::default();
+        settings.scheduled_out[1][2].duration = 1000 * 60 * 45;
+        settings.thermostat[7].mode = ThermostatMode::Always;
+        let mut statistics = RuntimeStats::<8>::default();
+        statistics.relays[1].switch_count = 5;
+        let flash = Flash {
+            settings: encode_settings(&settings),
+            statistics: encode_statistics(&statistics),
+        };
+        let settings: Settings<2, 3> = load_settings(&flash);
+        assert_eq!(settings.scheduled_out[1][2].duration, 1000 * 60 * 45);
+        let statistics: RuntimeStats<2> = load_statistics(&flash);
+        assert_eq!(statistics.relays[1].switch_count, 5);
+    }
+}
+
+mod calendar {
+    use crate::calendar::{civil_from_days, days_from_civil, is_valid_date, weekday_from_days};
+
+    #[test]
+    fn known_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        // 2024-02-29 was a Thursday
+        assert_eq!(weekday_from_days(days_from_civil(2024, 2, 29)), 3);
+        assert!(is_valid_date(2000, 2, 29));
+        assert!(!is_valid_date(2100, 2, 29));
+        assert!(!is_valid_date(2023, 4, 31));
+    }
+
+    #[test]
+    fn roundtrip() {
+        for days in days_from_civil(1999, 12, 1)..days_from_civil(2101, 1, 31) {
+            let (year, month, day) = civil_from_days(days);
+            assert!(is_valid_date(year, month, day));
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
+
+mod schedule {
+    use crate::calendar::{days_from_civil, weekday_from_days};
+    use crate::solar::{Location, SunTable};
+    use crate::system::RtcTime;
+    use crate::timezone::TimeZone;
+    use crate::{
+        CycleOutEntry, DatedOutEntry, OverrideMode, ScheduledOutEntry, StartMode, TimeWindow,
+        Weekdays, WindowShift,
+    };
+
+    /// local time is the time of the real time clock
+    const UTC: &TimeZone = &TimeZone::UTC;
+
+    fn rtc(day: u8, hour: u8, minute: u8, second: u8) -> RtcTime {
+        RtcTime {
+            year: 2024,
+            month: 3,
+            day,
+            weekday: weekday_from_days(days_from_civil(2024, 3, day)),
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    fn sun_table(day: u8) -> SunTable {
+        SunTable::new(
+            Location::default(),
+            TimeZone::UTC,
+            days_from_civil(2024, 3, day),
+        )
+    }
+
+    #[test]
+    fn missed_start_is_caught_up() {
+        let entry = ScheduledOutEntry {
+            start_hour: 7,
+            start_minute: 0,
+            duration: 1000 * 60 * 60,
+            weekdays: Weekdays::full(),
+            ..Default::default()
+        };
+        assert!(!entry.is_active(&rtc(4, 6, 59, 59), UTC, &sun_table(4)));
+        assert!(entry.is_active(&rtc(4, 7, 0, 0), UTC, &sun_table(4)));
+        assert!(entry.is_active(&rtc(4, 7, 5, 0), UTC, &sun_table(4)));
+        assert!(entry.is_active(&rtc(4, 7, 59, 59), UTC, &sun_table(4)));
+        assert!(!entry.is_active(&rtc(4, 8, 0, 0), UTC, &sun_table(4)));
+    }
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        // 2024-03-04 is a Monday
+        let entry = ScheduledOutEntry {
+            start_hour: 23,
+            start_minute: 30,
+            duration: 1000 * 60 * 60,
+            weekdays: Weekdays::Monday,
+            ..Default::default()
+        };
+        assert!(entry.is_active(&rtc(4, 23, 45, 0), UTC, &sun_table(4)));
+        assert!(entry.is_active(&rtc(5, 0, 15, 0), UTC, &sun_table(5)));
+        assert!(!entry.is_active(&rtc(5, 0, 30, 0), UTC, &sun_table(5)));
+        // the window belongs to the day it starts on
+        assert!(!entry.is_active(&rtc(4, 0, 15, 0), UTC, &sun_table(4)));
+        assert!(!entry.is_active(&rtc(5, 23, 45, 0), UTC, &sun_table(5)));
+        let whole_day = ScheduledOutEntry {
+            duration: 1000 * 60 * 60 * 24,
+            ..entry
+        };
+        assert!(whole_day.is_active(&rtc(5, 23, 29, 59), UTC, &sun_table(5)));
+        assert!(!whole_day.is_active(&rtc(5, 23, 30, 0), UTC, &sun_table(5)));
+        assert_eq!(
+            whole_day.time_left(&rtc(5, 23, 29, 59), UTC, &sun_table(5)),
+            Some(1000)
+        );
+    }
+
+    #[test]
+    fn sunset_entry_follows_the_sun() {
+        let entry = ScheduledOutEntry {
+            duration: 1000 * 60 * 60,
+            start_mode: StartMode::Sunset,
+            sun_offset: -30,
+            ..Default::default()
+        };
+        let sunset = sun_table(4)
+            .get(days_from_civil(2024, 3, 4))
+            .sunset
+            .unwrap();
+        let start = sunset - 30;
+        let (hour, minute) = ((start / 60) as u8, (start % 60) as u8);
+        assert!(!entry.is_active(&rtc(4, hour, minute - 1, 59), UTC, &sun_table(4)));
+        assert!(entry.is_active(&rtc(4, hour, minute, 0), UTC, &sun_table(4)));
+        assert_eq!(
+            entry.time_left(&rtc(4, hour, minute, 0), UTC, &sun_table(4)),
+            Some(1000 * 60 * 60)
+        );
+        // the day after, the sun sets a little later
+        let next_sunset = sun_table(5)
+            .get(days_from_civil(2024, 3, 5))
+            .sunset
+            .unwrap();
+        assert!(next_sunset > sunset);
+    }
+
+    #[test]
+    fn cycle_entry_repeats_from_anchor() {
+        let entry = CycleOutEntry {
+            on_duration: 1000 * 60 * 5,
+            off_duration: 1000 * 60 * 25,
+            anchor_hour: 0,
+            anchor_minute: 10,
+            window: None,
+        };
+        assert_eq!(
+            entry.state(&rtc(4, 0, 10, 0), UTC),
+            Some((true, 1000 * 60 * 5))
+        );
+        assert_eq!(
+            entry.state(&rtc(4, 0, 14, 30), UTC),
+            Some((true, 1000 * 30))
+        );
+        assert_eq!(
+            entry.state(&rtc(4, 0, 15, 0), UTC),
+            Some((false, 1000 * 60 * 25))
+        );
+        assert_eq!(
+            entry.state(&rtc(4, 12, 40, 0), UTC),
+            Some((true, 1000 * 60 * 5))
+        );
+        // before today's anchor, the cycles started yesterday are still running
+        assert_eq!(
+            entry.state(&rtc(5, 0, 5, 0), UTC),
+            Some((false, 1000 * 60 * 5))
+        );
+        let inactive = CycleOutEntry::default();
+        assert_eq!(inactive.state(&rtc(4, 0, 10, 0), UTC), None);
+    }
+
+    #[test]
+    fn cycle_entry_within_window() {
+        let entry = CycleOutEntry {
+            on_duration: 1000 * 60 * 10,
+            off_duration: 1000 * 60 * 110,
+            anchor_hour: 0,
+            anchor_minute: 0,
+            window: Some(TimeWindow {
+                start_hour: 8,
+                start_minute: 0,
+                end_hour: 20,
+                end_minute: 0,
+            }),
+        };
+        let is_on = |hour, minute| entry.state(&rtc(4, hour, minute, 0), UTC).unwrap().0;
+        assert!(!is_on(6, 0));
+        assert!(is_on(8, 0));
+        assert!(!is_on(8, 10));
+        assert!(is_on(18, 5));
+        assert!(!is_on(20, 0));
+        // a window extending past midnight
+        let night = TimeWindow {
+            start_hour: 22,
+            start_minute: 0,
+            end_hour: 6,
+            end_minute: 0,
+        };
+        assert!(night.contains(23, 0) && night.contains(5, 59));
+        assert!(!night.contains(6, 0) && !night.contains(21, 59));
+    }
+
+    #[test]
+    fn override_takes_precedence() {
+        for timer_state in [false, true] {
+            assert_eq!(OverrideMode::Auto.apply(timer_state), timer_state);
+            assert_eq!(OverrideMode::Vacation.apply(timer_state), timer_state);
+            assert!(OverrideMode::ForcedOn.apply(timer_state));
+            assert!(!OverrideMode::ForcedOff.apply(timer_state));
+        }
+    }
+
+    #[test]
+    fn vacation_shift_moves_the_window() {
+        let entry = ScheduledOutEntry {
+            start_hour: 19,
+            start_minute: 0,
+            duration: 1000 * 60 * 60 * 2,
+            ..Default::default()
+        };
+        let shift = |_| WindowShift {
+            start: -20,
+            duration: 10,
+        };
+        let time_left = |time: &RtcTime| entry.time_left_shifted(time, UTC, &sun_table(4), shift);
+        assert_eq!(time_left(&rtc(4, 18, 39, 59)), None);
+        assert_eq!(time_left(&rtc(4, 18, 40, 0)), Some(1000 * 60 * 130));
+        assert_eq!(time_left(&rtc(4, 20, 49, 0)), Some(1000 * 60));
+        assert_eq!(time_left(&rtc(4, 20, 50, 0)), None);
+    }
+
+    #[test]
+    fn vacation_shifts_are_bounded_and_drawn_daily() {
+        let bound = 1000 * 60 * 30;
+        let day = days_from_civil(2024, 3, 4);
+        let shifts: Vec<_> = (0..50)
+            .map(|i| WindowShift::draw(42, day + i, 1, 0, bound))
+            .collect();
+        for shift in &shifts {
+            assert!(shift.start.abs() <= 30 && shift.duration.abs() <= 30);
+        }
+        assert!(shifts.iter().any(|shift| *shift != shifts[0]));
+        // the same arguments always give the same shift
+        assert_eq!(WindowShift::draw(42, day, 1, 0, bound), shifts[0]);
+        assert_eq!(WindowShift::draw(42, day, 1, 0, 0), WindowShift::default());
+    }
+
+    #[test]
+    fn dated_entry_window() {
+        let entry = DatedOutEntry {
+            year: 2024,
+            month: 3,
+            day: 4,
+            start_hour: 23,
+            start_minute: 50,
+            duration: 1000 * 60 * 20,
+        };
+        assert!(!entry.is_active(&rtc(4, 23, 49, 59), UTC));
+        assert_eq!(entry.time_left(&rtc(5, 0, 5, 0), UTC), Some(1000 * 60 * 5));
+        assert!(!entry.has_ended(&rtc(5, 0, 9, 59), UTC));
+        assert!(entry.has_ended(&rtc(5, 0, 10, 0), UTC));
+        assert!(!entry.is_active(&rtc(5, 0, 10, 0), UTC));
+    }
+}
+
+mod random {
+    use crate::random::{seed_from, Rng};
+    use crate::system::RtcTime;
+    use greaheisl_async::InstantMillis;
+
+    #[test]
+    fn sequence_is_reproducible() {
+        // reference values of SplitMix64 seeded with 0
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        let draws: Vec<_> = (0..100).map(|_| Rng::new(7).symmetric(5)).collect();
+        assert!(draws.iter().all(|&x| x == draws[0]));
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| (-5..=5).contains(&rng.symmetric(5))));
+    }
+
+    #[test]
+    fn seed_depends_on_clock_and_instant() {
+        let rtc = RtcTime::from_minutes_since_epoch(28_000_000, 12);
+        let seed = seed_from(&rtc, InstantMillis::from_absolute(1234));
+        assert_eq!(seed_from(&rtc, InstantMillis::from_absolute(1234)), seed);
+        assert_ne!(seed_from(&rtc, InstantMillis::from_absolute(1235)), seed);
+        let later = RtcTime::from_minutes_since_epoch(28_000_000, 13);
+        assert_ne!(seed_from(&later, InstantMillis::from_absolute(1234)), seed);
+    }
+}
+
+mod solar {
+    use crate::calendar::days_from_civil;
+    use crate::solar::{sun_times, Location};
+    use crate::timezone::TimeZone;
+
+    /// asserts that the calculated time is within 3 minutes of the expected one
+    fn assert_near(minutes: Option<i16>, hour: i16, minute: i16) {
+        let minutes = minutes.unwrap();
+        let expected = hour * 60 + minute;
+        assert!(
+            (minutes - expected).abs() <= 3,
+            "{:02}:{:02} instead of {:02}:{:02}",
+            minutes / 60,
+            minutes % 60,
+            hour,
+            minute
+        );
+    }
+
+    #[test]
+    fn known_sun_times() {
+        let berlin = Location {
+            latitude: 5252,
+            longitude: 1340,
+        };
+        // central European time, with daylight saving time in summer
+        let cet = TimeZone::default();
+        let times = sun_times(&berlin, &cet, days_from_civil(2024, 6, 21));
+        assert_near(times.sunrise, 4, 43);
+        assert_near(times.sunset, 21, 33);
+        let times = sun_times(&berlin, &cet, days_from_civil(2024, 12, 21));
+        assert_near(times.sunrise, 8, 15);
+        assert_near(times.sunset, 15, 54);
+        let sydney = Location {
+            latitude: -3387,
+            longitude: 15121,
+        };
+        // daylight saving time in the southern hemisphere, which has no rule here
+        let aedt = TimeZone {
+            utc_offset: 660,
+            ..TimeZone::UTC
+        };
+        let times = sun_times(&sydney, &aedt, days_from_civil(2024, 3, 4));
+        assert_near(times.sunrise, 6, 46);
+        assert_near(times.sunset, 19, 27);
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let tromso = Location {
+            latitude: 6965,
+            longitude: 1896,
+        };
+        let cet = TimeZone::default();
+        for (month, day) in [(6, 21), (12, 21)] {
+            let times = sun_times(&tromso, &cet, days_from_civil(2024, month, day));
+            assert_eq!((times.sunrise, times.sunset), (None, None));
+        }
+        let times = sun_times(&tromso, &cet, days_from_civil(2024, 3, 20));
+        assert!(times.sunrise.is_some() && times.sunset.is_some());
+    }
+}
+
+mod timezone {
+    use crate::calendar::days_from_civil;
+    use crate::solar::{Location, SunTable};
+    use crate::system::RtcTime;
+    use crate::timezone::{DstRule, TimeZone};
+    use crate::ScheduledOutEntry;
+
+    fn utc(month: u8, day: u8, hour: u8, minute: u8) -> RtcTime {
+        RtcTime::from_minutes_since_epoch(
+            days_from_civil(2024, month, day) * 24 * 60 + hour as i32 * 60 + minute as i32,
+            0,
+        )
+    }
+
+    fn hour_minute(time: &RtcTime) -> (u8, u8) {
+        (time.hour, time.minute)
+    }
+
+    #[test]
+    fn eu_transitions() {
+        let cet = TimeZone::default();
+        // daylight saving time from 2024-03-31 to 2024-10-27, switching at 01:00 UTC
+        assert_eq!(hour_minute(&cet.to_local(&utc(3, 31, 0, 59))), (1, 59));
+        assert_eq!(hour_minute(&cet.to_local(&utc(3, 31, 1, 0))), (3, 0));
+        assert_eq!(hour_minute(&cet.to_local(&utc(10, 27, 0, 59))), (2, 59));
+        assert_eq!(hour_minute(&cet.to_local(&utc(10, 27, 1, 0))), (2, 0));
+        assert_eq!(hour_minute(&cet.to_local(&utc(12, 31, 23, 30))), (0, 30));
+        assert_eq!(cet.to_local(&utc(12, 31, 23, 30)).year, 2025);
+    }
+
+    #[test]
+    fn us_transitions() {
+        let eastern = TimeZone {
+            utc_offset: -5 * 60,
+            dst: DstRule::Us,
+        };
+        // daylight saving time from 2024-03-10 to 2024-11-03, switching at 02:00 local time
+        assert_eq!(hour_minute(&eastern.to_local(&utc(3, 10, 6, 59))), (1, 59));
+        assert_eq!(hour_minute(&eastern.to_local(&utc(3, 10, 7, 0))), (3, 0));
+        assert_eq!(hour_minute(&eastern.to_local(&utc(11, 3, 5, 59))), (1, 59));
+        assert_eq!(hour_minute(&eastern.to_local(&utc(11, 3, 6, 0))), (1, 0));
+    }
+
+    #[test]
+    fn setting_the_clock() {
+        let cet = TimeZone::default();
+        for time in [utc(1, 15, 12, 0), utc(7, 15, 12, 0), utc(10, 27, 0, 30)] {
+            assert_eq!(cet.to_utc(&cet.to_local(&time)), time);
+        }
+        // within the repeated hour, the first occurrence
+        let local = RtcTime {
+            hour: 2,
+            minute: 30,
+            ..utc(10, 27, 0, 0)
+        };
+        assert_eq!(cet.to_utc(&local), utc(10, 27, 0, 30));
+        // within the skipped hour, an hour later
+        let local = RtcTime {
+            hour: 2,
+            minute: 30,
+            ..utc(3, 31, 0, 0)
+        };
+        assert_eq!(hour_minute(&cet.to_local(&cet.to_utc(&local))), (3, 30));
+    }
+
+    #[test]
+    fn scheduled_entry_in_skipped_and_repeated_hour() {
+        let cet = TimeZone::default();
+        let entry = ScheduledOutEntry {
+            start_hour: 2,
+            start_minute: 30,
+            duration: 1000 * 60 * 60,
+            ..Default::default()
+        };
+        let is_active = |time: RtcTime| {
+            let local_day = cet.to_local(&time).days_since_epoch();
+            let sun_table = SunTable::new(Location::default(), cet, local_day);
+            entry.is_active(&time, &cet, &sun_table)
+        };
+        // skipped hour: 02:30 is taken as 03:30 CEST, and the entry runs for an hour
+        assert!(!is_active(utc(3, 31, 1, 29)));
+        assert!(is_active(utc(3, 31, 1, 30)));
+        assert!(is_active(utc(3, 31, 2, 29)));
+        assert!(!is_active(utc(3, 31, 2, 30)));
+        // repeated hour: the entry starts at the first 02:30 only
+        assert!(!is_active(utc(10, 27, 0, 29)));
+        assert!(is_active(utc(10, 27, 0, 30)));
+        assert!(is_active(utc(10, 27, 1, 29)));
+        assert!(!is_active(utc(10, 27, 1, 30)));
+        assert!(!is_active(utc(10, 27, 2, 29)));
+        // an ordinary day in summer
+        assert!(!is_active(utc(7, 1, 0, 29)));
+        assert!(is_active(utc(7, 1, 0, 30)));
+    }
+}
+
+mod interlocks {
+    use crate::interlocks::{InterlockState, Interlocks};
+    use crate::stagger::StaggerState;
+    use greaheisl_async::InstantMillis;
+
+    fn at(millis: u32) -> InstantMillis {
+        InstantMillis::from_absolute(millis)
+    }
+
+    /// enforces the interlocks and records the result as applied
+    fn apply<const N: usize>(
+        state: &mut InterlockState<N>,
+        requested: [bool; N],
+        now: InstantMillis,
+    ) -> ([bool; N], Option<i32>) {
+        let (states, wake_up) = state.enforce(&requested, now);
+        state.record(&states, now);
+        (states, wake_up)
+    }
+
+    #[test]
+    fn lower_index_wins_and_running_relay_keeps_priority() {
+        let mut state = InterlockState::<4>::new(Interlocks {
+            exclusive_groups: vec![0b0011],
+            ..Default::default()
+        });
+        let (states, _) = apply(&mut state, [true, true, false, false], at(0));
+        assert_eq!(states, [true, false, false, false]);
+        let (states, _) = apply(&mut state, [false, true, false, false], at(100));
+        assert_eq!(states, [false, true, false, false]);
+        // relay 1 is already on, so it is not displaced by relay 0
+        let (states, _) = apply(&mut state, [true, true, false, false], at(200));
+        assert_eq!(states, [false, true, false, false]);
+    }
+
+    #[test]
+    fn dead_time_defers_partner() {
+        let mut state = InterlockState::<4>::new(Interlocks {
+            exclusive_groups: vec![0b0011],
+            dead_time: 500,
+            ..Default::default()
+        });
+        apply(&mut state, [true, false, false, false], at(0));
+        let (states, wake_up) = apply(&mut state, [false, true, true, false], at(1000));
+        assert_eq!(states, [false, false, true, false]);
+        assert_eq!(wake_up, Some(500));
+        let (states, wake_up) = apply(&mut state, [false, true, true, false], at(1400));
+        assert_eq!(states, [false, false, true, false]);
+        assert_eq!(wake_up, Some(100));
+        let (states, wake_up) = apply(&mut state, [false, true, true, false], at(1500));
+        assert_eq!(states, [false, true, true, false]);
+        assert_eq!(wake_up, None);
+    }
+
+    #[test]
+    fn max_active_is_respected() {
+        let mut state = InterlockState::<4>::new(Interlocks {
+            max_active: Some(2),
+            ..Default::default()
+        });
+        apply(&mut state, [false, false, true, false], at(0));
+        let (states, _) = apply(&mut state, [true; 4], at(100));
+        assert_eq!(states, [true, false, true, false]);
+    }
+
+    #[test]
+    fn relay_held_back_by_stagger_does_not_count_as_on() {
+        let mut interlocks = InterlockState::<3>::new(Interlocks {
+            exclusive_groups: vec![0b011],
+            max_active: Some(1),
+            dead_time: 500,
+        });
+        let mut stagger = StaggerState::<3>::new(1000);
+        let mut current = [false; 3];
+        // returns the states and the time left of the dead time
+        let mut step = |requested, now| {
+            let (permitted, dead_time_left) = interlocks.enforce(&requested, now);
+            let (states, _) = stagger.apply(&permitted, &current, now);
+            interlocks.record(&states, now);
+            current = states;
+            (states, dead_time_left)
+        };
+        assert_eq!(
+            step([false, false, true], at(0)),
+            ([false, false, true], None)
+        );
+        // relay 0 is permitted once relay 2 is off, but has to wait for the stagger
+        assert_eq!(step([true, false, false], at(100)), ([false; 3], None));
+        // relay 0 has never been on, so its partner does not wait for the dead time
+        assert_eq!(step([false, true, false], at(200)), ([false; 3], None));
+        assert_eq!(
+            step([false, true, false], at(1000)),
+            ([false, true, false], None)
+        );
+    }
+}
+
+mod wear {
+    use crate::wear::{WearLimits, WearState};
+    use greaheisl_async::InstantMillis;
+
+    fn at(millis: u32) -> InstantMillis {
+        InstantMillis::from_absolute(millis)
+    }
+
+    #[test]
+    fn minimum_on_and_off_times_defer_transitions() {
+        let mut state = WearState::<2>::new(vec![WearLimits {
+            min_on: 1000,
+            min_off: 3000,
+            max_switches_per_hour: None,
+        }]);
+        let decision = state.apply(&[true, true], at(0));
+        assert_eq!(decision.states, [true, true]);
+        state.record(&decision.states, at(0));
+        // relay 0 has to stay on, relay 1 is not limited
+        let decision = state.apply(&[false, false], at(400));
+        assert_eq!(decision.states, [true, false]);
+        assert_eq!(decision.deferred, [true, false]);
+        assert_eq!(decision.wake_up, Some(600));
+        state.record(&decision.states, at(400));
+        let decision = state.apply(&[false, false], at(1000));
+        assert_eq!(decision.states, [false, false]);
+        state.record(&decision.states, at(1000));
+        let decision = state.apply(&[true, false], at(2000));
+        assert_eq!(decision.states, [false, false]);
+        assert_eq!(decision.wake_up, Some(2000));
+    }
+
+    #[test]
+    fn switches_per_hour_are_limited() {
+        const HOUR: u32 = 60 * 60 * 1000;
+        let mut state = WearState::<1>::new(vec![WearLimits {
+            max_switches_per_hour: Some(2),
+            ..Default::default()
+        }]);
+        for (on, off) in [(0, 10), (20, 30)] {
+            state.record(&[true], at(on));
+            state.record(&[false], at(off));
+        }
+        let decision = state.apply(&[true], at(40));
+        assert_eq!(decision.states, [false]);
+        assert_eq!(decision.wake_up, Some(HOUR as i32 - 40));
+        // switching off is never limited by the rate
+        state.record(&[true], at(HOUR));
+        assert_eq!(state.apply(&[false], at(HOUR + 1)).states, [false]);
+    }
+}
+
+mod stagger {
+    use crate::stagger::StaggerState;
+    use greaheisl_async::InstantMillis;
+
+    fn at(millis: u32) -> InstantMillis {
+        InstantMillis::from_absolute(millis)
+    }
+
+    #[test]
+    fn relays_switch_on_one_at_a_time() {
+        let mut state = StaggerState::<4>::new(500);
+        let target = [true, true, false, true];
+        let (current, wake_up) = state.apply(&target, &[false; 4], at(0));
+        assert_eq!(current, [true, false, false, false]);
+        assert_eq!(wake_up, Some(500));
+        let (current, wake_up) = state.apply(&target, &current, at(300));
+        assert_eq!(current, [true, false, false, false]);
+        assert_eq!(wake_up, Some(200));
+        // relay 1 is no longer wanted, so relay 3 is next
+        let target = [true, false, false, true];
+        let (current, wake_up) = state.apply(&target, &current, at(500));
+        assert_eq!(current, [true, false, false, true]);
+        assert_eq!(wake_up, None);
+        // switching off is never delayed
+        let (current, _) = state.apply(&[false; 4], &current, at(600));
+        assert_eq!(current, [false; 4]);
+    }
+
+    #[test]
+    fn zero_interval_switches_at_once() {
+        let mut state = StaggerState::<3>::new(0);
+        let (current, wake_up) = state.apply(&[true; 3], &[false; 3], at(0));
+        assert_eq!(current, [true; 3]);
+        assert_eq!(wake_up, None);
+    }
+}
+
+mod inputs {
+    use crate::inputs::{InputAction, InputRule, InputState};
+    use greaheisl_async::InstantMillis;
+
+    fn at(millis: u32) -> InstantMillis {
+        InstantMillis::from_absolute(millis)
+    }
+
+    #[test]
+    fn hold_follows_the_debounced_level() {
+        let rules = vec![InputRule {
+            input: 1,
+            relay: 0,
+            action: InputAction::Hold,
+        }];
+        let mut state = InputState::<2>::new(rules, 50);
+        let decision = state.apply(0b10, at(0));
+        assert_eq!(decision.states, [false, false]);
+        assert_eq!(decision.wake_up, Some(50));
+        // a glitch shorter than the debounce time is ignored
+        state.apply(0b00, at(20));
+        let decision = state.apply(0b10, at(60));
+        assert_eq!(decision.states, [false, false]);
+        assert_eq!(decision.wake_up, Some(50));
+        let decision = state.apply(0b10, at(110));
+        assert_eq!(decision.states, [true, false]);
+        assert_eq!(decision.wake_up, None);
+        state.apply(0b00, at(200));
+        assert_eq!(state.apply(0b00, at(250)).states, [false, false]);
+    }
+
+    #[test]
+    fn rising_edge_starts_a_pulse() {
+        let rules = vec![InputRule {
+            input: 0,
+            relay: 1,
+            action: InputAction::Pulse(1000),
+        }];
+        let mut state = InputState::<2>::new(rules, 0);
+        let decision = state.apply(0b01, at(0));
+        assert_eq!(decision.states, [false, true]);
+        assert_eq!(decision.wake_up, Some(1000));
+        // the pulse runs on after the input becomes inactive
+        let decision = state.apply(0b00, at(400));
+        assert_eq!(decision.states, [false, true]);
+        assert_eq!(decision.wake_up, Some(600));
+        // becoming active again restarts the pulse
+        let decision = state.apply(0b01, at(800));
+        assert_eq!(decision.wake_up, Some(1000));
+        // staying active does not
+        let decision = state.apply(0b01, at(1800));
+        assert_eq!(decision.states, [false, false]);
+        assert_eq!(decision.wake_up, None);
+    }
+
+    #[test]
+    fn rules_for_missing_relays_are_ignored() {
+        let rules = vec![InputRule {
+            input: 0,
+            relay: 5,
+            action: InputAction::Hold,
+        }];
+        let mut state = InputState::<2>::new(rules, 0);
+        assert_eq!(state.apply(0b01, at(0)).states, [false, false]);
+    }
+}
+
+mod thermostat {
+    use crate::{ThermostatEntry, ThermostatMode};
+
+    /// the sequence of requests for the given readings, starting off
+    fn demands(entry: &ThermostatEntry, values: &[i16]) -> Vec<bool> {
+        let mut is_on = false;
+        values
+            .iter()
+            .map(|&value| {
+                is_on = entry.demand(is_on, Some(value));
+                is_on
+            })
+            .collect()
+    }
+
+    #[test]
+    fn heating_switches_on_below_the_gap() {
+        let entry = ThermostatEntry {
+            mode: ThermostatMode::Always,
+            on_threshold: 190,
+            off_threshold: 210,
+        };
+        assert_eq!(
+            demands(&entry, &[200, 191, 190, 200, 209, 210, 200]),
+            [false, false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn hygrostat_switches_on_above_the_gap() {
+        let entry = ThermostatEntry {
+            mode: ThermostatMode::Always,
+            on_threshold: 650,
+            off_threshold: 550,
+        };
+        assert_eq!(
+            demands(&entry, &[600, 650, 600, 551, 550, 600]),
+            [false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn missing_reading_switches_off() {
+        let entry = ThermostatEntry {
+            mode: ThermostatMode::Always,
+            on_threshold: 190,
+            off_threshold: 210,
+        };
+        assert!(entry.demand(false, Some(150)));
+        assert!(!entry.demand(true, None));
+    }
+}
+
+mod event_log {
+    use crate::event_log::{Cause, Event, EventLog};
+    use crate::system::RtcTime;
+
+    fn minute(minute: u8) -> RtcTime {
+        RtcTime {
+            year: 2024,
+            month: 5,
+            day: 1,
+            weekday: 2,
+            hour: 12,
+            minute,
+            second: 0,
+        }
+    }
+
+    fn relay_on(relay: u8) -> Event {
+        Event::RelayOn {
+            relay,
+            cause: Cause::Scheduled { slot: 1 },
+        }
+    }
+
+    #[test]
+    fn empty_log() {
+        let log = EventLog::<4>::new();
+        assert_eq!(log.newest(), None);
+        assert_eq!(log.first_since(0), None);
+    }
+
+    #[test]
+    fn oldest_entries_are_overwritten() {
+        let log = EventLog::<4>::new();
+        for k in 0..6 {
+            log.record(minute(k), relay_on(k));
+        }
+        assert_eq!(log.next_sequence(), 6);
+        assert_eq!(log.get(1), None);
+        let entry = log.get(2).unwrap();
+        assert_eq!(entry.time, minute(2));
+        assert_eq!(entry.event, relay_on(2));
+        assert_eq!(log.newest().unwrap().sequence, 5);
+        assert_eq!(log.get(6), None);
+    }
+
+    #[test]
+    fn reading_incrementally_skips_overwritten_entries() {
+        let log = EventLog::<4>::new();
+        log.record(minute(0), Event::SettingsCommitted);
+        assert_eq!(log.first_since(0).unwrap().event, Event::SettingsCommitted);
+        assert_eq!(log.first_since(1), None);
+        for k in 1..7 {
+            log.record(minute(k), Event::ClockSet);
+        }
+        // entries 0 to 2 are gone, so reading continues with entry 3
+        let mut sequence = 1;
+        let mut read = Vec::new();
+        while let Some(entry) = log.first_since(sequence) {
+            read.push(entry.sequence);
+            sequence = entry.sequence + 1;
+        }
+        assert_eq!(read, [3, 4, 5, 6]);
+    }
+}
+
+mod statistics {
+    use crate::statistics::{RelayStats, RuntimeStats};
+    use crate::storage::{
+        decode_settings, decode_statistics, encode_statistics, statistics_blob_size, StorageError,
+    };
+    use crate::{DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS};
+
+    const HOUR: i32 = 60 * 60 * 1000;
+
+    #[test]
+    fn on_times_are_counted_per_day() {
+        let mut statistics = RuntimeStats::<2>::default();
+        statistics.roll_over(100);
+        statistics.add_on_time(&[true, false], 2 * HOUR);
+        statistics.count_switch(0);
+        assert!(!statistics.roll_over(100));
+        assert!(statistics.roll_over(101));
+        statistics.add_on_time(&[true, true], HOUR);
+        assert_eq!(
+            statistics.relays[0],
+            RelayStats {
+                total_on: 3 * HOUR as u64,
+                today: HOUR,
+                yesterday: 2 * HOUR,
+                switch_count: 1,
+            }
+        );
+        assert_eq!(statistics.relays[1].total_on, HOUR as u64);
+        // after a gap, yesterday's on-time is unknown
+        statistics.roll_over(105);
+        assert_eq!(statistics.relays[0].today, 0);
+        assert_eq!(statistics.relays[0].yesterday, 0);
+        assert_eq!(statistics.relays[0].total_on, 3 * HOUR as u64);
+    }
+
+    #[test]
+    fn on_time_is_split_at_midnight() {
+        let mut statistics = RuntimeStats::<2>::default();
+        statistics.roll_over(100);
+        // off until 22:00, then on until 01:00 of the next day
+        assert!(!statistics.count_until(&[false, false], 22 * HOUR, 100, 22 * HOUR));
+        assert!(statistics.count_until(&[true, false], 3 * HOUR, 101, HOUR));
+        assert_eq!(statistics.relays[0].yesterday, 2 * HOUR);
+        assert_eq!(statistics.relays[0].today, HOUR);
+        assert_eq!(statistics.relays[0].total_on, 3 * HOUR as u64);
+        assert_eq!(statistics.relays[1], RelayStats::default());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut statistics = RuntimeStats::<DEFAULT_NUM_RELAYS>::default();
+        statistics.roll_over(19_000);
+        statistics.add_on_time(&[false, true, false, true], 5 * HOUR);
+        statistics.relays[3].total_on = 1 << 40;
+        statistics.count_switch(1);
+        let blob = encode_statistics(&statistics);
+        assert_eq!(blob.len(), statistics_blob_size(DEFAULT_NUM_RELAYS));
+        assert_eq!(decode_statistics(&blob), Ok(statistics.clone()));
+        // other numbers of relays
+        let smaller: RuntimeStats<2> = decode_statistics(&blob).unwrap();
+        assert_eq!(smaller.relays, statistics.relays[..2]);
+        assert_eq!(smaller.day, 19_000);
+        let larger: RuntimeStats<8> = decode_statistics(&blob).unwrap();
+        assert_eq!(larger.relays[7], RelayStats::default());
+        // settings and statistics are not mixed up
+        let settings = decode_settings::<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>(&blob);
+        assert_eq!(settings.err(), Some(StorageError::BadMagic));
+    }
+}
+
+mod commands {
+    use crate::commands::{execute, write_entry, Command, CommandError, Start};
+    use crate::event_log::EventLog;
+    use crate::storage::decode_settings;
+    use crate::system::{AccessOutputStates, AccessPersistentStorage, AccessRtc, RtcTime};
+    use crate::ui::commit_menu_edits;
+    use crate::{
+        OutputStatus, ScheduledOutEntry, Settings, StartMode, Weekdays, DEFAULT_NUM_RELAYS,
+        DEFAULT_NUM_SLOTS,
+    };
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use greaheisl_async::{AccessTiming, DurationWrapper, InstantMillis, Notify};
+
+    const MINUTE: i32 = 60 * 1000;
+
+    type DefaultSettings = Settings<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>;
+
+    #[derive(Default)]
+    pub(super) struct CommandTestSys {
+        pub(super) stored: RefCell<Vec<u8>>,
+    }
+
+    impl AccessTiming for CommandTestSys {
+        fn get_instant(&self) -> InstantMillis {
+            InstantMillis::from_absolute(0)
+        }
+        fn set_delay_request(&self, _millis: DurationWrapper) {}
+    }
+
+    impl AccessRtc for CommandTestSys {
+        fn get_rtc(&self) -> RtcTime {
+            RtcTime::from_minutes_since_epoch(0, 0)
+        }
+        fn set_rtc(&self, _time: &RtcTime) {}
+    }
+
+    impl AccessPersistentStorage for CommandTestSys {
+        fn load_settings(&self, buffer: &mut [u8]) -> usize {
+            let stored = self.stored.borrow();
+            buffer[..stored.len()].copy_from_slice(&stored);
+            stored.len()
+        }
+        fn store_settings(&self, data: &[u8]) {
+            *self.stored.borrow_mut() = data.to_vec();
+        }
+    }
+
+    impl AccessOutputStates<DEFAULT_NUM_RELAYS> for CommandTestSys {
+        fn set_relay_states(&self, _relais_states: &[bool; DEFAULT_NUM_RELAYS]) {}
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(Command::parse("help"), Ok(Command::Help));
+        assert_eq!(Command::parse(" TIME "), Ok(Command::GetTime));
+        assert_eq!(
+            Command::parse("time 2024-02-29 07:05"),
+            Ok(Command::SetTime(RtcTime {
+                year: 2024,
+                month: 2,
+                day: 29,
+                weekday: 3,
+                hour: 7,
+                minute: 5,
+                second: 0,
+            }))
+        );
+        assert_eq!(
+            Command::parse("LIST 2"),
+            Ok(Command::List { relay: Some(1) })
+        );
+        assert_eq!(
+            Command::parse("EDIT 1 3 SS-15 2H"),
+            Ok(Command::Edit {
+                relay: 0,
+                slot: 2,
+                start: Start::Sunset { offset: -15 },
+                duration: 120 * MINUTE,
+                weekdays: None,
+            })
+        );
+        assert_eq!(
+            Command::parse("START 4 90"),
+            Ok(Command::Start {
+                relay: 3,
+                duration: 90 * MINUTE,
+            })
+        );
+        assert_eq!(Command::parse("relays"), Ok(Command::Relays));
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        assert_eq!(Command::parse("OPEN 1"), Err(CommandError::Unknown));
+        assert_eq!(Command::parse("CLEAR 1"), Err(CommandError::Syntax));
+        assert_eq!(
+            Command::parse("TIME 2023-02-29 07:05"),
+            Err(CommandError::Range)
+        );
+        assert_eq!(
+            Command::parse("TIME 2024-02-28 24:00"),
+            Err(CommandError::Range)
+        );
+        assert_eq!(
+            Command::parse("EDIT 0 1 07:30 10"),
+            Err(CommandError::Range)
+        );
+        assert_eq!(
+            Command::parse("EDIT 1 1 SR+200 10"),
+            Err(CommandError::Range)
+        );
+        assert_eq!(
+            Command::parse("EDIT 1 1 07:30 25H"),
+            Err(CommandError::Range)
+        );
+        assert_eq!(
+            Command::parse("EDIT 1 1 07:30 10X"),
+            Err(CommandError::Syntax)
+        );
+        assert_eq!(
+            Command::parse("EDIT 1 1 07:30 10 11111"),
+            Err(CommandError::Syntax)
+        );
+        assert_eq!(Command::parse("START 1 -5"), Err(CommandError::Range));
+    }
+
+    #[test]
+    fn listed_entries_can_be_edited() {
+        let entry = ScheduledOutEntry {
+            start_hour: 6,
+            start_minute: 0,
+            duration: 30 * 1000,
+            weekdays: Weekdays::weekend(),
+            start_mode: StartMode::Sunrise,
+            sun_offset: -30,
+        };
+        let mut line = String::from("EDIT ");
+        write_entry(&mut line, 1, 2, &entry).unwrap();
+        assert_eq!(line, "EDIT 2 3 SR-30 30S 0000011\r\n");
+        assert_eq!(
+            Command::parse(line.trim_end()),
+            Ok(Command::Edit {
+                relay: 1,
+                slot: 2,
+                start: Start::Sunrise { offset: -30 },
+                duration: 30 * 1000,
+                weekdays: Some(Weekdays::weekend()),
+            })
+        );
+    }
+
+    #[test]
+    fn serial_edits_survive_an_open_menu() {
+        let sys = CommandTestSys::default();
+        let settings = RefCell::new(DefaultSettings::default());
+        let settings_changed = Notify::new();
+        let status = OutputStatus::new();
+        let event_log = EventLog::new();
+        // the menu is opened and works on a copy of the settings
+        let original = settings.borrow().clone();
+        let mut edited = original.clone();
+        edited.scheduled_out[0][0].start_hour = 6;
+        edited.scheduled_out[0][0].duration = 10 * MINUTE;
+        // meanwhile, an entry is edited over the serial port
+        let mut out = String::new();
+        execute(
+            &sys,
+            &settings,
+            &settings_changed,
+            &status,
+            &event_log,
+            Command::Edit {
+                relay: 1,
+                slot: 2,
+                start: Start::Clock {
+                    hour: 19,
+                    minute: 45,
+                },
+                duration: 20 * MINUTE,
+                weekdays: None,
+            },
+            &mut out,
+        )
+        .unwrap();
+        // the menu is left
+        commit_menu_edits(
+            &sys,
+            &settings,
+            &settings_changed,
+            &event_log,
+            &original,
+            &edited,
+        );
+        let check = |settings: &DefaultSettings| {
+            assert_eq!(settings.scheduled_out[0][0].start_hour, 6);
+            assert_eq!(settings.scheduled_out[0][0].duration, 10 * MINUTE);
+            assert_eq!(settings.scheduled_out[1][2].start_hour, 19);
+            assert_eq!(settings.scheduled_out[1][2].start_minute, 45);
+            assert_eq!(settings.scheduled_out[1][2].duration, 20 * MINUTE);
+        };
+        check(&settings.borrow());
+        let stored: DefaultSettings = decode_settings(&sys.stored.borrow()).unwrap();
+        check(&stored);
+    }
+}
+
+mod host_protocol {
+    use super::commands::CommandTestSys;
+    use crate::event_log::EventLog;
+    use crate::host_protocol::{
+        respond, DeviceInfo, ErrorCode, FrameDecoder, Message, Request, Response, FRAME_START,
+        PROTOCOL_VERSION,
+    };
+    use crate::storage::encode_settings;
+    use crate::system::RtcTime;
+    use crate::ui::commit_menu_edits;
+    use crate::{OutputStatus, Settings, DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS};
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use greaheisl_async::Notify;
+
+    type DefaultSettings = Settings<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>;
+
+    fn decode_all(decoder: &mut FrameDecoder, bytes: &[u8]) -> Vec<Vec<u8>> {
+        bytes
+            .iter()
+            .filter_map(|byte| decoder.push(*byte))
+            .collect()
+    }
+
+    #[test]
+    fn messages_survive_the_roundtrip() {
+        let time = RtcTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            weekday: 3,
+            hour: 23,
+            minute: 59,
+            second: 30,
+        };
+        let requests = [
+            Request::Info,
+            Request::SetTime(time),
+            Request::PutSettings(vec![1, 2, FRAME_START, 3]),
+        ];
+        let responses = [
+            Response::Ack,
+            Response::Info(DeviceInfo {
+                protocol_version: PROTOCOL_VERSION,
+                num_relays: 8,
+                num_slots: 3,
+                relay_count: 4,
+            }),
+            Response::Time(time),
+            Response::Relays(vec![false, true]),
+            Response::Error(ErrorCode::InvalidValue),
+        ];
+        let mut decoder = FrameDecoder::new();
+        for (request_id, body) in requests.into_iter().enumerate() {
+            let message = Message {
+                request_id: request_id as u16,
+                body,
+            };
+            let received = decode_all(&mut decoder, &message.encode());
+            assert_eq!(received.len(), 1);
+            assert_eq!(Message::decode(&received[0]), Ok(message));
+        }
+        for body in responses {
+            let message = Message {
+                request_id: 0xBEEF,
+                body,
+            };
+            let received = decode_all(&mut decoder, &message.encode());
+            assert_eq!(Message::decode(&received[0]), Ok(message));
+        }
+    }
+
+    #[test]
+    fn corrupt_frames_are_dropped() {
+        let frame = Message {
+            request_id: 7,
+            body: Request::GetRelays,
+        }
+        .encode();
+        let mut corrupt = frame.clone();
+        corrupt[5] ^= 0x01;
+        let mut bytes = b"OK\r\n".to_vec();
+        bytes.extend_from_slice(&corrupt);
+        bytes.extend_from_slice(&frame);
+        let mut decoder = FrameDecoder::new();
+        let received = decode_all(&mut decoder, &bytes);
+        assert_eq!(received.len(), 1);
+        assert!(!decoder.is_receiving());
+        // an oversized length is rejected at once
+        assert_eq!(
+            decode_all(&mut decoder, &[FRAME_START, 0xFF, 0xFF]).len(),
+            0
+        );
+        assert!(!decoder.is_receiving());
+    }
+
+    #[test]
+    fn unknown_messages_are_rejected() {
+        let decode = |message: &[u8]| Message::<Request>::decode(message);
+        assert_eq!(
+            decode(&[PROTOCOL_VERSION + 1, 0, 0, 0x01]),
+            Err(ErrorCode::UnsupportedVersion)
+        );
+        assert_eq!(
+            decode(&[PROTOCOL_VERSION, 0, 0, 0x7F]),
+            Err(ErrorCode::UnknownType)
+        );
+        assert_eq!(
+            decode(&[PROTOCOL_VERSION, 0, 0, 0x01, 0]),
+            Err(ErrorCode::Malformed)
+        );
+        // February 30th
+        assert_eq!(
+            decode(&[PROTOCOL_VERSION, 0, 0, 0x03, 0xE8, 0x07, 2, 30, 0, 0, 0]),
+            Err(ErrorCode::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn uploaded_settings_survive_an_open_menu() {
+        let sys = CommandTestSys::default();
+        let settings = RefCell::new(DefaultSettings::default());
+        let settings_changed = Notify::new();
+        let status = OutputStatus::new();
+        let event_log = EventLog::new();
+        let respond = |message: &[u8]| {
+            respond(
+                &sys,
+                &settings,
+                &settings_changed,
+                &status,
+                &event_log,
+                message,
+            )
+        };
+        // the menu is opened and works on a copy of the settings
+        let original = settings.borrow().clone();
+        let mut edited = original.clone();
+        edited.vacation_jitter[0] = 1000;
+        // meanwhile, the host uploads new settings
+        let mut uploaded = DefaultSettings::default();
+        uploaded.scheduled_out[2][1].start_hour = 5;
+        let request = Message {
+            request_id: 3,
+            body: Request::PutSettings(encode_settings(&uploaded)),
+        };
+        let received = decode_all(&mut FrameDecoder::new(), &request.encode());
+        let response = respond(&received[0]).unwrap();
+        let received = decode_all(&mut FrameDecoder::new(), &response);
+        assert_eq!(
+            Message::decode(&received[0]),
+            Ok(Message {
+                request_id: 3,
+                body: Response::Ack,
+            })
+        );
+        // the menu is left
+        commit_menu_edits(
+            &sys,
+            &settings,
+            &settings_changed,
+            &event_log,
+            &original,
+            &edited,
+        );
+        let settings = settings.borrow();
+        assert_eq!(settings.scheduled_out[2][1].start_hour, 5);
+        assert_eq!(settings.vacation_jitter[0], 1000);
+    }
+
+    #[test]
+    fn truncated_messages_are_not_answered() {
+        let sys = CommandTestSys::default();
+        let settings = RefCell::new(DefaultSettings::default());
+        let settings_changed = Notify::new();
+        let status = OutputStatus::new();
+        let event_log = EventLog::new();
+        let respond = |message: &[u8]| {
+            respond(
+                &sys,
+                &settings,
+                &settings_changed,
+                &status,
+                &event_log,
+                message,
+            )
+        };
+        assert_eq!(respond(&[]), None);
+        assert_eq!(respond(&[PROTOCOL_VERSION, 0]), None);
+        assert!(respond(&[PROTOCOL_VERSION, 0, 0]).is_some());
+    }
+}
+
+mod watch_output {
+    use crate::event_log::EventLog;
+    use crate::statistics::RuntimeStats;
+    use crate::system::{
+        AccessAnalogInput, AccessDigitalInputs, AccessOutputStates, AccessPersistentStorage,
+        AccessRtc, RtcTime, SignalFlags,
+    };
+    use crate::{
+        watch_output, DatedOutEntry, ImmediateOutEntry, OutputConfig, OutputStatus, OverrideMode,
+        Settings, DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS,
+    };
+    use alloc::rc::Rc;
+    use ambassador::Delegate;
+    use core::cell::{Cell, RefCell};
+    use greaheisl_async::{
+        ambassador_impl_AccessExecutorSignals, ambassador_impl_AccessTiming, AccessExecutorSignals,
+        AccessTiming, DurationWrapper, InstantMillis, MiniExecutor, MiniScheduler, Notify,
+    };
+
+    type DefaultSettings = Settings<DEFAULT_NUM_RELAYS, DEFAULT_NUM_SLOTS>;
+
+    /// the real time clock at instant zero, in seconds past midnight of 1970-01-01
+    const RTC_START: u32 = 30;
+
+    #[derive(Clone, Delegate)]
+    #[delegate(AccessTiming, target = "scheduler")]
+    #[delegate(AccessExecutorSignals<SignalFlags>, target = "scheduler")]
+    struct OutputTestSys {
+        scheduler: Rc<RefCell<MiniScheduler<SignalFlags>>>,
+        relay_states: Rc<Cell<[bool; DEFAULT_NUM_RELAYS]>>,
+        /// number of evaluations of the output, which reads the sensor once each
+        evaluations: Rc<Cell<usize>>,
+        /// number of writes to non-volatile memory
+        stores: Rc<Cell<usize>>,
+    }
+
+    impl AccessRtc for OutputTestSys {
+        fn get_rtc(&self) -> RtcTime {
+            let seconds = RTC_START + self.get_instant().into_inner() / 1000;
+            RtcTime::from_minutes_since_epoch((seconds / 60) as i32, (seconds % 60) as u8)
+        }
+        fn set_rtc(&self, _time: &RtcTime) {}
+    }
+
+    impl AccessOutputStates<DEFAULT_NUM_RELAYS> for OutputTestSys {
+        fn set_relay_states(&self, relais_states: &[bool; DEFAULT_NUM_RELAYS]) {
+            self.relay_states.set(*relais_states);
+        }
+    }
+
+    impl AccessPersistentStorage for OutputTestSys {
+        fn load_settings(&self, _buffer: &mut [u8]) -> usize {
+            0
+        }
+        fn store_settings(&self, _data: &[u8]) {
+            self.stores.set(self.stores.get() + 1);
+        }
+    }
+
+    impl AccessDigitalInputs for OutputTestSys {
+        fn get_input_flags(&self) -> u8 {
+            0
+        }
+    }
+
+    impl AccessAnalogInput for OutputTestSys {
+        fn get_analog_value(&self) -> Option<i16> {
+            self.evaluations.set(self.evaluations.get() + 1);
+            None
+        }
+    }
+
+    /// [`watch_output`] running as the main task of an executor
+    struct OutputTest {
+        executor: MiniExecutor<SignalFlags>,
+        sys: OutputTestSys,
+        settings: Rc<RefCell<DefaultSettings>>,
+        settings_changed: Rc<Notify>,
+        event_log: Rc<EventLog>,
+    }
+
+    impl OutputTest {
+        fn new(settings: DefaultSettings) -> Self {
+            let executor_builder = MiniExecutor::new(InstantMillis::from_absolute(0));
+            let sys = OutputTestSys {
+                scheduler: executor_builder.scheduler().clone(),
+                relay_states: Rc::default(),
+                evaluations: Rc::default(),
+                stores: Rc::default(),
+            };
+            let settings = Rc::new(RefCell::new(settings));
+            let settings_changed = Rc::new(Notify::new());
+            let event_log = Rc::new(EventLog::new());
+            let task_sys = sys.clone();
+            let task_settings = settings.clone();
+            let task_settings_changed = settings_changed.clone();
+            let task_event_log = event_log.clone();
+            let executor = executor_builder.build(async move {
+                watch_output(
+                    &task_sys,
+                    &task_settings,
+                    &task_settings_changed,
+                    &OutputStatus::new(),
+                    &task_event_log,
+                    &RefCell::new(RuntimeStats::default()),
+                    OutputConfig::default(),
+                )
+                .await;
+            });
+            Self {
+                executor,
+                sys,
+                settings,
+                settings_changed,
+                event_log,
+            }
+        }
+
+        fn step(&mut self, millis: u32, signals: SignalFlags) -> Option<i32> {
+            self.executor
+                .step(InstantMillis::from_absolute(millis), signals)
+        }
+    }
+
+    fn on(relays: &[usize]) -> [bool; DEFAULT_NUM_RELAYS] {
+        let mut states = [false; DEFAULT_NUM_RELAYS];
+        for &relay in relays {
+            states[relay] = true;
+        }
+        states
+    }
+
+    #[test]
+    fn steps_follow_window_edges_and_notifications() {
+        let mut settings = DefaultSettings::default();
+        settings.immediate_out[0] = Some(ImmediateOutEntry {
+            start: InstantMillis::from_absolute(0),
+            duration: 20 * 1000,
+        });
+        let mut test = OutputTest::new(settings);
+        // the immediate timer ends before the next full minute
+        assert_eq!(test.step(0, SignalFlags::none()), Some(20 * 1000));
+        assert_eq!(test.sys.relay_states.get(), on(&[0]));
+        // nothing has changed, so the output is not evaluated again
+        assert_eq!(test.step(5 * 1000, SignalFlags::Button), Some(15 * 1000));
+        assert_eq!(test.sys.evaluations.get(), 1);
+        // then the next full minute is due
+        assert_eq!(test.step(20 * 1000, SignalFlags::none()), Some(10 * 1000));
+        assert_eq!(test.sys.relay_states.get(), on(&[]));
+        assert_eq!(test.sys.evaluations.get(), 2);
+        test.settings.borrow_mut().override_mode[1] = OverrideMode::ForcedOn;
+        test.settings_changed.notify();
+        // notified outside of a step, the output is evaluated in the next one
+        assert_eq!(test.step(25 * 1000, SignalFlags::none()), Some(5 * 1000));
+        assert_eq!(test.sys.relay_states.get(), on(&[1]));
+        assert_eq!(test.sys.evaluations.get(), 3);
+    }
+
+    #[test]
+    fn expired_dated_entry_is_disarmed_without_log_entry() {
+        let mut settings = DefaultSettings::default();
+        settings.dated_out[2] = Some(DatedOutEntry {
+            year: 1970,
+            month: 1,
+            day: 1,
+            start_hour: 0,
+            start_minute: 0,
+            duration: 10 * 1000,
+        });
+        let mut test = OutputTest::new(settings);
+        test.step(0, SignalFlags::none());
+        assert!(test.settings.borrow().dated_out[2].is_none());
+        assert_eq!(test.sys.stores.get(), 1);
+        // in particular, no `Event::SettingsCommitted`
+        assert!(test.event_log.newest().is_none());
+    }
 }
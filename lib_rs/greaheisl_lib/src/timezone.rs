@@ -0,0 +1,151 @@
+//! conversion between the real time clock, which runs in UTC, and local time
+//!
+//! The schedules and the clock display refer to local time, given by
+//! the [`TimeZone`] of the device: a standard offset from UTC, and
+//! optionally a rule for daylight saving time (DST).
+//!
+//! ## skipped and repeated hours
+//!
+//! When DST starts, the local clock skips an hour. A local time within that
+//! hour is taken with the offset in effect before the transition, so it lands
+//! an hour later, e.g. 02:30 becomes 03:30 daylight saving time in the EU.
+//! When DST ends, the local clock repeats an hour. A local time within that
+//! hour refers to its first occurrence, still in daylight saving time.
+//! Hence, a scheduled timer starts exactly once on either day,
+//! and it runs for its full duration.
+
+use crate::calendar::{civil_from_days, days_from_civil, days_in_month, weekday_from_days};
+use crate::system::RtcTime;
+use enum_iterator::Sequence;
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+/// when daylight saving time is in effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Sequence)]
+pub enum DstRule {
+    /// standard time all year
+    #[default]
+    None,
+    /// European Union: from the last Sunday in March
+    /// to the last Sunday in October, switching at 01:00 UTC
+    Eu,
+    /// United States and Canada: from the second Sunday in March
+    /// to the first Sunday in November, switching at 02:00 local time
+    Us,
+}
+
+impl DstRule {
+    /// start and end of daylight saving time in the given year,
+    /// in minutes since 1970-01-01 00:00 UTC
+    fn period(self, year: u16, utc_offset: i16) -> Option<(i32, i32)> {
+        let utc_offset = utc_offset as i32;
+        match self {
+            DstRule::None => None,
+            DstRule::Eu => Some((
+                last_sunday(year, 3) * MINUTES_PER_DAY + 60,
+                last_sunday(year, 10) * MINUTES_PER_DAY + 60,
+            )),
+            DstRule::Us => Some((
+                (first_sunday(year, 3) + 7) * MINUTES_PER_DAY + 120 - utc_offset,
+                first_sunday(year, 11) * MINUTES_PER_DAY + 120 - (utc_offset + 60),
+            )),
+        }
+    }
+}
+
+/// the first Sunday of the given month, in days since 1970-01-01
+fn first_sunday(year: u16, month: u8) -> i32 {
+    let first = days_from_civil(year, month, 1);
+    first + (6 - weekday_from_days(first) as i32)
+}
+
+/// the last Sunday of the given month, in days since 1970-01-01
+fn last_sunday(year: u16, month: u8) -> i32 {
+    let last = days_from_civil(year, month, days_in_month(year, month));
+    last - (weekday_from_days(last) as i32 + 1) % 7
+}
+
+/// the time zone of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone {
+    /// the difference between standard time and UTC in minutes
+    pub utc_offset: i16,
+    /// when an hour is added to the standard time
+    pub dst: DstRule,
+}
+
+/// central European time, with daylight saving time
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self {
+            utc_offset: 60,
+            dst: DstRule::Eu,
+        }
+    }
+}
+
+impl TimeZone {
+    /// UTC without daylight saving time, i.e., local time is the time of the real time clock
+    pub const UTC: Self = Self {
+        utc_offset: 0,
+        dst: DstRule::None,
+    };
+
+    /// the valid range of [`Self::utc_offset`]
+    pub const UTC_OFFSET_RANGE: core::ops::RangeInclusive<i16> = -14 * 60..=14 * 60;
+
+    /// checks whether all fields are within their valid ranges
+    pub fn is_valid(&self) -> bool {
+        Self::UTC_OFFSET_RANGE.contains(&self.utc_offset)
+    }
+
+    /// checks whether daylight saving time is in effect at the given minute
+    /// (minutes since 1970-01-01 00:00 UTC)
+    pub fn is_dst(&self, utc_minutes: i32) -> bool {
+        let (year, _, _) = civil_from_days(utc_minutes.div_euclid(MINUTES_PER_DAY));
+        self.dst
+            .period(year, self.utc_offset)
+            .is_some_and(|(start, end)| (start..end).contains(&utc_minutes))
+    }
+
+    /// the difference between local time and UTC in minutes at the given minute
+    pub fn offset_at(&self, utc_minutes: i32) -> i16 {
+        self.utc_offset + if self.is_dst(utc_minutes) { 60 } else { 0 }
+    }
+
+    /// converts minutes since 1970-01-01 00:00 UTC into local time
+    pub fn utc_to_local_minutes(&self, utc_minutes: i32) -> i32 {
+        utc_minutes + self.offset_at(utc_minutes) as i32
+    }
+
+    /// converts minutes since 1970-01-01 00:00 local time into UTC
+    ///
+    /// See the [module documentation](self) for skipped and repeated hours.
+    pub fn local_to_utc_minutes(&self, local_minutes: i32) -> i32 {
+        let standard = local_minutes - self.utc_offset as i32;
+        let daylight = standard - 60;
+        // within a repeated hour, both readings are consistent and the earlier one wins;
+        // within a skipped hour, neither is, and the offset of standard time applies
+        if self.is_dst(daylight) {
+            daylight
+        } else {
+            standard
+        }
+    }
+
+    /// converts a reading of the real time clock into local time
+    pub fn to_local(&self, utc: &RtcTime) -> RtcTime {
+        RtcTime::from_minutes_since_epoch(
+            self.utc_to_local_minutes(utc.minutes_since_epoch()),
+            utc.second,
+        )
+    }
+
+    /// converts local time into a setting of the real time clock
+    pub fn to_utc(&self, local: &RtcTime) -> RtcTime {
+        RtcTime::from_minutes_since_epoch(
+            self.local_to_utc_minutes(local.minutes_since_epoch()),
+            local.second,
+        )
+    }
+}
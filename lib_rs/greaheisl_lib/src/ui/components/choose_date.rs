@@ -0,0 +1,162 @@
+use crate::calendar::{days_in_month, MAX_YEAR, MIN_YEAR};
+use crate::system::buttons::{wait_button_press2_or_timeout, ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::ui::display::run_blinking_led_matrix;
+use greaheisl_async::{AccessTiming, Timer};
+use greaheisl_bitvecimg::font::typeset::TextPrinterTrait;
+use greaheisl_button_processor::ButtonEvent;
+
+use super::choose_time::with_blinking_printer;
+use super::{SelectionResponse, BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT};
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Year,
+    Month,
+    Day,
+}
+
+/// lets the user choose a date
+///
+/// First the year is shown with four digits,
+/// then day and month are shown side by side.
+pub async fn choose_date(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    year: &mut u16,
+    month: &mut u8,
+    day: &mut u8,
+) -> SelectionResponse {
+    let mut year_state = (*year).clamp(MIN_YEAR, MAX_YEAR);
+    let mut month_state = (*month).clamp(1, 12);
+    let mut day_state = (*day).clamp(1, days_in_month(year_state, month_state));
+    let mut phase = Phase::Year;
+    loop {
+        let response = match phase {
+            Phase::Year => {
+                let mut value = (year_state - MIN_YEAR) as u8;
+                let response = choose_value(
+                    sys,
+                    btns,
+                    &mut value,
+                    (MAX_YEAR - MIN_YEAR) as u8,
+                    |value| [MIN_YEAR + value as u16, 0],
+                    &[true, true, true, true],
+                )
+                .await;
+                year_state = MIN_YEAR + value as u16;
+                response
+            }
+            Phase::Month => {
+                let mut value = month_state - 1;
+                let response = choose_value(
+                    sys,
+                    btns,
+                    &mut value,
+                    11,
+                    |value| [day_state as u16, value as u16 + 1],
+                    &[false, false, true, true],
+                )
+                .await;
+                month_state = value + 1;
+                response
+            }
+            Phase::Day => {
+                let mut value = day_state - 1;
+                let response = choose_value(
+                    sys,
+                    btns,
+                    &mut value,
+                    days_in_month(year_state, month_state) - 1,
+                    |value| [value as u16 + 1, month_state as u16],
+                    &[true, true, false, false],
+                )
+                .await;
+                day_state = value + 1;
+                response
+            }
+        };
+        // keep the day valid while month and year change
+        day_state = day_state.clamp(1, days_in_month(year_state, month_state));
+        phase = match (phase, response) {
+            (_, SelectionResponse::Timeout) => return response,
+            (Phase::Year, SelectionResponse::Back) => return response,
+            (Phase::Year, SelectionResponse::Ok) => Phase::Month,
+            (Phase::Month, SelectionResponse::Back) => Phase::Year,
+            (Phase::Month, SelectionResponse::Ok) => Phase::Day,
+            (Phase::Day, SelectionResponse::Back) => Phase::Month,
+            (Phase::Day, SelectionResponse::Ok) => break,
+        };
+    }
+    // confirm that a new date has been chosen by blinking quickly
+    let sheets = with_blinking_printer(&[true, true, true, true], |printer| {
+        printer.print_uint::<_, 2>(day_state).unwrap();
+        printer.skip(1);
+        printer.print_uint::<_, 2>(month_state).unwrap();
+    });
+    run_blinking_led_matrix(sys, &sheets, BLINK_DELAY_CONFIRM_VALUE, async {
+        Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+    })
+    .await;
+    *year = year_state;
+    *month = month_state;
+    *day = day_state;
+    SelectionResponse::Ok
+}
+
+/// lets the user change `value` cyclically within `0..=max`
+///
+/// `numbers` maps the value to the numbers shown on the display.
+/// If the second number is zero, the first number is shown with four digits,
+/// otherwise both numbers are shown with two digits.
+async fn choose_value(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    value: &mut u8,
+    max: u8,
+    numbers: impl Fn(u8) -> [u16; 2],
+    mask: &[bool],
+) -> SelectionResponse {
+    *value = (*value).min(max);
+    loop {
+        let [first, second] = numbers(*value);
+        let sheets = with_blinking_printer(mask, |printer| {
+            if second == 0 {
+                printer.print_uint::<_, 4>(first).unwrap();
+            } else {
+                printer.print_uint::<_, 2>(first).unwrap();
+                printer.skip(1);
+                printer.print_uint::<_, 2>(second).unwrap();
+            }
+        });
+        run_blinking_led_matrix(
+            sys,
+            &sheets,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press2_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                if *value > 0 {
+                    *value -= 1;
+                } else {
+                    *value = max;
+                }
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                if *value < max {
+                    *value += 1;
+                } else {
+                    *value = 0;
+                }
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                break SelectionResponse::Ok;
+            }
+            _ => {}
+        }
+    }
+}
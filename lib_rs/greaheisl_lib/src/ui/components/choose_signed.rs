@@ -0,0 +1,73 @@
+use crate::system::buttons::{wait_button_press2_or_timeout, ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::ui::display::run_blinking_led_matrix;
+use greaheisl_async::{AccessTiming, Timer};
+use greaheisl_bitvecimg::font::typeset::TextPrinterTrait;
+use greaheisl_button_processor::ButtonEvent;
+
+use super::choose_time::with_blinking_printer;
+use super::{SelectionResponse, BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT};
+
+/// lets the user choose a value within `-max..=max` in steps of `step`
+///
+/// The font has no minus sign, so the sign is shown as a letter,
+/// `letters[0]` for negative and `letters[1]` for positive values,
+/// followed by the magnitude; zero is shown without a letter.
+pub async fn choose_signed(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    value: &mut i16,
+    max: i16,
+    step: i16,
+    letters: [&str; 2],
+) -> SelectionResponse {
+    let mut state = (*value / step * step).clamp(-max, max);
+    loop {
+        let sheets = with_blinking_printer(&[true, true, true, true], |printer| {
+            print_signed(printer, state, letters)
+        });
+        run_blinking_led_matrix(
+            sys,
+            &sheets,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press2_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => return SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => return SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                state = if state > -max { state - step } else { max };
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                state = if state < max { state + step } else { -max };
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => break,
+            _ => {}
+        }
+    }
+    // confirm that a new value has been chosen by blinking quickly
+    let sheets = with_blinking_printer(&[true, true, true, true], |printer| {
+        print_signed(printer, state, letters)
+    });
+    run_blinking_led_matrix(sys, &sheets, BLINK_DELAY_CONFIRM_VALUE, async {
+        Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+    })
+    .await;
+    *value = state;
+    SelectionResponse::Ok
+}
+
+/// prints the sign as a letter followed by the magnitude without leading zeros
+fn print_signed(printer: &mut impl TextPrinterTrait, value: i16, letters: [&str; 2]) {
+    if value != 0 {
+        printer.print_str(letters[(value > 0) as usize]).unwrap();
+    }
+    let magnitude = value.unsigned_abs();
+    match magnitude {
+        0..=9 => printer.print_uint::<_, 1>(magnitude),
+        10..=99 => printer.print_uint::<_, 2>(magnitude),
+        _ => printer.print_uint::<_, 3>(magnitude),
+    }
+    .unwrap();
+}
@@ -165,7 +165,7 @@ async fn choose_minute(
     }
 }
 
-fn with_blinking_printer<'a>(
+pub(super) fn with_blinking_printer<'a>(
     mask: &'a [bool],
     fcn: impl FnOnce(
         &mut TextLinePrinter<CarbonCopyCanvas<'a, &mut Image<12, 8, 3>>, FitzlFontNarrowNum>,
@@ -11,26 +11,30 @@ use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
 
 use super::{BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT};
 
-pub fn print_duration(matrix: ImageRegionMut, duration: DurationMillis) {
+/// prints a duration in milliseconds as two digits and a unit
+///
+/// The unit is the smallest of seconds, minutes, hours, days, weeks (`W`)
+/// and years (`J`, "Jahre") that keeps the number below 100.
+pub fn print_duration(matrix: ImageRegionMut, duration: impl Into<i64>) {
+    const UNITS: [(i64, &str); 6] = [
+        (1000, "S"),
+        (60 * 1000, "M"),
+        (60 * 60 * 1000, "H"),
+        (24 * 60 * 60 * 1000, "D"),
+        (7 * 24 * 60 * 60 * 1000, "W"),
+        (365 * 24 * 60 * 60 * 1000, "J"),
+    ];
     let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
-    let number: DurationMillis;
-    let unit_code: &'static str;
-    if duration <= 0 {
-        number = 0;
-        unit_code = "";
-    } else if duration < 100 * 1000 {
-        number = duration / 1000;
-        unit_code = "S";
-    } else if duration < 100 * 60 * 1000 {
-        number = duration / (60 * 1000);
-        unit_code = "M";
-    } else if duration < 100 * 60 * 60 * 1000 {
-        number = duration / (60 * 60 * 1000);
-        unit_code = "H";
+    let duration = duration.into();
+    let (number, unit_code) = if duration <= 0 {
+        (0, "")
     } else {
-        number = duration / (24 * 60 * 60 * 1000);
-        unit_code = "D";
-    }
+        let (unit, unit_code) = UNITS
+            .into_iter()
+            .find(|(unit, _)| duration < 100 * unit)
+            .unwrap_or(UNITS[UNITS.len() - 1]);
+        ((duration / unit).min(99), unit_code)
+    };
     printer.print_uint::<_, 2>(number as u8).unwrap();
     printer.print_str(unit_code).unwrap();
 }
@@ -12,13 +12,19 @@ pub enum SelectionResponse {
     Timeout,
 }
 
+mod choose_date;
+mod choose_signed;
 mod choose_time;
 mod duration;
 mod selection;
+mod weekdays;
 
+pub use choose_date::choose_date;
+pub use choose_signed::choose_signed;
 pub use choose_time::choose_time;
 pub use duration::{choose_duration, print_duration};
 pub use selection::{selection, SelectionState};
+pub use weekdays::{choose_weekdays, SCHEDULE_WEEKDAY_CHOICES};
 
 pub trait DisplayImage {
     fn display_image(&self, canvas: ImageRegionMut);
@@ -0,0 +1,83 @@
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::ui::display::run_blinking_led_matrix;
+use crate::Weekdays;
+use greaheisl_async::{AccessTiming, Timer};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+use super::{SelectionResponse, BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT};
+
+/// the choices offered for scheduled timers
+pub const SCHEDULE_WEEKDAY_CHOICES: [Weekdays; 10] = [
+    Weekdays::full(),
+    Weekdays::workdays(),
+    Weekdays::weekend(),
+    Weekdays::Monday,
+    Weekdays::Tuesday,
+    Weekdays::Wednesday,
+    Weekdays::Thursday,
+    Weekdays::Friday,
+    Weekdays::Saturday,
+    Weekdays::Sunday,
+];
+
+/// abbreviations of the days of the week, starting with Monday
+const WEEKDAY_NAMES: [&str; 7] = ["MO", "DI", "MI", "DO", "FR", "SA", "SO"];
+
+pub fn print_weekdays(matrix: ImageRegionMut, weekdays: Weekdays) {
+    let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
+    let text = match weekdays {
+        x if x.is_full() => "TGL",              // "täglich" = daily
+        x if x == Weekdays::workdays() => "WT", // "Werktage" = workdays
+        x if x == Weekdays::weekend() => "WE",  // "Wochenende" = weekend
+        x if x.bits().count_ones() == 1 => WEEKDAY_NAMES[x.bits().trailing_zeros() as usize],
+        _ => "IND", // "individuell" = some other combination
+    };
+    printer.print_str(text).unwrap();
+}
+
+/// lets the user pick one of the given `choices`
+///
+/// If the current setting is not among the choices,
+/// the selection starts with the first choice.
+pub async fn choose_weekdays(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    setting: &mut Weekdays,
+    choices: &[Weekdays],
+) -> SelectionResponse {
+    let mut index = choices.iter().position(|c| c == setting).unwrap_or(0);
+    loop {
+        let mut blink_matrices = [Image::<12, 8, 3>::zero(), Image::<12, 8, 3>::zero()];
+        print_weekdays(blink_matrices[0].as_region_mut(), choices[index]);
+        run_blinking_led_matrix(
+            sys,
+            &blink_matrices,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                index = (index + choices.len() - 1) % choices.len();
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                index = (index + 1) % choices.len();
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                run_blinking_led_matrix(sys, &blink_matrices, BLINK_DELAY_CONFIRM_VALUE, async {
+                    Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+                })
+                .await;
+                *setting = choices[index];
+                break SelectionResponse::Ok; // successful setting
+            }
+            _ => {}
+        }
+    }
+}
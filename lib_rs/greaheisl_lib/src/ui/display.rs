@@ -1,4 +1,5 @@
 use crate::system::{AccessLedMatrix, AccessRtc};
+use crate::timezone::TimeZone;
 use bitvec::{order::Msb0, view::BitView};
 use core::cell::Cell;
 use core::future::Future;
@@ -19,16 +20,6 @@ pub fn display_clock(imat: &mut impl ImageMatrixViewMut, hours: u8, minutes: u8)
 }
 */
 
-pub fn with_led_printer(
-    sys: &impl AccessLedMatrix,
-    f: impl FnOnce(&mut TextLinePrinter<&mut Image<12, 8, 3>, &FitzlFontNarrowNum>),
-) {
-    let mut imat = Image::<12, 8, 3>::zero();
-    let mut printer = TextLinePrinter::new(&mut imat, &FitzlFontNarrowNum {});
-    f(&mut printer);
-    sys.set_led_matrix(&imat.0.into_inner());
-}
-
 pub async fn run_blinking_led_matrix<T>(
     sys: &(impl AccessTiming + AccessLedMatrix),
     matrices: &[Image<12, 8, 3>; 2],
@@ -56,8 +47,8 @@ pub async fn run_blinking_led_matrix<T>(
     .0
 }
 
-pub fn show_clock(sys: &(impl AccessLedMatrix + AccessRtc)) {
-    let rtc_time = sys.get_rtc();
+pub fn show_clock(sys: &(impl AccessLedMatrix + AccessRtc), time_zone: &TimeZone) {
+    let rtc_time = time_zone.to_local(&sys.get_rtc());
     let mut imat = Image::<12, 8, 3>::zero();
     {
         let mut printer = TextLinePrinter::new(&mut imat, &FitzlFontNarrowNum {});
@@ -70,3 +61,31 @@ pub fn show_clock(sys: &(impl AccessLedMatrix + AccessRtc)) {
     imat_bits[0..6].copy_from_bitslice(&second_bits.view_bits::<Msb0>()[26..32]);
     sys.set_led_matrix(&imat.0.into_inner());
 }
+
+/// shows the reading of the analog sensor, given in tenths of its unit
+///
+/// The whole units are printed, with `M` in front of negative readings.
+/// The tenths are shown as a row of dots at the bottom.
+/// A missing reading is shown as `FEH` ("Fehler" = error).
+pub fn show_analog_value(sys: &impl AccessLedMatrix, value: Option<i16>) {
+    let mut imat = Image::<12, 8, 3>::zero();
+    let mut printer = TextLinePrinter::new(&mut imat, &FitzlFontNarrowNum {});
+    let Some(value) = value else {
+        printer.print_str("FEH").unwrap();
+        sys.set_led_matrix(&imat.0.into_inner());
+        return;
+    };
+    if value < 0 {
+        printer.print_str("M").unwrap();
+    }
+    let units = value.unsigned_abs() / 10;
+    match units {
+        0..=9 => printer.print_uint::<_, 1>(units),
+        10..=99 => printer.print_uint::<_, 2>(units),
+        _ => printer.print_uint::<_, 3>(units),
+    }
+    .unwrap();
+    let tenths = (value.unsigned_abs() % 10) as usize;
+    imat.row_bits_mut(7)[0..tenths].fill(true);
+    sys.set_led_matrix(&imat.0.into_inner());
+}
@@ -1,5 +1,6 @@
 use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
-use crate::system::{AccessLedMatrix, AccessRtc};
+use crate::system::{AccessAnalogInput, AccessLedMatrix, AccessRtc};
+use crate::timezone::TimeZone;
 use core::cell::Cell;
 use enum_iterator::{next_cycle, previous_cycle, Sequence};
 use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
@@ -7,11 +8,12 @@ use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
 use greaheisl_async::join2;
 use greaheisl_async::{AccessTiming, Timer};
 
-use super::display::show_clock;
+use super::display::{show_analog_value, show_clock};
 
 async fn idly_show_clock(
     sys: &(impl AccessLedMatrix + AccessTiming + AccessLedMatrix + AccessRtc),
     btns: &SysButtonProcessor,
+    time_zone: &TimeZone,
     longer: bool,
 ) {
     let timeout = match longer {
@@ -26,7 +28,37 @@ async fn idly_show_clock(
         },
         async {
             while !stop_signal.get() {
-                show_clock(sys);
+                show_clock(sys, time_zone);
+                let update_timer = Timer::new(sys, 1000);
+                while update_timer.yield_if_time_left().await {
+                    if stop_signal.get() {
+                        break;
+                    }
+                }
+            }
+        },
+    )
+    .await;
+}
+
+async fn idly_show_sensor(
+    sys: &(impl AccessLedMatrix + AccessTiming + AccessAnalogInput),
+    btns: &SysButtonProcessor,
+    longer: bool,
+) {
+    let timeout = match longer {
+        true => 5000,
+        false => 20000,
+    };
+    let stop_signal = Cell::new(false);
+    join2(
+        async {
+            wait_button_press_or_timeout(sys, btns, timeout).await;
+            stop_signal.set(true);
+        },
+        async {
+            while !stop_signal.get() {
+                show_analog_value(sys, sys.get_analog_value());
                 let update_timer = Timer::new(sys, 1000);
                 while update_timer.yield_if_time_left().await {
                     if stop_signal.get() {
@@ -68,6 +100,8 @@ async fn idly_show_flower(
 #[derive(Clone, Copy, Debug, PartialEq, Sequence)]
 pub enum IdleScenes {
     Clock,
+    /// the reading of the analog sensor; skipped if there is none
+    Sensor,
     Flower,
 }
 
@@ -86,21 +120,36 @@ impl IdleDisplay {
         self.idle_scene
     }
 
+    /// cycles through the idle scenes; the clock shows local time in `time_zone`
     pub async fn run(
         &mut self,
-        sys: &(impl AccessLedMatrix + AccessTiming + AccessRtc),
+        sys: &(impl AccessLedMatrix + AccessTiming + AccessRtc + AccessAnalogInput),
         btns: &SysButtonProcessor,
+        time_zone: &TimeZone,
     ) {
         let mut longer = false;
+        let mut backwards = false;
         loop {
             match self.idle_scene {
-                IdleScenes::Clock => idly_show_clock(sys, &btns, longer).await,
-                IdleScenes::Flower => idly_show_flower(sys, &btns, longer).await,
+                IdleScenes::Clock => idly_show_clock(sys, btns, time_zone, longer).await,
+                IdleScenes::Sensor if !sys.has_analog_input() => {
+                    // move on in the direction the user is going
+                    self.idle_scene = if backwards {
+                        previous_cycle(&self.idle_scene).unwrap()
+                    } else {
+                        next_cycle(&self.idle_scene).unwrap()
+                    };
+                    continue;
+                }
+                IdleScenes::Sensor => idly_show_sensor(sys, btns, longer).await,
+                IdleScenes::Flower => idly_show_flower(sys, btns, longer).await,
             }
+            backwards = false;
             match btns.event() {
                 ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
                     self.idle_scene = previous_cycle(&self.idle_scene).unwrap();
                     longer = true;
+                    backwards = true;
                 }
                 ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
                     self.idle_scene = next_cycle(&self.idle_scene).unwrap();
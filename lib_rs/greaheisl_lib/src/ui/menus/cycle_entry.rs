@@ -0,0 +1,154 @@
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::CycleOutEntry;
+use greaheisl_async::{AccessTiming, DurationMillis};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::ImageRegionMut;
+use greaheisl_button_processor::ButtonEvent;
+
+use crate::ui::components::{
+    choose_time, selection, DisplayImage, SelectionResponse, SelectionState,
+};
+
+use enum_iterator::{next_cycle, previous_cycle, Sequence};
+
+#[derive(Default, Clone, Copy, PartialEq, Sequence)]
+enum MenuState {
+    #[default]
+    OnDuration,
+    OffDuration,
+    Anchor,
+    WindowStart,
+    WindowEnd,
+}
+
+impl SelectionState for MenuState {
+    type SelectionItem = Self;
+
+    fn item(&self) -> &Self::SelectionItem {
+        self
+    }
+
+    fn next(&mut self) -> bool {
+        let Some(new_value) = next_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let Some(new_value) = previous_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+}
+
+impl DisplayImage for MenuState {
+    fn display_image(&self, canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+        match self {
+            MenuState::OnDuration => {
+                printer.print_str("EIN").unwrap();
+            }
+            MenuState::OffDuration => {
+                printer.print_str("AUS").unwrap();
+            }
+            MenuState::Anchor => {
+                printer.print_str("PHA").unwrap(); // "Phase"
+            }
+            MenuState::WindowStart => {
+                printer.print_str("VON").unwrap(); // "von" = from
+            }
+            MenuState::WindowEnd => {
+                printer.print_str("BIS").unwrap(); // "bis" = until
+            }
+        }
+    }
+}
+
+/// edits the cycle timer of a relay
+///
+/// The durations are set as hours and minutes, like a time of day,
+/// since a cycle needs finer steps than the durations of the other timers.
+/// Without a window, `VON` and `BIS` both show midnight.
+/// Setting them to the same time removes the window.
+pub async fn menu_cycle_entry(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    settings: &mut CycleOutEntry,
+) -> bool {
+    let mut current_item = MenuState::default();
+    loop {
+        selection(sys, btns, &mut current_item).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => match current_item {
+                MenuState::OnDuration | MenuState::OffDuration => {
+                    let duration = if current_item == MenuState::OnDuration {
+                        &mut settings.on_duration
+                    } else {
+                        &mut settings.off_duration
+                    };
+                    let SelectionResponse::Back = choose_hours_minutes(sys, btns, duration).await
+                    else {
+                        break true;
+                    };
+                }
+                MenuState::Anchor => {
+                    let response = choose_time(
+                        sys,
+                        btns,
+                        &mut settings.anchor_hour,
+                        &mut settings.anchor_minute,
+                    )
+                    .await;
+                    let SelectionResponse::Back = response else {
+                        break true;
+                    };
+                }
+                MenuState::WindowStart | MenuState::WindowEnd => {
+                    let mut window = settings.window.clone().unwrap_or_default();
+                    let (hour, minute) = if current_item == MenuState::WindowStart {
+                        (&mut window.start_hour, &mut window.start_minute)
+                    } else {
+                        (&mut window.end_hour, &mut window.end_minute)
+                    };
+                    match choose_time(sys, btns, hour, minute).await {
+                        SelectionResponse::Back => {}
+                        SelectionResponse::Ok => {
+                            let is_whole_day = (window.start_hour, window.start_minute)
+                                == (window.end_hour, window.end_minute);
+                            settings.window = (!is_whole_day).then_some(window);
+                            break true;
+                        }
+                        SelectionResponse::Timeout => break true,
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+const MILLIS_PER_MINUTE: DurationMillis = 60 * 1000;
+
+/// lets the user choose a duration below 24 hours with minute resolution
+async fn choose_hours_minutes(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    duration: &mut DurationMillis,
+) -> SelectionResponse {
+    let minutes = (*duration / MILLIS_PER_MINUTE).clamp(0, 24 * 60 - 1);
+    let mut hour = (minutes / 60) as u8;
+    let mut minute = (minutes % 60) as u8;
+    let response = choose_time(sys, btns, &mut hour, &mut minute).await;
+    if let SelectionResponse::Ok = response {
+        *duration = (hour as DurationMillis * 60 + minute as DurationMillis) * MILLIS_PER_MINUTE;
+    }
+    response
+}
@@ -0,0 +1,136 @@
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::{AccessLedMatrix, AccessRtc};
+use crate::timezone::TimeZone;
+use crate::DatedOutEntry;
+use greaheisl_async::AccessTiming;
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::ImageRegionMut;
+use greaheisl_button_processor::ButtonEvent;
+
+use crate::ui::components::{
+    choose_date, choose_duration, choose_time, selection, DisplayImage, SelectionResponse,
+    SelectionState,
+};
+
+use enum_iterator::{next_cycle, previous_cycle, Sequence};
+
+#[derive(Default, Clone, Copy, Sequence)]
+enum MenuState {
+    #[default]
+    Duration,
+    StartTime,
+    Date,
+}
+
+impl SelectionState for MenuState {
+    type SelectionItem = Self;
+
+    fn item(&self) -> &Self::SelectionItem {
+        self
+    }
+
+    fn next(&mut self) -> bool {
+        let Some(new_value) = next_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let Some(new_value) = previous_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+}
+
+impl DisplayImage for MenuState {
+    fn display_image(&self, canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+        match self {
+            MenuState::Duration => {
+                printer.print_str("DAU").unwrap();
+            }
+            MenuState::StartTime => {
+                printer.print_str("STA").unwrap();
+            }
+            MenuState::Date => {
+                printer.print_str("DAT").unwrap();
+            }
+        }
+    }
+}
+
+/// edits the timer starting once on a given date
+///
+/// An empty entry is initialized with today's date in `time_zone`.
+/// Setting the duration to zero removes the entry.
+pub async fn menu_dated_entry(
+    sys: &(impl AccessLedMatrix + AccessTiming + AccessRtc),
+    btns: &SysButtonProcessor,
+    settings: &mut Option<DatedOutEntry>,
+    time_zone: &TimeZone,
+) -> bool {
+    let mut entry = settings.clone().unwrap_or_else(|| {
+        let today = time_zone.to_local(&sys.get_rtc());
+        DatedOutEntry {
+            year: today.year,
+            month: today.month,
+            day: today.day,
+            start_hour: 0,
+            start_minute: 0,
+            duration: 0,
+        }
+    });
+    let mut current_item = MenuState::default();
+    loop {
+        selection(sys, btns, &mut current_item).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => match current_item {
+                MenuState::Duration => {
+                    let old_duration = entry.duration;
+                    if choose_duration(sys, btns, &mut entry.duration).await {
+                        if entry.duration != old_duration {
+                            *settings = if entry.duration > 0 {
+                                Some(entry)
+                            } else {
+                                None
+                            };
+                        }
+                        break true;
+                    }
+                }
+                MenuState::StartTime => {
+                    let response =
+                        choose_time(sys, btns, &mut entry.start_hour, &mut entry.start_minute)
+                            .await;
+                    if let SelectionResponse::Ok = response {
+                        *settings = Some(entry);
+                        break true;
+                    }
+                    let SelectionResponse::Back = response else {
+                        break true;
+                    };
+                }
+                MenuState::Date => {
+                    let response =
+                        choose_date(sys, btns, &mut entry.year, &mut entry.month, &mut entry.day)
+                            .await;
+                    if let SelectionResponse::Ok = response {
+                        *settings = Some(entry);
+                        break true;
+                    }
+                    let SelectionResponse::Back = response else {
+                        break true;
+                    };
+                }
+            },
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::event_log::{Cause, Event, EventLog, LogEntry};
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::{AccessLedMatrix, RtcTime};
+use crate::timezone::TimeZone;
+use crate::ui::components::{selection, DisplayImage, SelectionState, MENU_TIMEOUT};
+use greaheisl_async::{AccessTiming, DurationMillis};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+/// how long the time of day and the date of an entry are shown in turn
+const TIME_SCREEN_DURATION: DurationMillis = 2000;
+
+/// the letter standing for a cause, named after the menu of the timer if there is one
+fn cause_letter(cause: Cause) -> &'static str {
+    match cause {
+        Cause::Immediate => "J",
+        Cause::Scheduled { .. } => "S",
+        Cause::Dated => "D",
+        Cause::Cycle => "Z",
+        Cause::Thermostat => "T",
+        Cause::Input => "K", // "Kontakt" = contact
+        Cause::Override => "M",
+        Cause::Interlock => "V", // "Verriegelung" = interlock
+    }
+}
+
+impl DisplayImage for LogEntry {
+    fn display_image(&self, mut canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas.as_region_mut(), FitzlFontNarrowNum {});
+        let (relay, switched, cause) = match self.event {
+            Event::RelayOn { relay, cause } => (relay, "E", cause),
+            Event::RelayOff { relay, cause } => (relay, "A", cause),
+            Event::ClockSet => {
+                printer.print_str("UHR").unwrap();
+                return;
+            }
+            Event::SettingsCommitted => {
+                printer.print_str("KON").unwrap(); // "Konfiguration" = settings
+                return;
+            }
+        };
+        printer.print_uint::<_, 1>(relay + 1).unwrap();
+        printer.print_str(switched).unwrap();
+        printer.print_str(cause_letter(cause)).unwrap();
+        if let Cause::Scheduled { slot } = cause {
+            canvas.row_bits_mut(7)[0..=slot as usize].fill(true);
+        }
+    }
+}
+
+/// browses the entries of the log, starting with the most recent one
+struct EventLogState<'a> {
+    event_log: &'a EventLog,
+    entry: LogEntry,
+}
+
+impl SelectionState for EventLogState<'_> {
+    type SelectionItem = LogEntry;
+
+    fn item(&self) -> &Self::SelectionItem {
+        &self.entry
+    }
+
+    /// goes back in time
+    fn next(&mut self) -> bool {
+        let older = self.entry.sequence.checked_sub(1);
+        let Some(entry) = older.and_then(|sequence| self.event_log.get(sequence)) else {
+            return false;
+        };
+        self.entry = entry;
+        true
+    }
+
+    /// goes forward in time
+    fn previous(&mut self) -> bool {
+        let Some(entry) = self.event_log.get(self.entry.sequence + 1) else {
+            return false;
+        };
+        self.entry = entry;
+        true
+    }
+}
+
+/// lets the user browse the event log
+///
+/// `Enter` shows the local time of the selected entry.
+/// An empty log is shown as `LEE` ("leer" = empty).
+pub async fn menu_event_log(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    event_log: &EventLog,
+    time_zone: &TimeZone,
+) -> bool {
+    let Some(entry) = event_log.newest() else {
+        let mut matrix = Image::<12, 8, 3>::zero();
+        let mut printer = TextLinePrinter::new(matrix.as_region_mut(), FitzlFontNarrowNum {});
+        printer.print_str("LEE").unwrap();
+        sys.set_led_matrix(&matrix.0.into_inner());
+        wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT).await;
+        return matches!(btns.event(), ButtonEvent::None); // timeout => exit all menus
+    };
+    let mut state = EventLogState { event_log, entry };
+    loop {
+        selection(sys, btns, &mut state).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                let time = time_zone.to_local(&state.entry.time);
+                if show_entry_time(sys, btns, &time).await {
+                    break true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// shows the time of day and the date, underlined, in turn until a button is pressed
+async fn show_entry_time(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    time: &RtcTime,
+) -> bool {
+    let mut show_date = false;
+    let mut time_shown = 0;
+    loop {
+        let (left, right) = if show_date {
+            (time.day, time.month)
+        } else {
+            (time.hour, time.minute)
+        };
+        let mut matrix = Image::<12, 8, 3>::zero();
+        let mut printer = TextLinePrinter::new(matrix.as_region_mut(), FitzlFontNarrowNum {});
+        printer.print_uint::<_, 2>(left).unwrap();
+        printer.skip(1);
+        printer.print_uint::<_, 2>(right).unwrap();
+        if show_date {
+            matrix.row_bits_mut(7).fill(true);
+        }
+        sys.set_led_matrix(&matrix.0.into_inner());
+        wait_button_press_or_timeout(sys, btns, TIME_SCREEN_DURATION).await;
+        match btns.event() {
+            ButtonEvent::None => {
+                time_shown += TIME_SCREEN_DURATION;
+                if time_shown >= MENU_TIMEOUT {
+                    break true; // timeout => exit all menus
+                }
+                show_date = !show_date;
+            }
+            ButtonEvent::Press(_) => break false, // back to the entries
+            _ => {}
+        }
+    }
+}
@@ -3,19 +3,25 @@ use core::cell::Cell;
 use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
 use crate::system::AccessLedMatrix;
 use crate::ui::components::{choose_duration, print_duration, MENU_TIMEOUT};
-use crate::ui::display::with_led_printer;
 use crate::ImmediateOutEntry;
 use greaheisl_async::join2;
 use greaheisl_async::{AccessTiming, Timer};
 use greaheisl_async::{DurationMillis, InstantMillis};
-use greaheisl_bitvecimg::font::typeset::TextPrinterTrait;
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
 use greaheisl_bitvecimg::{BitVecImgViewMut, Image};
 use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
 
+/// shows the time left of the immediate timer and lets the user change it
+///
+/// A bar in the lower right corner indicates that the relay does not
+/// follow its timers right now, because a transition is deferred
+/// by the wear limits of the relay.
 pub async fn menu_immediate_out(
     sys: &(impl AccessLedMatrix + AccessTiming),
     btns: &SysButtonProcessor,
     settings: &mut Option<ImmediateOutEntry>,
+    is_deferred: impl Fn() -> bool,
 ) -> bool {
     loop {
         let stop_signal = Cell::new(false);
@@ -27,13 +33,18 @@ pub async fn menu_immediate_out(
             async {
                 while !stop_signal.get() {
                     let time_left = get_time_left(settings, sys.get_instant());
+                    let mut matrix = Image::<12, 8, 3>::zero();
                     if time_left > 0 {
-                        let mut matrix = Image::<12, 8, 3>::zero();
                         print_duration(matrix.as_region_mut(), time_left);
-                        sys.set_led_matrix(&matrix.0.into_inner());
                     } else {
-                        with_led_printer(sys, |printer| printer.print_str("AUS").unwrap());
+                        let mut printer =
+                            TextLinePrinter::new(matrix.as_region_mut(), FitzlFontNarrowNum {});
+                        printer.print_str("AUS").unwrap();
                     }
+                    if is_deferred() {
+                        matrix.row_bits_mut(7)[9..12].fill(true);
+                    }
+                    sys.set_led_matrix(&matrix.0.into_inner());
                     let update_timer = Timer::new(sys, 1000);
                     while update_timer.yield_if_time_left().await {
                         if stop_signal.get() {
@@ -0,0 +1,173 @@
+use crate::solar::Location;
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::timezone::{DstRule, TimeZone};
+use crate::ui::display::run_blinking_led_matrix;
+use greaheisl_async::{AccessTiming, Timer};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+use crate::ui::components::{
+    choose_signed, selection, DisplayImage, SelectionResponse, SelectionState,
+    BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT,
+};
+
+use enum_iterator::{next_cycle, previous_cycle, Sequence};
+
+#[derive(Default, Clone, Copy, Sequence)]
+enum MenuState {
+    #[default]
+    Latitude,
+    Longitude,
+    UtcOffset,
+    Dst,
+}
+
+impl SelectionState for MenuState {
+    type SelectionItem = Self;
+
+    fn item(&self) -> &Self::SelectionItem {
+        self
+    }
+
+    fn next(&mut self) -> bool {
+        let Some(new_value) = next_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let Some(new_value) = previous_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+}
+
+impl DisplayImage for MenuState {
+    fn display_image(&self, canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+        match self {
+            MenuState::Latitude => {
+                printer.print_str("BRE").unwrap(); // "Breite" = latitude
+            }
+            MenuState::Longitude => {
+                printer.print_str("LAE").unwrap(); // "Länge" = longitude
+            }
+            MenuState::UtcOffset => {
+                printer.print_str("UTC").unwrap();
+            }
+            MenuState::Dst => {
+                printer.print_str("SOZ").unwrap(); // "Sommerzeit" = daylight saving time
+            }
+        }
+    }
+}
+
+/// lets the user set the location and the time zone of the device
+///
+/// Latitude and longitude are set in whole degrees, which puts sunrise
+/// and sunset off by a few minutes at most; the difference between
+/// standard time and UTC is set in whole hours.
+/// Values that are not changed keep their precision.
+pub async fn menu_location(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    location: &mut Location,
+    time_zone: &mut TimeZone,
+) -> bool {
+    let mut current_item = MenuState::default();
+    loop {
+        selection(sys, btns, &mut current_item).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                // "Süd"/"Nord", "West"/"Ost"; the clock is ahead of UTC east of Greenwich
+                let (value, unit, max, letters) = match current_item {
+                    MenuState::Latitude => (&mut location.latitude, 100, 90, ["S", "N"]),
+                    MenuState::Longitude => (&mut location.longitude, 100, 180, ["W", "O"]),
+                    MenuState::UtcOffset => (&mut time_zone.utc_offset, 60, 14, ["W", "O"]),
+                    MenuState::Dst => {
+                        let SelectionResponse::Back =
+                            choose_dst_rule(sys, btns, &mut time_zone.dst).await
+                        else {
+                            break true;
+                        };
+                        continue;
+                    }
+                };
+                let mut rounded = round_div(*value, unit);
+                let response = choose_signed(sys, btns, &mut rounded, max, 1, letters).await;
+                if rounded != round_div(*value, unit) {
+                    *value = rounded * unit;
+                }
+                let SelectionResponse::Back = response else {
+                    break true;
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+fn print_dst_rule(matrix: ImageRegionMut, rule: DstRule) {
+    let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
+    let text = match rule {
+        DstRule::None => "AUS",
+        DstRule::Eu => "EU",
+        DstRule::Us => "US",
+    };
+    printer.print_str(text).unwrap();
+}
+
+/// lets the user pick the rule for daylight saving time
+///
+/// The current rule blinks right away.
+async fn choose_dst_rule(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    setting: &mut DstRule,
+) -> SelectionResponse {
+    let mut rule = *setting;
+    loop {
+        let mut blink_matrices = [Image::<12, 8, 3>::zero(), Image::<12, 8, 3>::zero()];
+        print_dst_rule(blink_matrices[0].as_region_mut(), rule);
+        run_blinking_led_matrix(
+            sys,
+            &blink_matrices,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                rule = previous_cycle(&rule).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                rule = next_cycle(&rule).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                run_blinking_led_matrix(sys, &blink_matrices, BLINK_DELAY_CONFIRM_VALUE, async {
+                    Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+                })
+                .await;
+                *setting = rule;
+                break SelectionResponse::Ok;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// divides, rounding to the nearest integer
+pub(super) fn round_div(value: i16, divisor: i16) -> i16 {
+    (value + value.signum() * divisor / 2) / divisor
+}
@@ -1,21 +1,39 @@
+use crate::event_log::EventLog;
+use crate::statistics::RuntimeStats;
 use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
-use crate::system::{AccessLedMatrix, NUM_RELAYS};
-use crate::{Settings, MAX_SCHEDULED_ENTRIES};
+use crate::system::{AccessLedMatrix, AccessRtc};
+use crate::{OutputStatus, Settings};
+use core::cell::RefCell;
 use greaheisl_async::AccessTiming;
 use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
 use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
 use greaheisl_bitvecimg::ImageRegionMut;
 use greaheisl_button_processor::ButtonEvent;
 
+use super::cycle_entry::menu_cycle_entry;
+use super::dated_entry::menu_dated_entry;
+use super::event_log::menu_event_log;
 use super::immediate_out::menu_immediate_out;
+use super::location::menu_location;
+use super::override_mode::menu_override_mode;
 use super::scheduled_entry::menu_scheduled_entry;
+use super::statistics::menu_statistics;
+use super::thermostat::menu_thermostat;
 
 use crate::ui::components::{selection, DisplayImage, SelectionState};
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)] // named after the fields of `Settings`
 enum MainMenuItem {
     ImmediateOut { channel: usize },
     ScheduledOut { channel: usize, i_entry: usize },
+    DatedOut { channel: usize },
+    CycleOut { channel: usize },
+    Thermostat { channel: usize },
+    OverrideMode { channel: usize },
+    Statistics { channel: usize },
+    Location,
+    EventLog,
 }
 
 impl DisplayImage for MainMenuItem {
@@ -32,114 +50,164 @@ impl DisplayImage for MainMenuItem {
                 printer.skip(2);
                 printer.print_uint::<_, 1>(i_entry + 1).unwrap();
             }
+            MainMenuItem::DatedOut { channel } => {
+                printer.print_str("D").unwrap();
+                printer.print_uint::<_, 1>(channel + 1).unwrap();
+            }
+            MainMenuItem::CycleOut { channel } => {
+                printer.print_str("Z").unwrap(); // "Zyklus" = cycle
+                printer.print_uint::<_, 1>(channel + 1).unwrap();
+            }
+            MainMenuItem::Thermostat { channel } => {
+                printer.print_str("T").unwrap(); // "Thermostat"
+                printer.print_uint::<_, 1>(channel + 1).unwrap();
+            }
+            MainMenuItem::OverrideMode { channel } => {
+                printer.print_str("M").unwrap();
+                printer.print_uint::<_, 1>(channel + 1).unwrap();
+            }
+            MainMenuItem::Statistics { channel } => {
+                printer.print_str("L").unwrap(); // "Laufzeit" = running time
+                printer.print_uint::<_, 1>(channel + 1).unwrap();
+            }
+            MainMenuItem::Location => {
+                printer.print_str("ORT").unwrap(); // "Ort" = location
+            }
+            MainMenuItem::EventLog => {
+                printer.print_str("PRO").unwrap(); // "Protokoll" = log
+            }
         }
     }
 }
 
-impl SelectionState for MainMenuItem {
-    type SelectionItem = MainMenuItem;
-
-    fn item(&self) -> &Self::SelectionItem {
-        return self;
-    }
-
-    fn next(&mut self) -> bool {
+impl MainMenuItem {
+    /// position in the list of menu items
+    fn index(&self, num_relays: usize, num_slots: usize) -> usize {
         match *self {
-            MainMenuItem::ImmediateOut { channel } => {
-                if channel < NUM_RELAYS - 1 {
-                    *self = MainMenuItem::ImmediateOut {
-                        channel: channel + 1,
-                    };
-                    true
-                } else {
-                    *self = MainMenuItem::ScheduledOut {
-                        channel: 0,
-                        i_entry: 0,
-                    };
-                    true
-                }
-            }
+            MainMenuItem::ImmediateOut { channel } => channel,
             MainMenuItem::ScheduledOut { channel, i_entry } => {
-                if i_entry < MAX_SCHEDULED_ENTRIES - 1 {
-                    *self = MainMenuItem::ScheduledOut {
-                        channel,
-                        i_entry: i_entry + 1,
-                    };
-                    true
-                } else {
-                    if channel < NUM_RELAYS - 1 {
-                        *self = MainMenuItem::ScheduledOut {
-                            channel: channel + 1,
-                            i_entry: 0,
-                        };
-                        true
-                    } else {
-                        *self = MainMenuItem::ImmediateOut { channel: 0 };
-                        true
-                    }
-                }
+                num_relays + channel * num_slots + i_entry
             }
+            MainMenuItem::DatedOut { channel } => num_relays * (1 + num_slots) + channel,
+            MainMenuItem::CycleOut { channel } => num_relays * (2 + num_slots) + channel,
+            MainMenuItem::Thermostat { channel } => num_relays * (3 + num_slots) + channel,
+            MainMenuItem::OverrideMode { channel } => num_relays * (4 + num_slots) + channel,
+            MainMenuItem::Statistics { channel } => num_relays * (5 + num_slots) + channel,
+            MainMenuItem::Location => num_relays * (6 + num_slots),
+            MainMenuItem::EventLog => num_relays * (6 + num_slots) + 1,
         }
     }
 
-    fn previous(&mut self) -> bool {
-        match *self {
-            MainMenuItem::ImmediateOut { channel } => {
-                if channel != 0 {
-                    *self = MainMenuItem::ImmediateOut {
-                        channel: channel - 1,
-                    };
-                    true
-                } else {
-                    let channel = NUM_RELAYS - 1;
-                    *self = MainMenuItem::ScheduledOut {
-                        channel,
-                        i_entry: MAX_SCHEDULED_ENTRIES - 1,
-                    };
-                    true
-                }
+    /// inverse of [`Self::index`]
+    fn from_index(index: usize, num_relays: usize, num_slots: usize) -> Self {
+        let num_scheduled = num_relays * num_slots;
+        if index < num_relays {
+            MainMenuItem::ImmediateOut { channel: index }
+        } else if index < num_relays + num_scheduled {
+            let index = index - num_relays;
+            MainMenuItem::ScheduledOut {
+                channel: index / num_slots,
+                i_entry: index % num_slots,
             }
-            MainMenuItem::ScheduledOut { channel, i_entry } => {
-                if channel != 0 {
-                    if i_entry != 0 {
-                        *self = MainMenuItem::ScheduledOut {
-                            channel,
-                            i_entry: i_entry - 1,
-                        };
-                        true
-                    } else {
-                        let channel = channel - 1;
-                        *self = MainMenuItem::ScheduledOut {
-                            channel,
-                            i_entry: MAX_SCHEDULED_ENTRIES - 1,
-                        };
-                        true
-                    }
-                } else {
-                    let channel = NUM_RELAYS - 1;
-                    *self = MainMenuItem::ImmediateOut { channel };
-                    true
-                }
+        } else if index < 2 * num_relays + num_scheduled {
+            MainMenuItem::DatedOut {
+                channel: index - num_relays - num_scheduled,
             }
+        } else if index < 3 * num_relays + num_scheduled {
+            MainMenuItem::CycleOut {
+                channel: index - 2 * num_relays - num_scheduled,
+            }
+        } else if index < 4 * num_relays + num_scheduled {
+            MainMenuItem::Thermostat {
+                channel: index - 3 * num_relays - num_scheduled,
+            }
+        } else if index < 5 * num_relays + num_scheduled {
+            MainMenuItem::OverrideMode {
+                channel: index - 4 * num_relays - num_scheduled,
+            }
+        } else if index < 6 * num_relays + num_scheduled {
+            MainMenuItem::Statistics {
+                channel: index - 5 * num_relays - num_scheduled,
+            }
+        } else if index < 6 * num_relays + num_scheduled + 1 {
+            MainMenuItem::Location
+        } else {
+            MainMenuItem::EventLog
         }
     }
 }
 
-pub async fn menu_main(
-    sys: &(impl AccessLedMatrix + AccessTiming),
+/// the current menu item together with the dimensions of the menu
+struct MainMenuState {
+    item: MainMenuItem,
+    /// number of relays offered in the menu
+    num_relays: usize,
+    /// number of scheduled entries per relay
+    num_slots: usize,
+}
+
+impl MainMenuState {
+    fn num_items(&self) -> usize {
+        self.num_relays * (6 + self.num_slots) + 2
+    }
+}
+
+impl SelectionState for MainMenuState {
+    type SelectionItem = MainMenuItem;
+
+    fn item(&self) -> &Self::SelectionItem {
+        &self.item
+    }
+
+    fn next(&mut self) -> bool {
+        let index = self.item.index(self.num_relays, self.num_slots);
+        let index = (index + 1) % self.num_items();
+        self.item = MainMenuItem::from_index(index, self.num_relays, self.num_slots);
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let index = self.item.index(self.num_relays, self.num_slots);
+        let index = (index + self.num_items() - 1) % self.num_items();
+        self.item = MainMenuItem::from_index(index, self.num_relays, self.num_slots);
+        true
+    }
+}
+
+/// the main menu
+///
+/// Only the first `relay_count` relays are offered.
+pub async fn menu_main<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessLedMatrix + AccessTiming + AccessRtc),
     btns: &SysButtonProcessor,
-    settings: &mut Settings,
+    settings: &mut Settings<NUM_RELAYS, NUM_SLOTS>,
+    relay_count: usize,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    statistics: &RefCell<RuntimeStats<NUM_RELAYS>>,
 ) {
-    let mut current_item = MainMenuItem::ImmediateOut { channel: 0 };
+    let mut current_item = MainMenuState {
+        item: MainMenuItem::ImmediateOut { channel: 0 },
+        num_relays: relay_count.clamp(1, NUM_RELAYS),
+        num_slots: NUM_SLOTS,
+    };
     loop {
         selection(sys, btns, &mut current_item).await;
         match btns.event() {
             ButtonEvent::Press(ButtonFlags::Escape) => {
                 break;
             } // user wants to leave
-            ButtonEvent::Press(ButtonFlags::Enter) => match current_item {
+            ButtonEvent::Press(ButtonFlags::Enter) => match current_item.item {
                 MainMenuItem::ImmediateOut { channel } => {
-                    if menu_immediate_out(sys, btns, &mut settings.immediate_out[channel]).await {
+                    let is_deferred = || status.deferred.get()[channel];
+                    if menu_immediate_out(
+                        sys,
+                        btns,
+                        &mut settings.immediate_out[channel],
+                        is_deferred,
+                    )
+                    .await
+                    {
                         break;
                     }
                 }
@@ -154,6 +222,46 @@ pub async fn menu_main(
                         break;
                     }
                 }
+                MainMenuItem::DatedOut { channel } => {
+                    let entry = &mut settings.dated_out[channel];
+                    if menu_dated_entry(sys, btns, entry, &settings.time_zone).await {
+                        break;
+                    }
+                }
+                MainMenuItem::CycleOut { channel } => {
+                    if menu_cycle_entry(sys, btns, &mut settings.cycle_out[channel]).await {
+                        break;
+                    }
+                }
+                MainMenuItem::Thermostat { channel } => {
+                    if menu_thermostat(sys, btns, &mut settings.thermostat[channel]).await {
+                        break;
+                    }
+                }
+                MainMenuItem::OverrideMode { channel } => {
+                    let mode = &mut settings.override_mode[channel];
+                    let jitter = &mut settings.vacation_jitter[channel];
+                    if menu_override_mode(sys, btns, mode, jitter).await {
+                        break;
+                    }
+                }
+                MainMenuItem::Statistics { channel } => {
+                    let stats = || statistics.borrow().relays[channel];
+                    if menu_statistics(sys, btns, stats).await {
+                        break;
+                    }
+                }
+                MainMenuItem::Location => {
+                    let location = &mut settings.location;
+                    if menu_location(sys, btns, location, &mut settings.time_zone).await {
+                        break;
+                    }
+                }
+                MainMenuItem::EventLog => {
+                    if menu_event_log(sys, btns, event_log, &settings.time_zone).await {
+                        break;
+                    }
+                }
             },
             _ => {} //? should not occur
         }
@@ -161,16 +269,16 @@ pub async fn menu_main(
 }
 
 /* obsolete
-fn get_index_last_schedulable_entry(entries: &[Option<ScheduledOutEntry>;MAX_SCHEDULED_ENTRIES]) -> usize {
+fn get_index_last_schedulable_entry(entries: &[Option<ScheduledOutEntry>;NUM_SLOTS]) -> usize {
     let mut count = 0;
     for k in entries {
         if k.is_none() { break }
         count += 1;
     }
-    usize::min(count,MAX_SCHEDULED_ENTRIES)
+    usize::min(count,NUM_SLOTS)
 }
 
-fn is_index_last_schedulable_entry(entries: &[Option<ScheduledOutEntry>;MAX_SCHEDULED_ENTRIES], i: usize) -> bool {
-    i == MAX_SCHEDULED_ENTRIES || entries[i].is_none()
+fn is_index_last_schedulable_entry(entries: &[Option<ScheduledOutEntry>;NUM_SLOTS], i: usize) -> bool {
+    i == NUM_SLOTS || entries[i].is_none()
 }
 */
@@ -1,5 +1,12 @@
+mod cycle_entry;
+mod dated_entry;
+mod event_log;
 mod immediate_out;
+mod location;
 mod main;
+mod override_mode;
 mod scheduled_entry;
+mod statistics;
+mod thermostat;
 
 pub use main::menu_main;
@@ -0,0 +1,71 @@
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::ui::components::{
+    choose_duration, BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT,
+};
+use crate::ui::display::run_blinking_led_matrix;
+use crate::OverrideMode;
+use enum_iterator::{next_cycle, previous_cycle};
+use greaheisl_async::{AccessTiming, DurationMillis, Timer};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+fn print_override_mode(matrix: ImageRegionMut, mode: OverrideMode) {
+    let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
+    let text = match mode {
+        OverrideMode::Auto => "AUT", // "Automatik" = timers decide
+        OverrideMode::ForcedOn => "EIN",
+        OverrideMode::ForcedOff => "AUS",
+        OverrideMode::Vacation => "URL", // "Urlaub" = vacation
+    };
+    printer.print_str(text).unwrap();
+}
+
+/// lets the user pick the override mode of a relay
+///
+/// The current mode blinks right away.
+/// After picking the vacation mode, the user chooses the bound `jitter`
+/// of its random shifts; escaping from there leads back to the modes.
+pub async fn menu_override_mode(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    setting: &mut OverrideMode,
+    jitter: &mut DurationMillis,
+) -> bool {
+    let mut mode = *setting;
+    loop {
+        let mut blink_matrices = [Image::<12, 8, 3>::zero(), Image::<12, 8, 3>::zero()];
+        print_override_mode(blink_matrices[0].as_region_mut(), mode);
+        run_blinking_led_matrix(
+            sys,
+            &blink_matrices,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                mode = previous_cycle(&mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                mode = next_cycle(&mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                run_blinking_led_matrix(sys, &blink_matrices, BLINK_DELAY_CONFIRM_VALUE, async {
+                    Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+                })
+                .await;
+                *setting = mode;
+                if mode == OverrideMode::Vacation && !choose_duration(sys, btns, jitter).await {
+                    continue;
+                }
+                break true;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,14 +1,17 @@
 use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
 use crate::system::AccessLedMatrix;
-use crate::ScheduledOutEntry;
+use crate::ui::display::run_blinking_led_matrix;
+use crate::{ScheduledOutEntry, StartMode};
 use greaheisl_async::AccessTiming;
 use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
 use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
-use greaheisl_bitvecimg::ImageRegionMut;
-use greaheisl_button_processor::ButtonEvent;
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
 
 use crate::ui::components::{
-    choose_duration, choose_time, selection, DisplayImage, SelectionResponse, SelectionState,
+    choose_duration, choose_signed, choose_time, choose_weekdays, selection, DisplayImage,
+    SelectionResponse, SelectionState, BLINK_DELAY_CHANGE_VALUE, MENU_TIMEOUT,
+    SCHEDULE_WEEKDAY_CHOICES,
 };
 
 use enum_iterator::{next_cycle, previous_cycle, Sequence};
@@ -18,6 +21,7 @@ enum MenuState {
     #[default]
     Duration,
     StartTime,
+    Weekdays,
 }
 
 impl SelectionState for MenuState {
@@ -54,6 +58,9 @@ impl DisplayImage for MenuState {
             MenuState::StartTime => {
                 printer.print_str("STA").unwrap();
             }
+            MenuState::Weekdays => {
+                printer.print_str("TAG").unwrap();
+            }
         }
     }
 }
@@ -76,11 +83,16 @@ pub async fn menu_scheduled_entry(
                     }
                 }
                 MenuState::StartTime => {
-                    let response = choose_time(
+                    let SelectionResponse::Back = choose_start(sys, btns, settings).await else {
+                        break true;
+                    };
+                }
+                MenuState::Weekdays => {
+                    let response = choose_weekdays(
                         sys,
                         btns,
-                        &mut settings.start_hour,
-                        &mut settings.start_minute,
+                        &mut settings.weekdays,
+                        &SCHEDULE_WEEKDAY_CHOICES,
                     )
                     .await;
                     let SelectionResponse::Back = response else {
@@ -92,3 +104,91 @@ pub async fn menu_scheduled_entry(
         }
     }
 }
+
+/// lets the user choose the start mode, followed by the time of day
+/// or the offset from sunrise or sunset
+async fn choose_start(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    settings: &mut ScheduledOutEntry,
+) -> SelectionResponse {
+    let mut start_mode = settings.start_mode;
+    loop {
+        let response = choose_start_mode(sys, btns, &mut start_mode).await;
+        let SelectionResponse::Ok = response else {
+            return response;
+        };
+        let response = match start_mode {
+            StartMode::Clock => {
+                choose_time(
+                    sys,
+                    btns,
+                    &mut settings.start_hour,
+                    &mut settings.start_minute,
+                )
+                .await
+            }
+            // "vor" = before, "nach" = after
+            StartMode::Sunrise | StartMode::Sunset => {
+                choose_signed(
+                    sys,
+                    btns,
+                    &mut settings.sun_offset,
+                    ScheduledOutEntry::MAX_SUN_OFFSET,
+                    5,
+                    ["V", "N"],
+                )
+                .await
+            }
+        };
+        match response {
+            SelectionResponse::Back => {} // back to the start mode
+            SelectionResponse::Ok => {
+                settings.start_mode = start_mode;
+                return response;
+            }
+            SelectionResponse::Timeout => return response,
+        }
+    }
+}
+
+fn print_start_mode(matrix: ImageRegionMut, mode: StartMode) {
+    let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
+    let text = match mode {
+        StartMode::Clock => "UHR",   // "Uhrzeit" = time of day
+        StartMode::Sunrise => "AUF", // "Sonnenaufgang" = sunrise
+        StartMode::Sunset => "UNT",  // "Sonnenuntergang" = sunset
+    };
+    printer.print_str(text).unwrap();
+}
+
+/// lets the user pick what the start time refers to
+async fn choose_start_mode(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    mode: &mut StartMode,
+) -> SelectionResponse {
+    loop {
+        let mut blink_matrices = [Image::<12, 8, 3>::zero(), Image::<12, 8, 3>::zero()];
+        print_start_mode(blink_matrices[0].as_region_mut(), *mode);
+        run_blinking_led_matrix(
+            sys,
+            &blink_matrices,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                *mode = previous_cycle(mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                *mode = next_cycle(mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => break SelectionResponse::Ok,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::statistics::RelayStats;
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use greaheisl_async::AccessTiming;
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+use crate::ui::components::{
+    print_duration, selection, DisplayImage, SelectionState, MENU_TIMEOUT,
+};
+
+use enum_iterator::{next_cycle, previous_cycle, Sequence};
+
+#[derive(Default, Clone, Copy, Sequence)]
+enum MenuState {
+    #[default]
+    Total,
+    Today,
+    Yesterday,
+    SwitchCount,
+}
+
+impl SelectionState for MenuState {
+    type SelectionItem = Self;
+
+    fn item(&self) -> &Self::SelectionItem {
+        self
+    }
+
+    fn next(&mut self) -> bool {
+        let Some(new_value) = next_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let Some(new_value) = previous_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+}
+
+impl DisplayImage for MenuState {
+    fn display_image(&self, canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+        match self {
+            MenuState::Total => {
+                printer.print_str("SUM").unwrap(); // "Summe" = total
+            }
+            MenuState::Today => {
+                printer.print_str("HEU").unwrap(); // "heute" = today
+            }
+            MenuState::Yesterday => {
+                printer.print_str("GST").unwrap(); // "gestern" = yesterday
+            }
+            MenuState::SwitchCount => {
+                printer.print_str("SCH").unwrap(); // "Schaltungen" = switchings
+            }
+        }
+    }
+}
+
+/// prints a count with at most three digits, in thousands (`K`) or millions if needed
+fn print_count(canvas: ImageRegionMut, count: u32) {
+    let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+    match count {
+        0..=9 => printer.print_uint::<_, 1>(count),
+        10..=99 => printer.print_uint::<_, 2>(count),
+        100..=999 => printer.print_uint::<_, 3>(count),
+        1000..=99_999 => printer
+            .print_uint::<_, 2>(count / 1000)
+            .and_then(|_| printer.print_str("K")),
+        _ => printer
+            .print_uint::<_, 2>((count / 1_000_000).min(99))
+            .and_then(|_| printer.print_str("M")),
+    }
+    .unwrap();
+}
+
+/// shows the on-times and the switch count of a relay
+///
+/// `stats` provides the current counters whenever a value is shown.
+pub async fn menu_statistics(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    stats: impl Fn() -> RelayStats,
+) -> bool {
+    let mut current_item = MenuState::default();
+    loop {
+        selection(sys, btns, &mut current_item).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                let stats = stats();
+                let mut matrix = Image::<12, 8, 3>::zero();
+                match current_item {
+                    MenuState::Total => {
+                        print_duration(matrix.as_region_mut(), stats.total_on as i64)
+                    }
+                    MenuState::Today => print_duration(matrix.as_region_mut(), stats.today),
+                    MenuState::Yesterday => print_duration(matrix.as_region_mut(), stats.yesterday),
+                    MenuState::SwitchCount => {
+                        print_count(matrix.as_region_mut(), stats.switch_count)
+                    }
+                }
+                sys.set_led_matrix(&matrix.0.into_inner());
+                wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT).await;
+                if let ButtonEvent::None = btns.event() {
+                    break true; // timeout => exit all menus
+                }
+            }
+            _ => {}
+        }
+    }
+}
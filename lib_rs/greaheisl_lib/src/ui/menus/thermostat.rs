@@ -0,0 +1,166 @@
+use crate::system::buttons::{ButtonFlags, SysButtonProcessor};
+use crate::system::AccessLedMatrix;
+use crate::ui::display::run_blinking_led_matrix;
+use crate::{ThermostatEntry, ThermostatMode};
+use greaheisl_async::{AccessTiming, Timer};
+use greaheisl_bitvecimg::font::fitzl_font::FitzlFontNarrowNum;
+use greaheisl_bitvecimg::font::typeset::{TextLinePrinter, TextPrinterTrait};
+use greaheisl_bitvecimg::{BitVecImgViewMut, Image, ImageRegionMut};
+use greaheisl_button_processor::{wait_button_press_or_timeout, ButtonEvent};
+
+use crate::ui::components::{
+    choose_signed, selection, DisplayImage, SelectionResponse, SelectionState,
+    BLINK_DELAY_CHANGE_VALUE, BLINK_DELAY_CONFIRM_VALUE, MENU_TIMEOUT,
+};
+
+use super::location::round_div;
+
+use enum_iterator::{next_cycle, previous_cycle, Sequence};
+
+/// the largest magnitude of a threshold that can be chosen, in units of the sensor
+const MAX_THRESHOLD: i16 = 150;
+
+#[derive(Default, Clone, Copy, Sequence)]
+enum MenuState {
+    #[default]
+    Mode,
+    OnThreshold,
+    OffThreshold,
+}
+
+impl SelectionState for MenuState {
+    type SelectionItem = Self;
+
+    fn item(&self) -> &Self::SelectionItem {
+        self
+    }
+
+    fn next(&mut self) -> bool {
+        let Some(new_value) = next_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        let Some(new_value) = previous_cycle(self) else {
+            return false;
+        };
+        *self = new_value;
+        true
+    }
+}
+
+impl DisplayImage for MenuState {
+    fn display_image(&self, canvas: ImageRegionMut) {
+        let mut printer = TextLinePrinter::new(canvas, FitzlFontNarrowNum {});
+        match self {
+            MenuState::Mode => {
+                printer.print_str("MOD").unwrap(); // "Modus" = mode
+            }
+            MenuState::OnThreshold => {
+                printer.print_str("EIN").unwrap();
+            }
+            MenuState::OffThreshold => {
+                printer.print_str("AUS").unwrap();
+            }
+        }
+    }
+}
+
+/// edits the thermostat of a relay
+///
+/// The thresholds are set in whole units of the sensor,
+/// with `M` for negative and `P` for positive values.
+/// Values that are not changed keep their precision.
+pub async fn menu_thermostat(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    settings: &mut ThermostatEntry,
+) -> bool {
+    let mut current_item = MenuState::default();
+    loop {
+        selection(sys, btns, &mut current_item).await;
+        match btns.event() {
+            ButtonEvent::None => break true, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break false, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                let value = match current_item {
+                    MenuState::Mode => {
+                        let SelectionResponse::Back =
+                            choose_thermostat_mode(sys, btns, &mut settings.mode).await
+                        else {
+                            break true;
+                        };
+                        continue;
+                    }
+                    MenuState::OnThreshold => &mut settings.on_threshold,
+                    MenuState::OffThreshold => &mut settings.off_threshold,
+                };
+                // "Minus"/"Plus"
+                let mut rounded = round_div(*value, 10);
+                let response =
+                    choose_signed(sys, btns, &mut rounded, MAX_THRESHOLD, 1, ["M", "P"]).await;
+                if rounded != round_div(*value, 10) {
+                    *value = rounded * 10;
+                }
+                let SelectionResponse::Back = response else {
+                    break true;
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+fn print_thermostat_mode(matrix: ImageRegionMut, mode: ThermostatMode) {
+    let mut printer = TextLinePrinter::new(matrix, FitzlFontNarrowNum {});
+    let text = match mode {
+        ThermostatMode::Off => "AUS",
+        ThermostatMode::Always => "IMM",    // "immer" = always
+        ThermostatMode::Scheduled => "ZPL", // "Zeitplan" = schedule
+    };
+    printer.print_str(text).unwrap();
+}
+
+/// lets the user pick when the thermostat switches its relay
+///
+/// The current mode blinks right away.
+async fn choose_thermostat_mode(
+    sys: &(impl AccessLedMatrix + AccessTiming),
+    btns: &SysButtonProcessor,
+    setting: &mut ThermostatMode,
+) -> SelectionResponse {
+    let mut mode = *setting;
+    loop {
+        let mut blink_matrices = [Image::<12, 8, 3>::zero(), Image::<12, 8, 3>::zero()];
+        print_thermostat_mode(blink_matrices[0].as_region_mut(), mode);
+        run_blinking_led_matrix(
+            sys,
+            &blink_matrices,
+            BLINK_DELAY_CHANGE_VALUE,
+            wait_button_press_or_timeout(sys, btns, MENU_TIMEOUT),
+        )
+        .await;
+        match btns.event() {
+            ButtonEvent::None => break SelectionResponse::Timeout, // timeout => exit all menus
+            ButtonEvent::Press(ButtonFlags::Escape) => break SelectionResponse::Back, // user wants to get back
+            ButtonEvent::Press(ButtonFlags::Prev) | ButtonEvent::Repeat(ButtonFlags::Prev) => {
+                mode = previous_cycle(&mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Next) | ButtonEvent::Repeat(ButtonFlags::Next) => {
+                mode = next_cycle(&mode).unwrap();
+            }
+            ButtonEvent::Press(ButtonFlags::Enter) => {
+                run_blinking_led_matrix(sys, &blink_matrices, BLINK_DELAY_CONFIRM_VALUE, async {
+                    Timer::new(sys, BLINK_DELAY_CONFIRM_VALUE * 5).wait().await;
+                })
+                .await;
+                *setting = mode;
+                break SelectionResponse::Ok;
+            }
+            _ => {}
+        }
+    }
+}
@@ -2,13 +2,17 @@
 
 use crate::system::buttons::{ButtonFlags, SysButtonProcessor, BUTTON_HOLD_DURATION};
 use crate::system::System;
-use crate::ui::components::{choose_time, SelectionResponse};
+use crate::ui::components::{choose_date, choose_time, SelectionResponse};
 use core::cell::RefCell;
+use greaheisl_async::Notify;
 use greaheisl_button_processor::{
     ButtonEvent, ButtonProcessorOptions, CheckHoldButton, CheckHoldButtonResult,
 };
 
-use crate::Settings;
+use crate::event_log::EventLog;
+use crate::statistics::RuntimeStats;
+use crate::system::{AccessPersistentStorage, AccessRtc};
+use crate::{set_clock, update_settings, OutputStatus, Settings};
 
 mod components;
 mod display;
@@ -18,43 +22,90 @@ mod menus;
 use idle_display::{IdleDisplay, IdleScenes};
 use menus::menu_main;
 
-pub async fn run_ui(sys: &impl System, settings: &RefCell<Settings>) {
+pub async fn run_ui<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &impl System<NUM_RELAYS>,
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    status: &OutputStatus<NUM_RELAYS>,
+    event_log: &EventLog,
+    statistics: &RefCell<RuntimeStats<NUM_RELAYS>>,
+) {
     // main loop
     let btns = SysButtonProcessor::new(ButtonProcessorOptions::default());
     btns.run(sys, async {
         let mut idle_display = IdleDisplay::new();
         loop {
-            idle_display.run(sys, &btns).await;
-            match btns.event() {
-                ButtonEvent::Press(ButtonFlags::Enter) => {
-                    let res = CheckHoldButton::new(sys, &btns, BUTTON_HOLD_DURATION)
-                        .wait()
+            let time_zone = settings.borrow().time_zone;
+            idle_display.run(sys, &btns, &time_zone).await;
+            if let ButtonEvent::Press(ButtonFlags::Enter) = btns.event() {
+                let res = CheckHoldButton::new(sys, &btns, BUTTON_HOLD_DURATION)
+                    .wait()
+                    .await;
+                match res {
+                    CheckHoldButtonResult::ReleaseEarly => {
+                        let original = settings.borrow().clone();
+                        let mut edited = original.clone();
+                        let relay_count = sys.relay_count();
+                        menu_main(
+                            sys,
+                            &btns,
+                            &mut edited,
+                            relay_count,
+                            status,
+                            event_log,
+                            statistics,
+                        )
                         .await;
-                    match res {
-                        CheckHoldButtonResult::ReleaseEarly => {
-                            let mut new_settings = settings.borrow().clone();
-                            menu_main(sys, &btns, &mut new_settings).await;
-                            *settings.borrow_mut() = new_settings;
-                        }
-                        CheckHoldButtonResult::Hold => {
-                            let mut time = sys.get_rtc();
-                            let IdleScenes::Clock = idle_display.idle_scene() else {
-                                continue;
-                            };
-                            let SelectionResponse::Ok =
-                                choose_time(sys, &btns, &mut time.hour, &mut time.minute).await
-                            else {
-                                continue;
-                            };
-                            time.second = 0;
-                            sys.set_rtc(&time);
-                        }
-                        CheckHoldButtonResult::Other => {}
+                        commit_menu_edits(
+                            sys,
+                            settings,
+                            settings_changed,
+                            event_log,
+                            &original,
+                            &edited,
+                        );
                     }
+                    CheckHoldButtonResult::Hold => {
+                        let mut time = time_zone.to_local(&sys.get_rtc());
+                        let IdleScenes::Clock = idle_display.idle_scene() else {
+                            continue;
+                        };
+                        let SelectionResponse::Ok =
+                            choose_time(sys, &btns, &mut time.hour, &mut time.minute).await
+                        else {
+                            continue;
+                        };
+                        let SelectionResponse::Ok =
+                            choose_date(sys, &btns, &mut time.year, &mut time.month, &mut time.day)
+                                .await
+                        else {
+                            continue;
+                        };
+                        // the day of the week is derived by the conversion to UTC
+                        time.second = 0;
+                        set_clock(sys, &time_zone.to_utc(&time), settings_changed, event_log);
+                    }
+                    CheckHoldButtonResult::Other => {}
                 }
-                _ => {}
             }
         }
     })
     .await;
 }
+
+/// takes over the settings edited in the menus, starting from `original`
+///
+/// Only the entries changed in the menus are replaced, so changes
+/// made through [`crate::commands`] while the menus were open are kept.
+pub(crate) fn commit_menu_edits<const NUM_RELAYS: usize, const NUM_SLOTS: usize>(
+    sys: &(impl AccessPersistentStorage + AccessRtc),
+    settings: &RefCell<Settings<NUM_RELAYS, NUM_SLOTS>>,
+    settings_changed: &Notify,
+    event_log: &EventLog,
+    original: &Settings<NUM_RELAYS, NUM_SLOTS>,
+    edited: &Settings<NUM_RELAYS, NUM_SLOTS>,
+) {
+    update_settings(sys, settings, settings_changed, event_log, |settings| {
+        settings.merge_changes(original, edited);
+    });
+}
@@ -0,0 +1,131 @@
+//! protection of the relays and the devices connected to them against short-cycling
+//!
+//! Compressors and contactors must not be switched on and off in quick succession,
+//! e.g., when schedules overlap or the user toggles a timer repeatedly.
+//! Transitions that would violate the [`WearLimits`] of a relay are deferred
+//! until they are permitted. Note that this also applies to the override modes:
+//! a relay forced off stays on until its minimum on-time has passed.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use greaheisl_async::{DurationMillis, InstantMillis};
+
+const MILLIS_PER_HOUR: DurationMillis = 60 * 60 * 1000;
+
+/// the wear limits of a single relay
+#[derive(Clone, Debug, Default)]
+pub struct WearLimits {
+    /// the minimum time in milliseconds the relay stays on once switched on
+    pub min_on: DurationMillis,
+    /// the minimum time in milliseconds the relay stays off once switched off
+    pub min_off: DurationMillis,
+    /// the maximum number of times the relay may be switched on within any hour
+    ///
+    /// Switching off is only restricted by [`Self::min_on`],
+    /// so a relay is never held on by this limit.
+    pub max_switches_per_hour: Option<u16>,
+}
+
+/// the outcome of [`WearState::apply`]
+pub(crate) struct WearDecision<const NUM_RELAYS: usize> {
+    /// the permitted relay states
+    pub states: [bool; NUM_RELAYS],
+    /// the relays whose requested transition is deferred
+    pub deferred: [bool; NUM_RELAYS],
+    /// the time until the next deferred transition may be permitted, if known
+    pub wake_up: Option<DurationMillis>,
+}
+
+/// enforces [`WearLimits`] over time
+pub(crate) struct WearState<const NUM_RELAYS: usize> {
+    limits: Vec<WearLimits>,
+    /// the states passed to the relays most recently
+    states: [bool; NUM_RELAYS],
+    /// when each relay was switched the last time
+    last_switch: [Option<InstantMillis>; NUM_RELAYS],
+    /// when each relay was switched on within the last hour
+    switch_ons: [VecDeque<InstantMillis>; NUM_RELAYS],
+}
+
+impl<const NUM_RELAYS: usize> WearState<NUM_RELAYS> {
+    /// `limits` are given by relay index; relays without an entry are not limited
+    pub fn new(limits: Vec<WearLimits>) -> Self {
+        Self {
+            limits,
+            states: [false; NUM_RELAYS],
+            last_switch: [None; NUM_RELAYS],
+            switch_ons: core::array::from_fn(|_| VecDeque::new()),
+        }
+    }
+
+    /// holds back the requested transitions that are not permitted yet
+    pub fn apply(
+        &mut self,
+        requested: &[bool; NUM_RELAYS],
+        now: InstantMillis,
+    ) -> WearDecision<NUM_RELAYS> {
+        let mut decision = WearDecision {
+            states: *requested,
+            deferred: [false; NUM_RELAYS],
+            wake_up: None,
+        };
+        for (k, limits) in self.limits.iter().enumerate().take(NUM_RELAYS) {
+            if requested[k] == self.states[k] {
+                continue;
+            }
+            let since_switch = self.last_switch[k].map(|last| now - last);
+            let min_time = if requested[k] {
+                limits.min_off
+            } else {
+                limits.min_on
+            };
+            let mut wait = since_switch.map_or(0, |since| min_time - since);
+            let mut forever = false;
+            if requested[k] {
+                let switch_ons = &mut self.switch_ons[k];
+                while switch_ons
+                    .front()
+                    .is_some_and(|&t| now - t >= MILLIS_PER_HOUR)
+                {
+                    switch_ons.pop_front();
+                }
+                if let Some(max) = limits.max_switches_per_hour {
+                    if switch_ons.len() >= max as usize {
+                        match switch_ons.front() {
+                            Some(&t) => wait = wait.max(MILLIS_PER_HOUR - (now - t)),
+                            // a limit of 0 means the relay never switches on
+                            None => forever = true,
+                        }
+                    }
+                }
+            }
+            if wait <= 0 && !forever {
+                continue;
+            }
+            decision.states[k] = self.states[k];
+            decision.deferred[k] = true;
+            if !forever {
+                decision.wake_up = Some(decision.wake_up.map_or(wait, |w| w.min(wait)));
+            }
+        }
+        decision
+    }
+
+    /// keeps track of the states actually passed to the relays
+    pub fn record(&mut self, states: &[bool; NUM_RELAYS], now: InstantMillis) {
+        for (k, &is_on) in states.iter().enumerate() {
+            if is_on == self.states[k] {
+                continue;
+            }
+            self.last_switch[k] = Some(now);
+            let is_counted = self
+                .limits
+                .get(k)
+                .is_some_and(|limits| limits.max_switches_per_hour.is_some());
+            if is_on && is_counted {
+                self.switch_ons[k].push_back(now);
+            }
+        }
+        self.states = *states;
+    }
+}
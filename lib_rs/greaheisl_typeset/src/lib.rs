@@ -447,6 +447,7 @@ where
     /// * `notdef_glyph` is set to the default defined by the font.
     /// *  The initial position of the `typesetter` is chosen such that
     ///    the origin of the text box is at (0,0).
+    ///
     /// You can change these values afterwards.
     pub fn new(canvas: G, font: F) -> Self {
         let writing_dir = font.default_writing_direction();
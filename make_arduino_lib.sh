@@ -11,9 +11,22 @@ ARDUIDE_LIB_DIR=./prog/libraries/greaheisl_lib
 LIB_SKELETON_DIR=./arduino_lib_skeleton
 WORKSPACE_DIR=./lib_rs
 
+# the maximum number of relays and the number of scheduled entries per relay,
+# from 1 to 9 each; they are compiled into the library and written to greaheisl_config.h
+export GREAHEISL_MAX_RELAYS=${GREAHEISL_MAX_RELAYS:-8}
+export GREAHEISL_NUM_SLOTS=${GREAHEISL_NUM_SLOTS:-3}
+
 cd $WORKSPACE_DIR/greaheisl_lib
 cargo build --release --target $RUST_ARCH_NAME --no-default-features
+mkdir -p ../target/generated
 cbindgen --config cbindgen.toml --crate greaheisl_lib --output ../target/generated/greaheisl_lib.h
+cat > ../target/generated/greaheisl_config.h <<EOF
+#pragma once
+
+// the configuration libgreaheisl_lib.a has been built with by make_arduino_lib.sh
+#define GREAHEISL_MAX_RELAYS $GREAHEISL_MAX_RELAYS
+#define GREAHEISL_NUM_SLOTS $GREAHEISL_NUM_SLOTS
+EOF
 cd $PKG_ROOT_DIR
 
 mkdir -p $ARDUIDE_LIB_DIR
@@ -15,6 +15,10 @@
 // Therefore, I cannot use it. Include file commented out.
 // #include "RTC.h"
 
+// The settings are kept in the emulated EEPROM (data flash)
+// so they survive a power loss.
+#include <EEPROM.h>
+
 // This is the interface to our own Rust library provided
 // in this repository.
 #include "greaheisl_lib.h"
@@ -31,13 +35,27 @@ const pin_size_t INPUT_BUTTON_PINS[NUM_BUTTONS] = {
   5  // Enter
 };
 
+// This defines the pins we use for digital inputs, e.g. float switches
+// or motion sensors, pulling the pin to ground when active.
+// Add or remove pins as needed, at most 8.
+const pin_size_t DIGITAL_INPUT_PINS[] = {
+  10,
+  11
+};
+const size_t NUM_INPUTS = sizeof(DIGITAL_INPUT_PINS) / sizeof(DIGITAL_INPUT_PINS[0]);
+static_assert(NUM_INPUTS <= 8, "too many inputs");
+
 // This defines the pins we use for our relay output.
-const pin_size_t OUTPUT_RELAY_PINS[NUM_RELAYS] = {
+// Add or remove pins to match the number of relays on your board,
+// at most GREAHEISL_MAX_RELAYS.
+const pin_size_t OUTPUT_RELAY_PINS[] = {
   6, 
   7, 
   8, 
   9  
 };
+const size_t NUM_RELAYS = sizeof(OUTPUT_RELAY_PINS) / sizeof(OUTPUT_RELAY_PINS[0]);
+static_assert(NUM_RELAYS <= GREAHEISL_MAX_RELAYS, "too many relays");
 
 // Opaque handle initialized and used by `greaheisl_lib`
 GreaheislExecutor *greaheisl;
@@ -45,6 +63,31 @@ GreaheislExecutor *greaheisl;
 // Time offset needed to use the millis() counter
 // as a substitute for the real time clock.
 unsigned long rtc_millis_offset = 0;
+// Day (counted from 1970-01-01) when the clock was set.
+long rtc_day_offset = 0;
+
+// days since 1970-01-01, see https://howardhinnant.github.io/date_algorithms.html
+long days_from_civil(long year, unsigned month, unsigned day) {
+  year -= month <= 2;
+  long era = (year >= 0 ? year : year - 399) / 400;
+  unsigned year_of_era = year - era * 400;
+  unsigned day_of_year = (153 * (month > 2 ? month - 3 : month + 9) + 2) / 5 + day - 1;
+  unsigned day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  return era * 146097 + (long)day_of_era - 719468;
+}
+
+// inverse of `days_from_civil`
+void civil_from_days(long days, RtcTime *rtc_time) {
+  days += 719468;
+  long era = (days >= 0 ? days : days - 146096) / 146097;
+  unsigned day_of_era = days - era * 146097;
+  unsigned year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  unsigned day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  unsigned mp = (5 * day_of_year + 2) / 153;
+  rtc_time->day = day_of_year - (153 * mp + 2) / 5 + 1;
+  rtc_time->month = mp < 10 ? mp + 3 : mp - 9;
+  rtc_time->year = year_of_era + era * 400 + (rtc_time->month <= 2);
+}
 
 /* -------------------------------------------------- 
  *
@@ -68,7 +111,8 @@ void *my_aligned_alloc(size_t align, size_t size) {
   return std::malloc(size);
 }
 
-// read the clock
+// read the clock, which is kept in UTC;
+// `greaheisl_lib` converts it into local time
 void callback_get_rtc(RtcTime *rtc_time) {
   /* My Arduino RTC is terribly inaccurate; use millis() as a preliminary substitute
   RTCTime currenttime;
@@ -76,8 +120,13 @@ void callback_get_rtc(RtcTime *rtc_time) {
   rtc_time->hour = currenttime.getHour();
   rtc_time->minute = currenttime.getMinutes();
   rtc_time->second = currenttime.getSeconds();
+  rtc_time->day = currenttime.getDayOfMonth();
+  rtc_time->month = Month2int(currenttime.getMonth());
+  rtc_time->year = currenttime.getYear();
+  // the Arduino library counts the days of the week from Sunday = 0
+  rtc_time->weekday = ((int)currenttime.getDayOfWeek() + 6) % 7;
   */
-  // convert the millisecond information into hours, minutes, seconds
+  // convert the millisecond information into date, hours, minutes, seconds
   unsigned long seconds = (millis() + rtc_millis_offset)/1000;
   unsigned long minutes = seconds/60;
   unsigned long hours = minutes/60;
@@ -85,9 +134,12 @@ void callback_get_rtc(RtcTime *rtc_time) {
   rtc_time->second = seconds - minutes*60;
   rtc_time->minute = minutes - hours*60;
   rtc_time->hour = hours - days*24;
+  civil_from_days(rtc_day_offset + days, rtc_time);
+  // 1970-01-01 was a Thursday
+  rtc_time->weekday = (rtc_day_offset + days + 3) % 7;
 }
 
-// set the clock
+// set the clock, in UTC
 void callback_set_rtc(const RtcTime* rtc_time) {
   /* My Arduino RTC is terribly inaccurate; use millis() as a preliminary substitute
   RTCTime newtime;
@@ -95,6 +147,10 @@ void callback_set_rtc(const RtcTime* rtc_time) {
   newtime.setHour(rtc_time->hour);
   newtime.setMinute(rtc_time->minute);
   newtime.setSecond(rtc_time->second);
+  newtime.setDayOfMonth(rtc_time->day);
+  newtime.setMonthOfYear((Month)(rtc_time->month - 1));
+  newtime.setYear(rtc_time->year);
+  newtime.setDayOfWeek((DayOfWeek)((rtc_time->weekday + 1) % 7));
   RTC.setTime(newtime);
   */
   // compute the offset such that we now get the desired time 
@@ -102,6 +158,7 @@ void callback_set_rtc(const RtcTime* rtc_time) {
                            + 1000ul * 60ul * (unsigned long)rtc_time->minute
                            + 1000ul * 60ul * 60ul * (unsigned long)rtc_time->hour;
   rtc_millis_offset = new_millis - millis();
+  rtc_day_offset = days_from_civil(rtc_time->year, rtc_time->month, rtc_time->day);
 }
 
 // set the LED bitmap
@@ -123,15 +180,181 @@ uint8_t callback_get_button_flags() {
   return result; 
 }
 
+// retrieve flags which digital inputs are active
+uint8_t callback_get_input_flags() {
+  uint8_t result = 0;
+  for (unsigned k=0;k<NUM_INPUTS;k++) {
+    if (digitalRead(DIGITAL_INPUT_PINS[k]) == LOW) {
+      result |= 1 << k;
+    }
+  }
+  return result;
+}
+
+// An analog sensor for the thermostats, e.g. a TMP36 temperature sensor
+// on pin A0, would be read like this (in tenths of a degree):
+//
+// bool callback_get_analog_value(int16_t *value) {
+//   long millivolts = analogRead(A0) * 5000L / 1023;
+//   *value = millivolts - 500;
+//   return true;
+// }
+//
+// and passed instead of NULL below.
+
 // set the output pins for the relays
-void callback_set_relay_states(const bool (*relay_states)[NUM_RELAYS]) {
-  for (unsigned k=0;k<NUM_RELAYS;k++) {
-    digitalWrite(OUTPUT_RELAY_PINS[k], (*relay_states)[k] ? HIGH : LOW );  
+void callback_set_relay_states(const bool *relay_states, size_t count) {
+  for (unsigned k=0;k<count;k++) {
+    digitalWrite(OUTPUT_RELAY_PINS[k], relay_states[k] ? HIGH : LOW );  
+  }
+}
+
+// The settings blob and the statistics blob are stored in the EEPROM,
+// each preceded by its length (2 bytes, little endian).
+const int SETTINGS_EEPROM_ADDRESS = 0;
+const int STATISTICS_EEPROM_ADDRESS = 1024;
+
+// read a blob stored at `address`
+size_t load_eeprom_blob(int address, uint8_t *buffer, size_t capacity) {
+  size_t len = EEPROM.read(address)
+             | ((size_t)EEPROM.read(address + 1) << 8);
+  if (len > capacity || address + 2 + len > EEPROM.length()) {
+    // nothing valid stored, e.g. erased EEPROM
+    return 0;
+  }
+  for (size_t k=0;k<len;k++) {
+    buffer[k] = EEPROM.read(address + 2 + k);
+  }
+  return len;
+}
+
+// store a blob at `address`, if it fits below `end_address`
+void store_eeprom_blob(int address, int end_address, const uint8_t *data, size_t len) {
+  if (address + 2 + len > min((size_t)end_address, (size_t)EEPROM.length())) {
+    Serial.println("Blob does not fit into EEPROM.");
+    return;
+  }
+  // `update` only writes bytes that have changed, which reduces wear
+  EEPROM.update(address, len & 0xff);
+  EEPROM.update(address + 1, (len >> 8) & 0xff);
+  for (size_t k=0;k<len;k++) {
+    EEPROM.update(address + 2 + k, data[k]);
+  }
+}
+
+// read the stored settings
+size_t callback_load_settings(uint8_t *buffer, size_t capacity) {
+  return load_eeprom_blob(SETTINGS_EEPROM_ADDRESS, buffer, capacity);
+}
+
+// store the settings
+void callback_store_settings(const uint8_t *data, size_t len) {
+  store_eeprom_blob(SETTINGS_EEPROM_ADDRESS, STATISTICS_EEPROM_ADDRESS, data, len);
+}
+
+// read the stored on-times of the relays
+size_t callback_load_statistics(uint8_t *buffer, size_t capacity) {
+  return load_eeprom_blob(STATISTICS_EEPROM_ADDRESS, buffer, capacity);
+}
+
+// store the on-times of the relays; this happens about once an hour,
+// so replace this callback by NULL if the data flash should be spared
+void callback_store_statistics(const uint8_t *data, size_t len) {
+  store_eeprom_blob(STATISTICS_EEPROM_ADDRESS, EEPROM.length(), data, len);
+}
+
+// read a byte of a text command sent over USB serial, e.g. from the serial monitor
+bool callback_read_serial(uint8_t *byte) {
+  int value = Serial.read();
+  if (value < 0) {
+    return false;
   }
+  *byte = (uint8_t)value;
+  return true;
+}
+
+// send the answer to a text command; the entries of the event log
+// are printed over the same connection, between the answers
+void callback_write_serial(const uint8_t *data, size_t len) {
+  Serial.write(data, len);
 }
 
 // collect all callbacks in a structure we can pass to `greaheisl_lib`
-const GreaheislCallbacks callbacks = { callback_get_rtc, callback_set_rtc, callback_set_led_matrix, callback_get_button_flags, callback_set_relay_states };
+const GreaheislCallbacks callbacks = { callback_get_rtc, callback_set_rtc, callback_set_led_matrix, callback_get_button_flags, callback_set_relay_states, callback_load_settings, callback_store_settings, callback_get_input_flags, NULL, callback_load_statistics, callback_store_statistics, callback_read_serial, callback_write_serial };
+
+// Restrictions on which relays may be on at the same time.
+// Each group is a bit mask of relays (bit k = relay k), of which
+// at most one may be on. E.g., for a motor driven in both directions
+// by relays 0 and 1, use { 0b0011 } and a dead time of some 500 ms.
+// By default, all relays can be switched freely.
+const uint32_t EXCLUSIVE_GROUPS[] = { 0 };
+const GreaheislInterlocks interlocks = {
+  EXCLUSIVE_GROUPS,
+  sizeof(EXCLUSIVE_GROUPS) / sizeof(EXCLUSIVE_GROUPS[0]),
+  0, // maximum number of relays on at the same time, 0 = no limit
+  0  // dead time in milliseconds
+};
+
+// Protection against short-cycling, for each relay:
+// minimum on-time and off-time in milliseconds, and the maximum
+// number of times the relay may be switched on within an hour (0 = no limit).
+// E.g., { 60000, 180000, 6 } for a compressor.
+// By default, the relays are not limited.
+const GreaheislWearLimits WEAR_LIMITS[NUM_RELAYS] = {};
+
+// Relays due to be switched on at the same time are switched on
+// one after the other, this many milliseconds apart, so the inrush
+// currents of connected pumps or motors do not add up. 0 = no delay.
+const DurationMillis STAGGER_INTERVAL = 0;
+
+// The seed of the random shifts of the scheduled timers in vacation mode.
+// 0 = derive a new seed from the real time clock at every start.
+const uint64_t JITTER_SEED = 0;
+
+// Relays switched by the digital inputs: input index, relay index, and
+// 0 to keep the relay on while the input is active, or the time in milliseconds
+// the relay stays on after the input becomes active.
+// E.g., { 1, 0, 0 } keeps relay 0 on while input 1 is active,
+// and { 0, 2, 600000 } switches relay 2 on for 10 minutes when input 0 becomes active.
+// By default, the inputs switch nothing. After adding rules, set
+// NUM_INPUT_RULES to sizeof(INPUT_RULES) / sizeof(INPUT_RULES[0]).
+const GreaheislInputRule INPUT_RULES[] = { { 0, 0, 0 } };
+const size_t NUM_INPUT_RULES = 0;
+
+// Time in milliseconds a digital input must keep its new level
+// before the change counts, to ignore contact bounce.
+const DurationMillis INPUT_DEBOUNCE = 50;
+
+const GreaheislOutputConfig output_config = {
+  &interlocks, WEAR_LIMITS, STAGGER_INTERVAL, JITTER_SEED,
+  INPUT_RULES, NUM_INPUT_RULES, INPUT_DEBOUNCE
+};
+
+// Prints an entry of the event log of `greaheisl_lib` over serial, e.g.
+// "#12 2024-05-01 04:30:00 UTC relay 2 on (scheduled 1)".
+// A gap in the numbers means that entries were overwritten before being printed.
+void print_log_entry(const GreaheislLogEntry *entry) {
+  static const char *const KIND_NAMES[] = { "on", "off", "clock set", "settings stored" };
+  static const char *const CAUSE_NAMES[] = {
+    "", "immediate", "scheduled", "dated", "cycle", "thermostat", "input", "override", "interlock"
+  };
+  char line[96];
+  const RtcTime &t = entry->time;
+  int len = snprintf(line, sizeof(line), "#%lu %04u-%02u-%02u %02u:%02u:%02u UTC ",
+    (unsigned long)entry->sequence, t.year, t.month, t.day, t.hour, t.minute, t.second);
+  uint8_t kind = (uint8_t)entry->kind;
+  if (entry->kind == GreaheislEventKind::RelayOn || entry->kind == GreaheislEventKind::RelayOff) {
+    len += snprintf(line + len, sizeof(line) - len, "relay %u %s (%s", entry->relay + 1,
+      KIND_NAMES[kind], CAUSE_NAMES[(uint8_t)entry->cause]);
+    if (entry->cause == GreaheislCause::Scheduled) {
+      len += snprintf(line + len, sizeof(line) - len, " %u", entry->slot + 1);
+    }
+    snprintf(line + len, sizeof(line) - len, ")");
+  } else {
+    snprintf(line + len, sizeof(line) - len, "%s", KIND_NAMES[kind]);
+  }
+  Serial.println(line);
+}
 
 /* -------------------------------------------------- 
  *
@@ -149,13 +372,17 @@ void setup() {
   for (unsigned k=0;k<NUM_BUTTONS;k++) {
     pinMode(INPUT_BUTTON_PINS[k],INPUT_PULLUP);
   }
+  for (unsigned k=0;k<NUM_INPUTS;k++) {
+    pinMode(DIGITAL_INPUT_PINS[k],INPUT_PULLUP);
+  }
   for (unsigned k=0;k<NUM_RELAYS;k++) {
     pinMode(OUTPUT_RELAY_PINS[k],OUTPUT);
     digitalWrite(OUTPUT_RELAY_PINS[k],LOW);
   }
   
   /*
-  RTCTime mytime(25, Month::AUGUST, 2022, 14, 37, 00, DayOfWeek::THURSDAY, SaveLight::SAVING_TIME_ACTIVE);
+  // the RTC runs in UTC, daylight saving time is applied by `greaheisl_lib`
+  RTCTime mytime(25, Month::AUGUST, 2022, 12, 37, 00, DayOfWeek::THURSDAY, SaveLight::SAVING_TIME_INACTIVE);
   if(!RTC.isRunning()) {
     RTC.setTime(mytime);
   }
@@ -166,7 +393,7 @@ void setup() {
   set_allocator_functions(my_aligned_alloc, std::free);
   Serial.println("Starting setup.");
   // initialize the library
-  greaheisl = greaheisl_init(&callbacks,millis());
+  greaheisl = greaheisl_init(&callbacks,NUM_RELAYS,&output_config,millis());
   Serial.println("Done setting up.");
 }
 
@@ -185,7 +412,33 @@ void loop() {
   // needs to be pressed in order to wake up
   // the system from a power saving mode.
   // This is not implemented, yet.
-  unsigned long delay_time = greaheisl_step(greaheisl,millis(),1);
+  // Signal 1 (buttons) is only passed if a button has been pressed or released.
+  // Signal 2 (digital inputs) is only passed if an input has changed,
+  // since it wakes up the evaluation of all timers.
+  // Signal 4 (serial) is passed while bytes are waiting to be read.
+  static uint8_t last_button_flags = 0;
+  uint8_t button_flags = callback_get_button_flags();
+  uint8_t signals = 0;
+  if (button_flags != last_button_flags) {
+    signals |= 1;
+    last_button_flags = button_flags;
+  }
+  static uint8_t last_input_flags = 0;
+  uint8_t input_flags = callback_get_input_flags();
+  if (input_flags != last_input_flags) {
+    signals |= 2;
+    last_input_flags = input_flags;
+  }
+  if (Serial.available() > 0) {
+    signals |= 4;
+  }
+  unsigned long delay_time = greaheisl_step(greaheisl,millis(),signals);
+  // Print what has happened since the last call.
+  static uint32_t log_sequence = 0;
+  GreaheislLogEntry log_entry;
+  while (greaheisl_event_log_read(greaheisl, &log_sequence, &log_entry)) {
+    print_log_entry(&log_entry);
+  }
   // never wait longer than 20 milliseconds, because we need to check for button state change
   delay_time = min(delay_time,10); 
   delay(delay_time);